@@ -0,0 +1,410 @@
+/*! The baseline computer players
+*
+* Three players of increasing strength, each able to play either side:
+*
+* - `RandomPlayer` plays any legal action, from a seed so its games can be replayed
+* - `GreedyPlayer` only looks at the next turn: the resistance places where it makes the most
+*   progress towards surrounding its temp tiles, and the suppressor covers the tiles around the temp
+*   tiles closest to being captured
+* - `HeuristicPlayer` scores every tile on a weighted set of features, so it can be tuned
+*/
+
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+use rand::{RngExt, SeedableRng};
+
+use crate::ai::{Player, PlayerError};
+use crate::gameplay::actors::{ResistanceAction, SuppressionAction};
+use crate::gameplay::rules::number_of_suppressed_neighbors;
+use crate::gameplay::suppression_zone::{ZoneRules, ROTATIONS};
+use crate::gameplay::tilemap::{Coord, CoordinateSystem, TileKind, TileMap, TileState};
+use crate::gameplay::views::{ResistanceView, SuppressionView};
+
+/// Every temp tile in the map, hidden ones included.
+pub(crate) fn temps(tiles: &TileMap) -> Vec<Coord> {
+    tiles.iter()
+        .filter(|tile| matches!(tile.state().kind(), TileKind::TemporaryResistance | TileKind::HiddenResistance))
+        .map(|tile| tile.coord())
+        .collect()
+}
+
+/// The tiles nothing has been placed on.
+pub(crate) fn open_tiles(tiles: &TileMap) -> Vec<Coord> {
+    tiles.board().coords().filter(|coord| tiles.state_at(coord).is_none()).collect()
+}
+
+/// The suppressed neighbors left across a set of temp tiles, and how many of them have none left
+fn surround_state(tiles: &TileMap, temps: &[Coord]) -> (u32, u32) {
+    temps.iter().fold((0, 0), |(left, captured), coord| {
+        let suppressed = number_of_suppressed_neighbors(tiles, coord, temps) as u32;
+        (left + suppressed, captured + (suppressed == 0) as u32)
+    })
+}
+
+/// What placing a temp tile would do for the resistance
+///
+/// Returns how many suppressed neighbors it takes away from the temp tiles, itself included as if it
+/// started with all of its neighbors suppressed, and how many temp tiles it would capture.
+pub(crate) fn placement_progress(tiles: &TileMap, temps: &[Coord], coord: Coord) -> (u32, u32) {
+    let (left_before, captured_before) = surround_state(tiles, temps);
+    let placed: Vec<Coord> = temps.iter().cloned().chain([coord]).collect();
+    let (left_after, captured_after) = surround_state(tiles, &placed);
+    let neighbors = tiles.board().neighbors_of(&coord).len() as u32;
+    (left_before + neighbors - left_after, captured_after.saturating_sub(captured_before))
+}
+
+/// How much the suppressor wants to cover an open tile
+///
+/// Returns how threatening the tile is, summing the temp tiles next to it weighted by how few
+/// suppressed neighbors each has left, and how many temp tiles a placement there would capture.
+pub(crate) fn cover_value(tiles: &TileMap, temps: &[Coord], coord: &Coord) -> (f32, u32) {
+    tiles.board().neighbors_of(coord).iter()
+        .filter(|neighbor| temps.contains(neighbor))
+        .fold((0.0, 0), |(threat, captures), neighbor| {
+            let suppressed = number_of_suppressed_neighbors(tiles, neighbor, temps).max(1);
+            (threat + 1.0 / suppressed as f32, captures + (suppressed == 1) as u32)
+        })
+}
+
+/// The coordinate with the highest score, the first of them on a tie
+fn best_by<F: FnMut(&Coord) -> f32>(coords: impl IntoIterator<Item = Coord>, mut score: F) -> Option<Coord> {
+    let mut best: Option<(Coord, f32)> = None;
+    for coord in coords {
+        let value = score(&coord);
+        if best.is_none_or(|(_, best_value)| value > best_value) {
+            best = Some((coord, value));
+        }
+    }
+    best.map(|(coord, _)| coord)
+}
+
+/// Build the zone covering the most value that the zone rules allow
+///
+/// With shapes to match, every placement of every shape is tried. Otherwise the zone grows from the
+/// most valuable tile, one neighbor at a time when it has to be contiguous, up to `size` tiles.
+///
+/// ## Arguments:
+/// * `tiles` - the board the zone has to fit on
+/// * `rules` - the zone rules to follow
+/// * `size` - how many tiles to cover, kept within the rules
+/// * `anchor` - a tile the zone has to cover, if any
+/// * `value` - how much covering each tile is worth
+pub(crate) fn build_zone<F: Fn(&Coord) -> f32>(tiles: &TileMap, rules: &ZoneRules, size: u32, anchor: Option<Coord>, value: F) -> Vec<Coord> {
+    let board = tiles.board();
+    if !rules.shapes.is_empty() {
+        let mut best: Option<(Vec<Coord>, f32)> = None;
+        for shape in &rules.shapes {
+            for rotation in ROTATIONS {
+                for origin in board.coords() {
+                    let zone = match shape.place(origin, rotation).and_then(|zone| rules.validate(&zone, board).ok()) {
+                        Some(zone) if anchor.is_none_or(|anchor| zone.contains(&anchor)) => zone,
+                        _ => continue
+                    };
+                    let total: f32 = zone.iter().map(&value).sum();
+                    if best.as_ref().is_none_or(|(_, best_total)| total > *best_total) {
+                        best = Some((zone, total));
+                    }
+                }
+            }
+        }
+        return best.map(|(zone, _)| zone).unwrap_or_default();
+    }
+
+    let size = size.clamp(rules.min_tiles.max(1), rules.max_tiles.min(board.tile_count())) as usize;
+    let mut zone: Vec<Coord> = anchor.or_else(|| best_by(board.coords(), &value)).into_iter().collect();
+    while zone.len() < size {
+        let candidates: Vec<Coord> = if rules.contiguous {
+            let mut neighbors: Vec<Coord> = zone.iter().flat_map(|coord| board.neighbors_of(coord)).collect();
+            neighbors.sort();
+            neighbors.dedup();
+            neighbors
+        } else {
+            board.coords().collect()
+        };
+        match best_by(candidates.into_iter().filter(|coord| !zone.contains(coord)), &value) {
+            Some(coord) => zone.push(coord),
+            None => break
+        }
+    }
+    zone
+}
+
+/// The tiles the suppressor covered last turn, as far as the resistance knows.
+fn last_zone(view: &ResistanceView) -> &[Coord] {
+    view.history.last().map_or(&[], |(zone, _)| &zone.suppression_zone)
+}
+
+/// A player that picks uniformly from legal actions.
+#[derive(Debug)]
+pub(crate) struct RandomPlayer {
+    rng: StdRng
+}
+
+impl RandomPlayer {
+    /// A random player whose choices are fixed by the seed
+    pub fn new(seed: u64) -> RandomPlayer {
+        RandomPlayer { rng: StdRng::seed_from_u64(seed) }
+    }
+}
+
+impl Player for RandomPlayer {
+    fn name(&self) -> &str {
+        "random"
+    }
+
+    fn resistance_action(&mut self, view: &ResistanceView) -> Result<ResistanceAction, PlayerError> {
+        let mut open = open_tiles(&view.tiles);
+        if open.is_empty() {
+            // A temp tile can be played on again, it is only the perm tiles that are off limits
+            open = temps(&view.tiles);
+        }
+        let public_coord = *open.choose(&mut self.rng).ok_or_else(|| PlayerError::Failed {
+            player: self.name().to_string(), message: "there is nowhere left to place a tile".to_string()
+        })?;
+        let private_coord = *open.choose(&mut self.rng).unwrap_or(&public_coord);
+        Ok(ResistanceAction { public_coord, private_coord })
+    }
+
+    fn suppression_action(&mut self, view: &SuppressionView) -> Result<SuppressionAction, PlayerError> {
+        let rules = &view.config.zone;
+        let size = self.rng.random_range(rules.min_tiles.max(1)..=rules.max_tiles.max(1));
+        let values: Vec<(Coord, f32)> = view.tiles.board().coords().map(|coord| (coord, self.rng.random::<f32>())).collect();
+        let value = |coord: &Coord| values.iter().find(|(c, _)| c == coord).map_or(0.0, |(_, value)| *value);
+        Ok(SuppressionAction { suppression_zone: build_zone(&view.tiles, rules, size, None, value) })
+    }
+}
+
+/// A player that makes the best move for the next turn alone.
+#[derive(Debug, Copy, Clone, Default)]
+pub(crate) struct GreedyPlayer;
+
+/// The greedy resistance action: the public tile goes where the surround progress is greatest,
+/// captures first, and the private tile on the runner-up. `None` when there is nowhere to place.
+pub(crate) fn greedy_resistance_action(tiles: &TileMap) -> Option<ResistanceAction> {
+    let temps = temps(tiles);
+    let score = |coord: &Coord| {
+        let (progress, captures) = placement_progress(tiles, &temps, *coord);
+        (captures * 100 + progress) as f32
+    };
+    let public_coord = best_by(open_tiles(tiles), score)?;
+    let private_coord = best_by(open_tiles(tiles).into_iter().filter(|coord| *coord != public_coord), score)
+        .unwrap_or(public_coord);
+    Some(ResistanceAction { public_coord, private_coord })
+}
+
+/// The greedy suppression zone, covering the open tiles around the temp tiles with the fewest
+/// suppressed neighbors left.
+pub(crate) fn greedy_suppression_action(tiles: &TileMap, rules: &ZoneRules) -> SuppressionAction {
+    let temps = temps(tiles);
+    let value = |coord: &Coord| match tiles.state_at(coord) {
+        None => cover_value(tiles, &temps, coord).0,
+        Some(_) => 0.0
+    };
+    SuppressionAction { suppression_zone: build_zone(tiles, rules, rules.max_tiles, None, value) }
+}
+
+impl Player for GreedyPlayer {
+    fn name(&self) -> &str {
+        "greedy"
+    }
+
+    fn resistance_action(&mut self, view: &ResistanceView) -> Result<ResistanceAction, PlayerError> {
+        greedy_resistance_action(&view.tiles).ok_or_else(|| PlayerError::Failed {
+            player: self.name().to_string(), message: "there is nowhere left to place a tile".to_string()
+        })
+    }
+
+    fn suppression_action(&mut self, view: &SuppressionView) -> Result<SuppressionAction, PlayerError> {
+        Ok(greedy_suppression_action(&view.tiles, &view.config.zone))
+    }
+}
+
+/** How the heuristic resistance scores a tile to place on
+
+*Fields*
+- `progress` (f32): per suppressed neighbor the placement takes away from the temp tiles
+- `capture` (f32): per temp tile the placement would capture
+- `edge` (f32): per neighbor the tile is missing by being on the edge of the board, as those are
+  quicker to surround
+- `territory` (f32): per perm tile next to the tile
+- `avoid_zone` (f32): taken off tiles the suppressor covered last turn, expecting it to stay put
+*/
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ResistanceWeights {
+    pub progress: f32,
+    pub capture: f32,
+    pub edge: f32,
+    pub territory: f32,
+    pub avoid_zone: f32
+}
+
+impl Default for ResistanceWeights {
+    fn default() -> Self {
+        Self {
+            progress: 1.0,
+            capture: 8.0,
+            edge: 0.5,
+            territory: 0.5,
+            avoid_zone: 3.0
+        }
+    }
+}
+
+/** How the heuristic suppressor scores a tile to cover
+
+*Fields*
+- `threat` (f32): per temp tile next to the tile, divided by the suppressed neighbors it has left
+- `block` (f32): per temp tile a placement on the tile would capture
+- `territory` (f32): per perm tile next to the tile, where the resistance tends to build
+- `recent` (f32): for tiles next to or on the resistance's last public placement, where a hidden
+  tile is likely to be nearby
+*/
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SuppressionWeights {
+    pub threat: f32,
+    pub block: f32,
+    pub territory: f32,
+    pub recent: f32
+}
+
+impl Default for SuppressionWeights {
+    fn default() -> Self {
+        Self {
+            threat: 2.0,
+            block: 6.0,
+            territory: 0.5,
+            recent: 1.0
+        }
+    }
+}
+
+/// A player scoring tiles on weighted features, for either side.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub(crate) struct HeuristicPlayer {
+    pub resistance: ResistanceWeights,
+    pub suppression: SuppressionWeights
+}
+
+/// The number of perm tiles next to a tile
+fn perm_neighbors(tiles: &TileMap, coord: &Coord) -> u32 {
+    tiles.board().neighbors_of(coord).iter().filter(|n| tiles.state_at(n) == Some(TileState::Resistance)).count() as u32
+}
+
+impl Player for HeuristicPlayer {
+    fn name(&self) -> &str {
+        "heuristic"
+    }
+
+    /// Places on the best scoring tile, hiding the best scoring tile left outside the last zone
+    fn resistance_action(&mut self, view: &ResistanceView) -> Result<ResistanceAction, PlayerError> {
+        let weights = self.resistance;
+        let tiles = &view.tiles;
+        let temps = temps(tiles);
+        let zone = last_zone(view);
+        let score = |coord: &Coord| {
+            let (progress, captures) = placement_progress(tiles, &temps, *coord);
+            let missing = 4 - tiles.board().neighbors_of(coord).len() as u32;
+            weights.progress * progress as f32
+                + weights.capture * captures as f32
+                + weights.edge * missing as f32
+                + weights.territory * perm_neighbors(tiles, coord) as f32
+                - if zone.contains(coord) { weights.avoid_zone } else { 0.0 }
+        };
+        let public_coord = best_by(open_tiles(tiles), score).ok_or_else(|| PlayerError::Failed {
+            player: self.name().to_string(), message: "there is nowhere left to place a tile".to_string()
+        })?;
+        let private_coord = best_by(open_tiles(tiles).into_iter().filter(|coord| *coord != public_coord), |coord| {
+            // A hidden tile caught in the zone is wasted, so steer well clear of it
+            score(coord) - if zone.contains(coord) { weights.avoid_zone } else { 0.0 }
+        }).unwrap_or(public_coord);
+        Ok(ResistanceAction { public_coord, private_coord })
+    }
+
+    /// Covers the zone with the highest total score
+    fn suppression_action(&mut self, view: &SuppressionView) -> Result<SuppressionAction, PlayerError> {
+        let weights = self.suppression;
+        let tiles = &view.tiles;
+        let temps = temps(tiles);
+        let last_public = view.history.last().map(|(_, coord)| *coord);
+        let value = |coord: &Coord| {
+            if tiles.state_at(coord).is_some() {
+                return 0.0;
+            }
+            let (threat, captures) = cover_value(tiles, &temps, coord);
+            let recent = last_public.is_some_and(|last| last == *coord || tiles.board().neighbors_of(&last).contains(coord));
+            weights.threat * threat
+                + weights.block * captures as f32
+                + weights.territory * perm_neighbors(tiles, coord) as f32
+                + if recent { weights.recent } else { 0.0 }
+        };
+        Ok(SuppressionAction { suppression_zone: build_zone(tiles, &view.config.zone, view.config.zone.max_tiles, None, value) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::{play_round, PlayedRound, Player};
+    use crate::ai::baseline::{GreedyPlayer, HeuristicPlayer, RandomPlayer};
+    use crate::gameplay::round_config::RoundConfig;
+    use crate::gameplay::round_manager::RoundState;
+    use crate::gameplay::suppression_zone::ZoneShape;
+    use crate::gameplay::tilemap::TileState;
+
+    /// Tests that every pairing of the baseline players plays a round out without forfeiting
+    #[test]
+    fn test_baseline_players_finish_rounds() {
+        let players = || -> Vec<Box<dyn Player>> {
+            vec![Box::new(RandomPlayer::new(7)), Box::new(GreedyPlayer), Box::new(HeuristicPlayer::default())]
+        };
+        let shaped = RoundConfig::builder()
+            .zone_tiles(1, 5)
+            .zone_shapes(vec![ZoneShape::Cross, ZoneShape::L])
+            .build()
+            .unwrap();
+        for config in [RoundConfig::default(), shaped] {
+            for mut resistance in players() {
+                for mut suppression in players() {
+                    let mut state = RoundState::new(config.clone());
+                    let played = play_round(&mut state, resistance.as_mut(), suppression.as_mut());
+                    assert!(matches!(played, PlayedRound::Finished(_)), "{} v {}: {:?}", resistance.name(), suppression.name(), played);
+                }
+            }
+        }
+    }
+
+    /// Tests that the random player plays the same round from the same seed
+    #[test]
+    fn test_random_player_is_seeded() {
+        let play = |seed| {
+            let mut state = RoundState::default();
+            play_round(&mut state, &mut RandomPlayer::new(seed), &mut RandomPlayer::new(seed + 1));
+            state.turn_history().to_vec()
+        };
+        assert_eq!(play(1), play(1));
+        assert_ne!(play(1), play(2));
+    }
+
+    /// Tests that the greedy resistance completes a surround when it can
+    #[test]
+    fn test_greedy_resistance_captures() {
+        let mut state = RoundState::default();
+        state.tiles_mut().set((0, 0), TileState::TemporaryResistance(2)).unwrap();
+        state.tiles_mut().set((0, 1), TileState::Resistance).unwrap();
+        let action = GreedyPlayer.resistance_action(&state.resistance_view()).unwrap();
+        // (1, 0) is the last open neighbor of the corner
+        assert_eq!(action.public_coord, (1, 0));
+    }
+
+    /// Tests that the greedy suppressor covers around the temp tile closest to capture
+    #[test]
+    fn test_greedy_suppression_covers_threats() {
+        let mut state = RoundState::default();
+        state.tiles_mut().set((4, 4), TileState::TemporaryResistance(2)).unwrap();
+        state.tiles_mut().set((4, 5), TileState::Resistance).unwrap();
+        state.tiles_mut().set((5, 4), TileState::Resistance).unwrap();
+        state.tiles_mut().set((3, 4), TileState::Resistance).unwrap();
+        let action = GreedyPlayer.suppression_action(&state.suppression_view()).unwrap();
+        assert!(action.suppression_zone.contains(&(4, 3)));
+        assert!(state.validate_suppression_action(&action).is_ok());
+    }
+}
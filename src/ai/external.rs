@@ -0,0 +1,144 @@
+/*! Players running in another process
+*
+* An external player is any program that speaks the bot protocol on its standard streams. Each turn
+* it is sent its side's view of the round as a single line of JSON, a serialized `PlayerView`, and
+* has to answer with a single line of JSON holding its action: a `ResistanceAction` for a resistance
+* view and a `SuppressionAction` for a suppression view. The program is started once and kept
+* running for the whole round, so it can remember what it likes between turns.
+*
+* The program's answers are read on a thread of their own, so a program that hangs only costs its
+* player the turn's time limit: once that passes the player fails to choose an action.
+*/
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+
+use crate::ai::{Player, PlayerError};
+use crate::gameplay::actors::{ResistanceAction, SuppressionAction};
+use crate::gameplay::views::{PlayerView, ResistanceView, SuppressionView};
+
+/// A player driven by a program speaking the bot protocol.
+#[derive(Debug)]
+pub(crate) struct ExternalPlayer {
+    name: String,
+    child: Child,
+    input: ChildStdin,
+    lines: Receiver<std::io::Result<String>>,
+    time_limit: Duration
+}
+
+impl ExternalPlayer {
+    /// Start the program the command runs, its standard input and output are taken over for the protocol
+    ///
+    /// ## Arguments:
+    /// * `name` - the name the player is known by
+    /// * `command` - the command starting the program
+    /// * `time_limit` - how long the program has to answer each turn
+    pub fn spawn(name: &str, command: &mut Command, time_limit: Duration) -> Result<ExternalPlayer, PlayerError> {
+        let failed = |message: String| PlayerError::Failed { player: name.to_string(), message };
+        let mut child = command.stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|error| failed(format!("couldn't start the program: {}", error)))?;
+        // Both streams were just asked for
+        let input = child.stdin.take().unwrap();
+        let output = BufReader::new(child.stdout.take().unwrap());
+        let (sender, lines) = mpsc::channel();
+        // The thread ends when the program's output closes, at the latest once the player kills it
+        thread::spawn(move || {
+            for line in output.lines() {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(ExternalPlayer { name: name.to_string(), child, input, lines, time_limit })
+    }
+
+    /// Send the program a view and read back its answer
+    fn exchange<T: DeserializeOwned>(&mut self, view: PlayerView) -> Result<T, PlayerError> {
+        let name = self.name.clone();
+        let failed = |message: String| PlayerError::Failed { player: name.clone(), message };
+        let mut request = serde_json::to_string(&view).map_err(|error| failed(error.to_string()))?;
+        request.push('\n');
+        self.input.write_all(request.as_bytes())
+            .and_then(|_| self.input.flush())
+            .map_err(|error| failed(format!("couldn't send the view: {}", error)))?;
+
+        let response = match self.lines.recv_timeout(self.time_limit) {
+            Ok(line) => line.map_err(|error| failed(format!("couldn't read an action: {}", error)))?,
+            Err(RecvTimeoutError::Timeout) => return Err(failed(format!("no action within {:?}", self.time_limit))),
+            Err(RecvTimeoutError::Disconnected) => return Err(failed("the program exited without an action".to_string()))
+        };
+        serde_json::from_str(response.trim()).map_err(|error| failed(format!("couldn't parse the action: {}", error)))
+    }
+}
+
+impl Player for ExternalPlayer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn resistance_action(&mut self, view: &ResistanceView) -> Result<ResistanceAction, PlayerError> {
+        self.exchange(PlayerView::Resistance(view.clone()))
+    }
+
+    fn suppression_action(&mut self, view: &SuppressionView) -> Result<SuppressionAction, PlayerError> {
+        self.exchange(PlayerView::Suppression(view.clone()))
+    }
+}
+
+impl Drop for ExternalPlayer {
+    fn drop(&mut self) {
+        // The program may well have exited on its own already
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+    use std::time::{Duration, Instant};
+
+    use crate::ai::{take_turn, Player, PlayerError};
+    use crate::ai::external::ExternalPlayer;
+    use crate::gameplay::actors::Side;
+    use crate::gameplay::round_manager::RoundState;
+
+    /// Tests a round trip through a program answering every view with the same action
+    #[test]
+    fn test_external_action() {
+        let answer = r#"while read view; do echo '{"public_coord":[0,0],"private_coord":[1,1]}'; done"#;
+        let mut player = ExternalPlayer::spawn("echo", Command::new("sh").args(["-c", answer]), Duration::from_secs(5)).unwrap();
+        let mut state = RoundState::default();
+        take_turn(&mut player, &mut state, Side::Resistance).unwrap();
+        let action = state.turn_buffer().1.unwrap();
+        assert_eq!((action.public_coord, action.private_coord), ((0, 0), (1, 1)));
+    }
+
+    /// Tests that a program that exits fails to choose an action
+    #[test]
+    fn test_external_exits() {
+        let mut player = ExternalPlayer::spawn("quitter", Command::new("sh").args(["-c", "exit 0"]), Duration::from_secs(5)).unwrap();
+        let error = player.resistance_action(&RoundState::default().resistance_view()).unwrap_err();
+        assert!(matches!(error, PlayerError::Failed { .. }), "{}", error);
+    }
+
+    /// Tests that a program that never answers fails once its time is up
+    #[test]
+    fn test_external_times_out() {
+        let mut command = Command::new("sh");
+        command.args(["-c", "read view; sleep 30"]);
+        let mut player = ExternalPlayer::spawn("sleeper", &mut command, Duration::from_millis(200)).unwrap();
+        let started = Instant::now();
+        let error = player.resistance_action(&RoundState::default().resistance_view()).unwrap_err();
+        assert!(matches!(&error, PlayerError::Failed { message, .. } if message.starts_with("no action within")), "{}", error);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
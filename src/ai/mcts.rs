@@ -0,0 +1,437 @@
+/*! Simultaneous-move Monte Carlo tree search
+*
+* Both sides act at once each turn, so a node can't be expanded one player's move at a time the way
+* minimax would. Instead each node keeps separate statistics for each side's actions and both sides
+* pick from their own statistics without knowing the other's pick, with the joint action leading to
+* the child. That pick is either decoupled UCT, where each side runs UCB1 on its own actions, or
+* smooth UCT, which mixes UCB1 with each side's average strategy so play settles into a mixed
+* strategy rather than chasing the opponent's last best response.
+*
+* Each node only considers a handful of candidate actions per side, the most promising by the same
+* measures the baseline players use, and playouts mix greedy and random moves. Searches are
+* deterministic for a seed unless they are cut short by a time budget.
+*
+* The search runs on a `RoundState` rebuilt from the player's view of the round, under the round's
+* rule set and with its sieges in progress. The resistance's view is the whole position, but the
+* suppressor can't see hidden tiles, so it searches as though there are none.
+*/
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+use rand::{RngExt, SeedableRng};
+
+use crate::ai::{resistance_reward, Player, PlayerError};
+use crate::ai::baseline::{build_zone, cover_value, greedy_resistance_action, greedy_suppression_action, open_tiles, placement_progress, temps};
+use crate::gameplay::actors::{ResistanceAction, SuppressionAction};
+use crate::gameplay::round_config::RoundConfig;
+use crate::gameplay::round_manager::RoundState;
+use crate::gameplay::tilemap::{Coord, TileMap};
+use crate::gameplay::views::{ResistanceView, SuppressionView};
+
+/// How each side picks its action at a node.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Selection {
+    /// Each side runs UCB1 on its own actions, and the most visited action is played.
+    DecoupledUct,
+    /// Each side mixes UCB1 with its average strategy, and the action played is drawn from that
+    /// average strategy.
+    ///
+    /// UCB1 is picked with probability `max(gamma, eta / (1 + d * sqrt(visits)))`.
+    SmoothUct { gamma: f32, eta: f32, d: f32 }
+}
+
+impl Selection {
+    /// Smooth UCT with the usual mixing schedule.
+    pub fn smooth() -> Selection {
+        Selection::SmoothUct { gamma: 0.1, eta: 0.9, d: 0.001 }
+    }
+}
+
+/** Settings for a search
+
+*Fields*
+- `iterations` (Option<u32>): the most playouts to run for one action
+- `time_limit` (Option<Duration>): the longest to search for one action, at the cost of determinism
+- `selection` (Selection): how each side picks its action at a node
+- `exploration` (f32): the UCB1 exploration constant
+- `candidates` (usize): how many public tiles, private tiles and zones each node considers
+- `greed` (f32): the chance each side makes the greedy move in a playout rather than a random one
+- `seed` (u64): the seed every choice in the search is drawn from
+
+The search stops at whichever budget runs out first, and always runs at least one playout.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct MctsConfig {
+    pub iterations: Option<u32>,
+    pub time_limit: Option<Duration>,
+    pub selection: Selection,
+    pub exploration: f32,
+    pub candidates: usize,
+    pub greed: f32,
+    pub seed: u64
+}
+
+impl Default for MctsConfig {
+    fn default() -> Self {
+        Self {
+            iterations: Some(1_000),
+            time_limit: None,
+            selection: Selection::DecoupledUct,
+            exploration: 0.7,
+            candidates: 6,
+            greed: 0.5,
+            seed: 0
+        }
+    }
+}
+
+/// How often one side's action was picked at a node, and the reward it brought that side.
+#[derive(Debug, Copy, Clone, Default)]
+struct ActionStats {
+    visits: u32,
+    reward: f32
+}
+
+/// A position in the search tree.
+#[derive(Debug, Default)]
+struct Node {
+    visits: u32,
+    resistance_actions: Vec<ResistanceAction>,
+    suppression_actions: Vec<SuppressionAction>,
+    resistance_stats: Vec<ActionStats>,
+    suppression_stats: Vec<ActionStats>,
+    /// The node each joint action leads to, keyed by the index of each side's action.
+    children: HashMap<(usize, usize), usize>
+}
+
+/// A player that searches ahead with simultaneous-move MCTS.
+#[derive(Debug)]
+pub(crate) struct MctsPlayer {
+    config: MctsConfig,
+    rng: StdRng
+}
+
+impl MctsPlayer {
+    pub fn new(config: MctsConfig) -> MctsPlayer {
+        let rng = StdRng::seed_from_u64(config.seed);
+        MctsPlayer { config, rng }
+    }
+
+    /// The settings searches are run with.
+    pub fn config(&self) -> &MctsConfig {
+        &self.config
+    }
+
+    /// Search from a position and return the chosen action for each side
+    ///
+    /// Only the action for the side being played is meant to be used, the other is what the search
+    /// expects the opponent to do. `None` if the round is already over.
+    pub fn search(&mut self, root: &RoundState) -> Option<(SuppressionAction, ResistanceAction)> {
+        if root.is_over() {
+            return None;
+        }
+        let started = Instant::now();
+        let mut nodes: Vec<Node> = vec![Node::default()];
+        let mut iterations = 0;
+        loop {
+            self.iterate(root, &mut nodes);
+            iterations += 1;
+            let out_of_iterations = self.config.iterations.is_some_and(|limit| iterations >= limit);
+            let out_of_time = self.config.time_limit.is_some_and(|limit| started.elapsed() >= limit);
+            if out_of_iterations || out_of_time {
+                break;
+            }
+        }
+        let root = &nodes[0];
+        let suppression = self.final_choice(&root.suppression_stats);
+        let resistance = self.final_choice(&root.resistance_stats);
+        Some((root.suppression_actions[suppression].clone(), root.resistance_actions[resistance]))
+    }
+
+    /// Run one playout from the root, growing the tree by a node
+    fn iterate(&mut self, root: &RoundState, nodes: &mut Vec<Node>) {
+        let mut state = root.clone();
+        let mut path: Vec<(usize, usize, usize)> = Vec::new();
+        let mut current = 0;
+        let reward = loop {
+            if let Some(outcome) = state.outcome() {
+                break resistance_reward(&outcome);
+            }
+            if nodes[current].resistance_actions.is_empty() {
+                self.expand(&mut nodes[current], &state);
+            }
+            let node = &nodes[current];
+            let resistance = self.select(node.visits, &node.resistance_stats);
+            let suppression = self.select(node.visits, &node.suppression_stats);
+            path.push((current, resistance, suppression));
+            let played = play_turn(&mut state, node.suppression_actions[suppression].clone(), node.resistance_actions[resistance]);
+            if !played {
+                // Candidates are checked when the node is expanded, so this only happens under
+                // rules that change what is legal from one turn to the next; score it as a draw
+                break 0.5;
+            }
+            match nodes[current].children.get(&(resistance, suppression)) {
+                Some(child) => current = *child,
+                None => {
+                    nodes.push(Node::default());
+                    let child = nodes.len() - 1;
+                    nodes[current].children.insert((resistance, suppression), child);
+                    break self.playout(&mut state);
+                }
+            }
+        };
+        for (index, resistance, suppression) in path {
+            let node = &mut nodes[index];
+            node.visits += 1;
+            node.resistance_stats[resistance].visits += 1;
+            node.resistance_stats[resistance].reward += reward;
+            node.suppression_stats[suppression].visits += 1;
+            node.suppression_stats[suppression].reward += 1.0 - reward;
+        }
+    }
+
+    /// Fill in the candidate actions for a node, keeping only those the round accepts
+    fn expand(&mut self, node: &mut Node, state: &RoundState) {
+        let count = self.config.candidates.max(1);
+        node.resistance_actions = resistance_candidates(state.tiles(), count).into_iter()
+            .filter(|action| state.validate_resistance_action(action).is_ok())
+            .collect();
+        node.suppression_actions = suppression_candidates(state.tiles(), state.config(), count).into_iter()
+            .filter_map(|action| state.validate_suppression_action(&action).ok())
+            .collect();
+        if node.resistance_actions.is_empty() {
+            // Some action is needed to search at all, and the round will refuse it if it must
+            node.resistance_actions.push(ResistanceAction { public_coord: (0, 0), private_coord: (0, 0) });
+        }
+        if node.suppression_actions.is_empty() {
+            node.suppression_actions.push(greedy_suppression_action(state.tiles(), &state.config().zone));
+        }
+        node.resistance_stats = vec![ActionStats::default(); node.resistance_actions.len()];
+        node.suppression_stats = vec![ActionStats::default(); node.suppression_actions.len()];
+    }
+
+    /// Pick one side's action at a node
+    fn select(&mut self, visits: u32, stats: &[ActionStats]) -> usize {
+        if let Selection::SmoothUct { gamma, eta, d } = self.config.selection {
+            let ucb_chance = gamma.max(eta / (1.0 + d * (visits as f32).sqrt()));
+            if visits > 0 && self.rng.random::<f32>() >= ucb_chance {
+                return self.sample_average_strategy(stats);
+            }
+        }
+        if let Some(unvisited) = stats.iter().position(|stat| stat.visits == 0) {
+            return unvisited;
+        }
+        let log_visits = (visits.max(1) as f32).ln();
+        let mut best = (0, f32::MIN);
+        for (index, stat) in stats.iter().enumerate() {
+            let mean = stat.reward / stat.visits as f32;
+            let ucb = mean + self.config.exploration * (log_visits / stat.visits as f32).sqrt();
+            if ucb > best.1 {
+                best = (index, ucb);
+            }
+        }
+        best.0
+    }
+
+    /// Draw an action in proportion to how often it has been picked
+    fn sample_average_strategy(&mut self, stats: &[ActionStats]) -> usize {
+        let total: u32 = stats.iter().map(|stat| stat.visits).sum();
+        if total == 0 {
+            return self.rng.random_range(0..stats.len());
+        }
+        let mut pick = self.rng.random_range(0..total);
+        for (index, stat) in stats.iter().enumerate() {
+            if pick < stat.visits {
+                return index;
+            }
+            pick -= stat.visits;
+        }
+        stats.len() - 1
+    }
+
+    /// The action a side plays once the search is done
+    fn final_choice(&mut self, stats: &[ActionStats]) -> usize {
+        match self.config.selection {
+            Selection::DecoupledUct => stats.iter().enumerate()
+                .max_by_key(|(index, stat)| (stat.visits, std::cmp::Reverse(*index)))
+                .map_or(0, |(index, _)| index),
+            Selection::SmoothUct { .. } => self.sample_average_strategy(stats)
+        }
+    }
+
+    /// Play the round out from here, returning the resistance's reward
+    fn playout(&mut self, state: &mut RoundState) -> f32 {
+        while state.outcome().is_none() {
+            let suppression = if self.rng.random::<f32>() < self.config.greed {
+                greedy_suppression_action(state.tiles(), &state.config().zone)
+            } else {
+                random_suppression_action(state.tiles(), state.config(), &mut self.rng)
+            };
+            let resistance = if self.rng.random::<f32>() < self.config.greed {
+                greedy_resistance_action(state.tiles())
+            } else {
+                random_resistance_action(state.tiles(), &mut self.rng)
+            };
+            let played = resistance.is_some_and(|resistance| play_turn(state, suppression, resistance));
+            if !played {
+                return 0.5;
+            }
+        }
+        state.outcome().map_or(0.5, |outcome| resistance_reward(&outcome))
+    }
+}
+
+impl Default for MctsPlayer {
+    fn default() -> Self {
+        MctsPlayer::new(MctsConfig::default())
+    }
+}
+
+impl Player for MctsPlayer {
+    fn name(&self) -> &str {
+        "mcts"
+    }
+
+    fn resistance_action(&mut self, view: &ResistanceView) -> Result<ResistanceAction, PlayerError> {
+        self.search(&view.position()).map(|(_, resistance)| resistance).ok_or_else(|| PlayerError::Failed {
+            player: self.name().to_string(), message: "the round is already over".to_string()
+        })
+    }
+
+    fn suppression_action(&mut self, view: &SuppressionView) -> Result<SuppressionAction, PlayerError> {
+        self.search(&view.position()).map(|(suppression, _)| suppression).ok_or_else(|| PlayerError::Failed {
+            player: self.name().to_string(), message: "the round is already over".to_string()
+        })
+    }
+}
+
+/// Resolve a turn, returning whether the round accepted both actions
+fn play_turn(state: &mut RoundState, suppression: SuppressionAction, resistance: ResistanceAction) -> bool {
+    state.intake_suppression_action(suppression).is_ok() && state.intake_resistance_action(resistance).is_ok()
+}
+
+/// The `count` best tiles by a score, best first and in board order on a tie
+fn top_by<F: Fn(&Coord) -> f32>(coords: Vec<Coord>, count: usize, score: F) -> Vec<Coord> {
+    let mut scored: Vec<(Coord, f32)> = coords.into_iter().map(|coord| (coord, score(&coord))).collect();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scored.into_iter().take(count).map(|(coord, _)| coord).collect()
+}
+
+/// How far a tile is from the middle of the board, so ties go to the centre
+fn off_centre(tiles: &TileMap, coord: &Coord) -> f32 {
+    let board = tiles.board();
+    let dx = coord.0 as f32 - (board.width() as f32 - 1.0) / 2.0;
+    let dy = coord.1 as f32 - (board.height() as f32 - 1.0) / 2.0;
+    dx.abs() + dy.abs()
+}
+
+/// Every pairing of the most promising public and private tiles
+fn resistance_candidates(tiles: &TileMap, count: usize) -> Vec<ResistanceAction> {
+    let temps = temps(tiles);
+    let best = top_by(open_tiles(tiles), count, |coord| {
+        let (progress, captures) = placement_progress(tiles, &temps, *coord);
+        (captures * 100 + progress) as f32 - 0.01 * off_centre(tiles, coord)
+    });
+    let mut actions: Vec<ResistanceAction> = Vec::new();
+    for public_coord in &best {
+        for private_coord in &best {
+            if public_coord != private_coord || best.len() == 1 {
+                actions.push(ResistanceAction { public_coord: *public_coord, private_coord: *private_coord });
+            }
+        }
+    }
+    actions
+}
+
+/// A zone anchored on each of the most threatening open tiles
+fn suppression_candidates(tiles: &TileMap, config: &RoundConfig, count: usize) -> Vec<SuppressionAction> {
+    let temps = temps(tiles);
+    let value = |coord: &Coord| match tiles.state_at(coord) {
+        None => cover_value(tiles, &temps, coord).0 - 0.01 * off_centre(tiles, coord),
+        Some(_) => 0.0
+    };
+    let mut zones: Vec<Vec<Coord>> = Vec::new();
+    for anchor in top_by(open_tiles(tiles), count, value) {
+        let mut zone = build_zone(tiles, &config.zone, config.zone.max_tiles, Some(anchor), value);
+        zone.sort();
+        if !zone.is_empty() && !zones.contains(&zone) {
+            zones.push(zone);
+        }
+    }
+    zones.into_iter().map(|suppression_zone| SuppressionAction { suppression_zone }).collect()
+}
+
+/// Any public and private tile nothing has been placed on
+fn random_resistance_action(tiles: &TileMap, rng: &mut StdRng) -> Option<ResistanceAction> {
+    let open = open_tiles(tiles);
+    let public_coord = *open.choose(rng)?;
+    let private_coord = *open.choose(rng)?;
+    Some(ResistanceAction { public_coord, private_coord })
+}
+
+/// A zone grown from random values, of a random size the zone rules allow
+fn random_suppression_action(tiles: &TileMap, config: &RoundConfig, rng: &mut StdRng) -> SuppressionAction {
+    let board = tiles.board();
+    let values: Vec<f32> = board.coords().map(|_| rng.random::<f32>()).collect();
+    let width = board.width() as usize;
+    let value = |coord: &Coord| values[coord.1 as usize * width + coord.0 as usize];
+    let size = rng.random_range(config.zone.min_tiles.max(1)..=config.zone.max_tiles.max(1));
+    SuppressionAction { suppression_zone: build_zone(tiles, &config.zone, size, None, value) }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::{play_round, PlayedRound, Player};
+    use crate::ai::baseline::RandomPlayer;
+    use crate::ai::mcts::{MctsConfig, MctsPlayer, Selection};
+    use crate::gameplay::actors::Side;
+    use crate::gameplay::round_config::RoundConfig;
+    use crate::gameplay::round_manager::RoundState;
+    use crate::gameplay::tilemap::TileState;
+
+    fn player(selection: Selection, seed: u64) -> MctsPlayer {
+        MctsPlayer::new(MctsConfig { iterations: Some(60), selection, seed, ..MctsConfig::default() })
+    }
+
+    /// A small, short round so searches finish quickly
+    fn small_round() -> RoundState {
+        RoundState::new(RoundConfig::builder().board_size(5, 5).max_turns(6).score_to_win(2).build().unwrap())
+    }
+
+    /// Tests that the same seed gives the same search
+    #[test]
+    fn test_search_is_seeded() {
+        let state = small_round();
+        for selection in [Selection::DecoupledUct, Selection::smooth()] {
+            assert_eq!(player(selection, 3).search(&state), player(selection, 3).search(&state));
+        }
+    }
+
+    /// Tests that the resistance finds a capture on offer
+    #[test]
+    fn test_search_finds_capture() {
+        let mut state = small_round();
+        state.tiles_mut().set((0, 0), TileState::TemporaryResistance(2)).unwrap();
+        state.tiles_mut().set((0, 1), TileState::Resistance).unwrap();
+        let action = player(Selection::DecoupledUct, 0).resistance_action(&state.resistance_view()).unwrap();
+        assert!(action.public_coord == (1, 0) || action.private_coord == (1, 0), "{:?}", action);
+    }
+
+    /// Tests that the search plays whole rounds from either side
+    #[test]
+    fn test_search_plays_rounds() {
+        for side in [Side::Resistance, Side::Suppression] {
+            let mut state = small_round();
+            let mut searcher = player(Selection::smooth(), 1);
+            let mut opponent = RandomPlayer::new(1);
+            let played = match side {
+                Side::Resistance => play_round(&mut state, &mut searcher, &mut opponent),
+                Side::Suppression => play_round(&mut state, &mut opponent, &mut searcher)
+            };
+            assert!(matches!(played, PlayedRound::Finished(_)), "{:?}", played);
+        }
+    }
+}
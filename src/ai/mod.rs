@@ -0,0 +1,281 @@
+/*! Computer players
+*
+* Anything that can play a side of a round implements `Player`, choosing its actions from its side's
+* view of the round and never from the `RoundState` itself. `take_turn` and `play_round` drive a
+* round with players, checking every action before intake: a player that can't come up with a legal
+* action forfeits the round. A rule set that fails is nobody's fault, so it calls the round off
+* instead. `play_match` does the same for a whole `MatchState`, the players swapping sides each round.
+*/
+
+pub mod baseline;
+pub mod external;
+pub mod mcts;
+pub mod solver;
+
+use std::fmt;
+
+use crate::gameplay::actors::{ResistanceAction, Side, SuppressionAction};
+use crate::gameplay::match_manager::{MatchError, MatchState, Seat};
+use crate::gameplay::round_manager::{ActionError, GameOutcome, IntakeStatus, RoundState};
+use crate::gameplay::rules::RuleError;
+use crate::gameplay::views::{ResistanceView, SuppressionView};
+
+/// The ways a player can fail to take its turn.
+#[derive(Debug)]
+pub enum PlayerError {
+    /// The player doesn't play the side.
+    CantPlay { player: String, side: Side },
+    /// The player couldn't choose an action at all.
+    Failed { player: String, message: String },
+    /// The round refused the action the player chose.
+    Rejected { player: String, side: Side, error: ActionError }
+}
+
+impl fmt::Display for PlayerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlayerError::CantPlay { player, side } => write!(f, "{} can't play the {:?} side", player, side),
+            PlayerError::Failed { player, message } => write!(f, "{} failed to choose an action: {}", player, message),
+            PlayerError::Rejected { player, side, error } => write!(
+                f, "{} chose an invalid {:?} action: {:?}", player, side, error
+            )
+        }
+    }
+}
+
+impl std::error::Error for PlayerError {}
+
+/// Something that chooses actions for one or both sides of a round.
+pub(crate) trait Player: Send {
+    /// The name the player is known by.
+    fn name(&self) -> &str;
+
+    /// Whether the player can play the side, both by default.
+    fn plays(&self, _side: Side) -> bool {
+        true
+    }
+
+    /// Choose the resistance's action for the turn
+    fn resistance_action(&mut self, view: &ResistanceView) -> Result<ResistanceAction, PlayerError>;
+
+    /// Choose the suppressor's action for the turn
+    fn suppression_action(&mut self, view: &SuppressionView) -> Result<SuppressionAction, PlayerError>;
+}
+
+/// How a round played out between two players.
+#[derive(Debug)]
+pub(crate) enum PlayedRound {
+    /// The round was played to the end.
+    Finished(GameOutcome),
+    /// A side couldn't take its turn, and so lost the round.
+    Forfeited { side: Side, error: PlayerError },
+    /// The rule set failed to resolve a turn, so the round was called off with nobody winning.
+    Aborted(RuleError)
+}
+
+impl PlayedRound {
+    /// The side that won the round, `None` for a draw or an aborted round.
+    pub fn winner(&self) -> Option<Side> {
+        match self {
+            PlayedRound::Aborted(_) => None,
+            PlayedRound::Finished(GameOutcome::ResistanceWins { .. }) => Some(Side::Resistance),
+            PlayedRound::Finished(GameOutcome::SuppressionWins { .. } | GameOutcome::SuppressionReclaims { .. }) => Some(Side::Suppression),
+            PlayedRound::Finished(GameOutcome::Draw(_)) => None,
+            PlayedRound::Forfeited { side, .. } => Some(side.opponent())
+        }
+    }
+}
+
+/// How a match played out between two players.
+#[derive(Debug)]
+pub(crate) enum PlayedMatch {
+    /// The match was played to the end, leaving its outcome on the `MatchState`.
+    Finished,
+    /// A seat couldn't take its turn, and so lost the round it was in and the match with it.
+    Forfeited { seat: Seat, error: PlayerError },
+    /// The rule set failed to resolve a turn, so the match was called off with nobody winning.
+    Aborted(RuleError)
+}
+
+/// What an outcome is worth to the resistance: 1 for a win, 0.5 for a draw and 0 for a loss. The
+/// suppressor gets whatever is left of 1.
+pub(crate) fn resistance_reward(outcome: &GameOutcome) -> f32 {
+    match outcome {
+        GameOutcome::ResistanceWins { .. } => 1.0,
+        GameOutcome::SuppressionWins { .. } | GameOutcome::SuppressionReclaims { .. } => 0.0,
+        GameOutcome::Draw(_) => 0.5
+    }
+}
+
+/// Have a player choose an action for a side from its view of the round, then hand it to the round
+///
+/// The action is validated before intake, so the round is left as it was unless it is accepted.
+pub(crate) fn take_turn(player: &mut dyn Player, state: &mut RoundState, side: Side) -> Result<IntakeStatus, PlayerError> {
+    if !player.plays(side) {
+        return Err(PlayerError::CantPlay { player: player.name().to_string(), side });
+    }
+    let name = player.name().to_string();
+    let rejected = |error| PlayerError::Rejected { player: name.clone(), side, error };
+    match side {
+        Side::Resistance => {
+            let action = player.resistance_action(&state.resistance_view())?;
+            state.validate_resistance_action(&action).map_err(rejected)?;
+            state.intake_resistance_action(action).map_err(rejected)
+        },
+        Side::Suppression => {
+            let action = player.suppression_action(&state.suppression_view())?;
+            state.validate_suppression_action(&action).map_err(rejected)?;
+            state.intake_suppression_action(action).map_err(rejected)
+        }
+    }
+}
+
+/// Have the player in a seat choose an action for its side of the current round, then hand it to the match
+///
+/// The action is validated before intake, so the match is left as it was unless it is accepted.
+fn take_match_turn(player: &mut dyn Player, state: &mut MatchState, seat: Seat, side: Side) -> Result<IntakeStatus, PlayerError> {
+    if !player.plays(side) {
+        return Err(PlayerError::CantPlay { player: player.name().to_string(), side });
+    }
+    let name = player.name().to_string();
+    let rejected = |error| PlayerError::Rejected { player: name.clone(), side, error };
+    let refused = |error: MatchError| match error {
+        MatchError::Action(error) => rejected(error),
+        error => PlayerError::Failed { player: name.clone(), message: error.to_string() }
+    };
+    let round = state.current_round();
+    match side {
+        Side::Resistance => {
+            let action = player.resistance_action(&round.resistance_view())?;
+            round.validate_resistance_action(&action).map_err(rejected)?;
+            state.intake_resistance_action(seat, action).map_err(refused)
+        },
+        Side::Suppression => {
+            let action = player.suppression_action(&round.suppression_view())?;
+            round.validate_suppression_action(&action).map_err(rejected)?;
+            state.intake_suppression_action(seat, action).map_err(refused)
+        }
+    }
+}
+
+/// Play a match out between two players, `first` taking the first seat
+///
+/// Every round is played as `play_round` plays one, with the seats swapping sides between rounds.
+/// A player that fails to take its turn forfeits the match, while a rule set that fails aborts it.
+pub(crate) fn play_match(state: &mut MatchState, first: &mut dyn Player, second: &mut dyn Player) -> PlayedMatch {
+    while !state.is_over() {
+        for side in [Side::Suppression, Side::Resistance] {
+            let seat = state.seat_playing(side);
+            let taken = match seat {
+                Seat::First => take_match_turn(first, state, seat, side),
+                Seat::Second => take_match_turn(second, state, seat, side)
+            };
+            match taken {
+                Ok(_) => {},
+                Err(PlayerError::Rejected { error: ActionError::RuleFailed(error), .. }) => return PlayedMatch::Aborted(error),
+                Err(error) => return PlayedMatch::Forfeited { seat, error }
+            }
+        }
+    }
+    PlayedMatch::Finished
+}
+
+/// Play a round out between two players
+///
+/// The suppressor chooses first each turn, though neither sees the other's action until the turn
+/// resolves. A player that fails to take its turn forfeits the round, while a rule set that fails
+/// to resolve one aborts it.
+pub(crate) fn play_round(state: &mut RoundState, resistance: &mut dyn Player, suppression: &mut dyn Player) -> PlayedRound {
+    while state.outcome().is_none() {
+        let taken = take_turn(suppression, state, Side::Suppression)
+            .map_err(|error| (Side::Suppression, error))
+            .and_then(|_| take_turn(resistance, state, Side::Resistance).map_err(|error| (Side::Resistance, error)));
+        match taken {
+            Ok(_) => {},
+            Err((_, PlayerError::Rejected { error: ActionError::RuleFailed(error), .. })) => return PlayedRound::Aborted(error),
+            Err((side, error)) => return PlayedRound::Forfeited { side, error }
+        }
+    }
+    // The loop only ends once the round has an outcome
+    PlayedRound::Finished(state.outcome().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::{play_match, play_round, take_turn, PlayedMatch, PlayedRound, Player, PlayerError};
+    use crate::ai::baseline::{GreedyPlayer, RandomPlayer};
+    use crate::gameplay::actors::{ResistanceAction, Side, SuppressionAction};
+    use crate::gameplay::match_manager::{MatchConfig, MatchState, Seat};
+    use crate::gameplay::round_config::RoundConfig;
+    use crate::gameplay::round_manager::{ActionError, RoundState};
+    use crate::gameplay::rules::Rules;
+    use crate::gameplay::views::{ResistanceView, SuppressionView};
+    use crate::scripting::ScriptLimits;
+    use crate::scripting::rules::ScriptedRuleSet;
+
+    /// A player that only ever plays off the board
+    struct OffBoard;
+
+    impl Player for OffBoard {
+        fn name(&self) -> &str {
+            "off board"
+        }
+
+        fn resistance_action(&mut self, _: &ResistanceView) -> Result<ResistanceAction, PlayerError> {
+            Ok(ResistanceAction { public_coord: (-1, -1), private_coord: (-1, -1) })
+        }
+
+        fn suppression_action(&mut self, _: &SuppressionView) -> Result<SuppressionAction, PlayerError> {
+            Ok(SuppressionAction { suppression_zone: vec![(-1, -1)] })
+        }
+    }
+
+    /// Tests that an invalid action is refused before it reaches the round
+    #[test]
+    fn test_invalid_action_is_rejected() {
+        let mut state = RoundState::default();
+        let error = take_turn(&mut OffBoard, &mut state, Side::Resistance).unwrap_err();
+        assert!(matches!(error, PlayerError::Rejected { error: ActionError::OutOfBounds((-1, -1)), .. }));
+        assert_eq!(state.turn_buffer().1, None);
+    }
+
+    /// Tests that a player forfeits by failing to take its turn
+    #[test]
+    fn test_invalid_action_forfeits() {
+        let mut state = RoundState::default();
+        let played = play_round(&mut state, &mut OffBoard, &mut GreedyPlayer);
+        assert!(matches!(played, PlayedRound::Forfeited { side: Side::Resistance, .. }));
+        assert_eq!(played.winner(), Some(Side::Suppression));
+        assert_eq!(state.current_turn(), 0);
+    }
+
+    /// Tests that a match is played out with the players swapping sides
+    #[test]
+    fn test_play_match() {
+        let config = MatchConfig { round: RoundConfig::builder().board_size(5, 5).max_turns(6).build().unwrap(), best_of: 3 };
+        let mut state = MatchState::new(config).unwrap();
+        let played = play_match(&mut state, &mut GreedyPlayer, &mut RandomPlayer::new(1));
+        assert!(matches!(played, PlayedMatch::Finished), "{:?}", played);
+        assert!((2..=3).contains(&state.rounds().len()));
+        assert!(state.rounds().iter().all(|round| round.is_over()));
+
+        let mut state = MatchState::new(MatchConfig::default()).unwrap();
+        let played = play_match(&mut state, &mut GreedyPlayer, &mut OffBoard);
+        // The second seat plays off the board as the suppressor in the first round
+        assert!(matches!(played, PlayedMatch::Forfeited { seat: Seat::Second, .. }), "{:?}", played);
+    }
+
+    /// Tests that a failing rule script aborts the round instead of forfeiting it for either side
+    #[test]
+    fn test_failing_rules_abort() {
+        let script = ScriptedRuleSet::from_source("broken.rhai", "fn check_outcome(round) { loop { } }", &ScriptLimits::default()).unwrap();
+        let mut state = RoundState::with_rules(RoundConfig::default(), Rules::new(script));
+        let played = play_round(&mut state, &mut GreedyPlayer, &mut GreedyPlayer);
+        match &played {
+            PlayedRound::Aborted(error) => assert_eq!(error.rule_set, "broken.rhai"),
+            other => panic!("expected the round to be aborted, got {:?}", other)
+        }
+        assert_eq!(played.winner(), None);
+        assert_eq!(state.current_turn(), 0);
+    }
+}
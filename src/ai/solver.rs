@@ -0,0 +1,481 @@
+/*! Equilibrium solver for small rounds
+*
+* On a tiny board with few turns the whole game can be solved. Every turn is a matrix game: both
+* sides choose at once, and each pair of actions leads to a position whose value is solved the
+* same way, down to the positions where the round is over. Each matrix game is solved by
+* regret matching, which converges on a mixed-strategy equilibrium, and the equilibrium's value is
+* the position's value.
+*
+* Rounds are solved under whichever rule set they are played under, and a rule set that fails to
+* resolve a turn stops the solve, as the position it would lead to is unknown.
+*
+* Values are the resistance's expected score: 1 for a win, 0.5 for a draw and 0 for a loss, so a
+* round worth more than 0.5 leans towards the resistance.
+*
+* Actions that lead to the same positions against everything the opponent could do are merged, as
+* most private coordinates land somewhere they are never placed. The solve treats hidden tiles as if
+* both sides could see them, so the value is what the resistance could expect against a suppressor
+* that always knows where its hidden tiles are: a lower bound on the real thing.
+*/
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::ai::resistance_reward;
+use crate::gameplay::actors::{ResistanceAction, SuppressionAction};
+use crate::gameplay::round_config::RoundConfig;
+use crate::gameplay::round_manager::{ActionError, RoundState};
+use crate::gameplay::rules::{RuleError, Rules};
+use crate::gameplay::suppression_zone::ZoneRules;
+use crate::gameplay::tilemap::{Board, Coord, TileState};
+use crate::tournament::{parse_flag, rules_from_path};
+
+/** Limits on a solve
+
+*Fields*
+- `iterations` (u32): the regret matching iterations run on each turn's matrix game
+- `max_states` (usize): the most positions to solve before giving up
+- `max_board_tiles` (u32): the largest board to try, as the number of zones grows quickly with it
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SolverConfig {
+    pub iterations: u32,
+    pub max_states: usize,
+    pub max_board_tiles: u32
+}
+
+impl Default for SolverConfig {
+    fn default() -> Self {
+        Self {
+            iterations: 1_000,
+            max_states: 200_000,
+            max_board_tiles: 12
+        }
+    }
+}
+
+/// The ways a round can be too much to solve.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolveError {
+    InvalidConfig(String),
+    BoardTooLarge { tiles: u32, max: u32 },
+    TooManyStates { max: usize },
+    RulesFailed(RuleError)
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolveError::InvalidConfig(error) => write!(f, "invalid round config: {}", error),
+            SolveError::BoardTooLarge { tiles, max } => write!(
+                f, "a board of {} tiles is too large to solve, the limit is {}", tiles, max
+            ),
+            SolveError::TooManyStates { max } => write!(f, "the round has more than {} positions to solve", max),
+            SolveError::RulesFailed(error) => write!(f, "the round couldn't be solved: {}", error)
+        }
+    }
+}
+
+impl std::error::Error for SolveError {}
+
+/** The equilibrium of one turn's matrix game
+
+*Fields*
+- `value` (f32): the resistance's expected score from here with both sides playing the equilibrium
+- `exploitability` (f32): how much either side could gain by deviating from the strategies below,
+  zero at an exact equilibrium
+- `resistance` (Vec<(ResistanceAction, f32)>): the resistance's strategy, most likely action first
+- `suppression` (Vec<(SuppressionAction, f32)>): the suppressor's strategy, most likely action first
+
+Merged actions are only listed once, under the first of them.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Stage {
+    pub value: f32,
+    pub exploitability: f32,
+    pub resistance: Vec<(ResistanceAction, f32)>,
+    pub suppression: Vec<(SuppressionAction, f32)>
+}
+
+/// Solves rounds, remembering every position it has solved.
+#[derive(Debug, Default)]
+pub(crate) struct Solver {
+    config: SolverConfig,
+    values: HashMap<Vec<u8>, f32>,
+    stages: HashMap<Vec<u8>, Stage>
+}
+
+impl Solver {
+    pub fn new(config: SolverConfig) -> Solver {
+        Solver { config, values: HashMap::new(), stages: HashMap::new() }
+    }
+
+    /// The number of positions solved so far.
+    pub fn states(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Solve a round from its first turn, played under the given rule set
+    pub fn solve(&mut self, config: &RoundConfig, rules: Rules) -> Result<Stage, SolveError> {
+        config.validate().map_err(|error| SolveError::InvalidConfig(error.to_string()))?;
+        self.stage(&RoundState::with_rules(config.clone(), rules))
+    }
+
+    /// The equilibrium for the turn a round is on
+    ///
+    /// The round's outcome has to still be open.
+    pub fn stage(&mut self, state: &RoundState) -> Result<Stage, SolveError> {
+        let tiles = state.board().tile_count();
+        if tiles > self.config.max_board_tiles {
+            return Err(SolveError::BoardTooLarge { tiles, max: self.config.max_board_tiles });
+        }
+        self.value(state)?;
+        let stage = self.stages.get(&position_key(state)).cloned();
+        // Only rounds that are over have a value without a stage
+        Ok(stage.unwrap_or_else(|| Stage {
+            value: state.outcome().map_or(0.5, |outcome| resistance_reward(&outcome)),
+            exploitability: 0.0,
+            resistance: Vec::new(),
+            suppression: Vec::new()
+        }))
+    }
+
+    /// The value of a position, solving it if it hasn't been already
+    fn value(&mut self, state: &RoundState) -> Result<f32, SolveError> {
+        if let Some(outcome) = state.outcome() {
+            return Ok(resistance_reward(&outcome));
+        }
+        let key = position_key(state);
+        if let Some(value) = self.values.get(&key) {
+            return Ok(*value);
+        }
+        if self.values.len() >= self.config.max_states {
+            return Err(SolveError::TooManyStates { max: self.config.max_states });
+        }
+
+        let resistance_actions = resistance_actions(state);
+        let suppression_actions = suppression_actions(state);
+        let mut next: Vec<Vec<RoundState>> = Vec::new();
+        for resistance in &resistance_actions {
+            let row = suppression_actions.iter().map(|suppression| {
+                let mut next = state.clone();
+                // Every action was enumerated from what the round accepts, so only the rule set can fail
+                match next.intake_suppression_action(suppression.clone()).and_then(|_| next.intake_resistance_action(*resistance)) {
+                    Err(ActionError::RuleFailed(error)) => Err(SolveError::RulesFailed(error)),
+                    _ => Ok(next)
+                }
+            }).collect::<Result<Vec<RoundState>, SolveError>>()?;
+            next.push(row);
+        }
+
+        // Merge actions that lead to the same positions whatever the opponent does
+        let keys: Vec<Vec<Vec<u8>>> = next.iter().map(|row| row.iter().map(position_key).collect()).collect();
+        let rows = distinct(keys.len(), |a, b| keys[a] == keys[b]);
+        let columns = distinct(suppression_actions.len(), |a, b| keys.iter().all(|row| row[a] == row[b]));
+
+        let mut matrix: Vec<Vec<f32>> = Vec::new();
+        for row in &rows {
+            let values = columns.iter()
+                .map(|column| self.value(&next[*row][*column]))
+                .collect::<Result<Vec<f32>, SolveError>>()?;
+            matrix.push(values);
+        }
+
+        let (resistance_strategy, suppression_strategy, value, exploitability) = solve_matrix(&matrix, self.config.iterations);
+        let mut resistance: Vec<(ResistanceAction, f32)> = rows.iter().map(|row| resistance_actions[*row])
+            .zip(resistance_strategy)
+            .collect();
+        let mut suppression: Vec<(SuppressionAction, f32)> = columns.iter().map(|column| suppression_actions[*column].clone())
+            .zip(suppression_strategy)
+            .collect();
+        resistance.sort_by(|a, b| b.1.total_cmp(&a.1));
+        suppression.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        self.values.insert(key.clone(), value);
+        self.stages.insert(key, Stage { value, exploitability, resistance, suppression });
+        Ok(value)
+    }
+}
+
+/// Everything about a position that decides how the rest of the round plays out
+fn position_key(state: &RoundState) -> Vec<u8> {
+    let mut key: Vec<u8> = state.current_turn().to_le_bytes().to_vec();
+    for coord in state.board().coords() {
+        key.extend(match state.tiles().state_at(&coord) {
+            None => [0, 0],
+            Some(TileState::TemporaryResistance(turns)) => [1, turns as u8],
+            Some(TileState::HiddenResistance(turns)) => [2, turns as u8],
+            Some(TileState::Resistance) => [3, 0],
+            Some(TileState::Suppressor) => [4, 0]
+        });
+    }
+    for (coord, turns) in state.sieges() {
+        key.extend([coord.0 as u8, coord.1 as u8, *turns as u8]);
+    }
+    key
+}
+
+/// The first index of each group of equivalent indices
+fn distinct(count: usize, same: impl Fn(usize, usize) -> bool) -> Vec<usize> {
+    let mut kept: Vec<usize> = Vec::new();
+    for index in 0..count {
+        if !kept.iter().any(|kept| same(*kept, index)) {
+            kept.push(index);
+        }
+    }
+    kept
+}
+
+/// Every action the resistance could legally play
+fn resistance_actions(state: &RoundState) -> Vec<ResistanceAction> {
+    let coords: Vec<Coord> = state.board().coords().collect();
+    coords.iter()
+        .flat_map(|public_coord| coords.iter().map(|private_coord| ResistanceAction { public_coord: *public_coord, private_coord: *private_coord }))
+        .filter(|action| state.validate_resistance_action(action).is_ok())
+        .collect()
+}
+
+/// Every zone the suppressor could legally play
+fn suppression_actions(state: &RoundState) -> Vec<SuppressionAction> {
+    zones(state.board(), &state.config().zone).into_iter()
+        .map(|suppression_zone| SuppressionAction { suppression_zone })
+        .filter(|action| state.validate_suppression_action(action).is_ok())
+        .collect()
+}
+
+/// Every set of tiles within the zone rules, as sorted lists
+fn zones(board: &Board, rules: &ZoneRules) -> Vec<Vec<Coord>> {
+    let coords: Vec<Coord> = board.coords().collect();
+    let mut zones: Vec<Vec<Coord>> = Vec::new();
+    let mut zone: Vec<Coord> = Vec::new();
+    extend_zones(&coords, 0, rules, board, &mut zone, &mut zones);
+    zones
+}
+
+/// Add every zone that extends `zone` with tiles from `coords[start..]`
+fn extend_zones(coords: &[Coord], start: usize, rules: &ZoneRules, board: &Board, zone: &mut Vec<Coord>, zones: &mut Vec<Vec<Coord>>) {
+    if !zone.is_empty() && rules.validate(zone, board).is_ok() {
+        let mut sorted = zone.clone();
+        sorted.sort();
+        zones.push(sorted);
+    }
+    if zone.len() as u32 >= rules.max_tiles {
+        return;
+    }
+    for index in start..coords.len() {
+        zone.push(coords[index]);
+        extend_zones(coords, index + 1, rules, board, zone, zones);
+        zone.pop();
+    }
+}
+
+/// Solve a zero-sum matrix game by regret matching
+///
+/// The rows are the resistance's actions, maximizing the entries, and the columns the suppressor's,
+/// minimizing them. Uses regret matching+ with linearly weighted averages. Returns each side's
+/// average strategy, the value of playing them against each other and how exploitable they are.
+fn solve_matrix(matrix: &[Vec<f32>], iterations: u32) -> (Vec<f32>, Vec<f32>, f32, f32) {
+    let rows = matrix.len();
+    let columns = matrix.first().map_or(0, Vec::len);
+    let mut row_regrets = vec![0.0f32; rows];
+    let mut column_regrets = vec![0.0f32; columns];
+    let mut row_average = vec![0.0f32; rows];
+    let mut column_average = vec![0.0f32; columns];
+
+    for iteration in 1..=iterations.max(1) {
+        let row_strategy = regret_matching(&row_regrets);
+        let column_strategy = regret_matching(&column_regrets);
+        let row_payoffs = row_payoffs(matrix, &column_strategy);
+        let column_payoffs = column_payoffs(matrix, &row_strategy);
+        let value = dot(&row_strategy, &row_payoffs);
+        for (regret, payoff) in row_regrets.iter_mut().zip(&row_payoffs) {
+            *regret = (*regret + payoff - value).max(0.0);
+        }
+        for (regret, payoff) in column_regrets.iter_mut().zip(&column_payoffs) {
+            *regret = (*regret + value - payoff).max(0.0);
+        }
+        for (average, probability) in row_average.iter_mut().zip(&row_strategy) {
+            *average += iteration as f32 * probability;
+        }
+        for (average, probability) in column_average.iter_mut().zip(&column_strategy) {
+            *average += iteration as f32 * probability;
+        }
+    }
+
+    let row_strategy = normalized(row_average);
+    let column_strategy = normalized(column_average);
+    let row_payoffs = row_payoffs(matrix, &column_strategy);
+    let column_payoffs = column_payoffs(matrix, &row_strategy);
+    let value = dot(&row_strategy, &row_payoffs);
+    let best_row = row_payoffs.iter().cloned().fold(f32::MIN, f32::max);
+    let best_column = column_payoffs.iter().cloned().fold(f32::MAX, f32::min);
+    (row_strategy, column_strategy, value, best_row - best_column)
+}
+
+/// Play each action in proportion to its positive regret, uniformly when there is none
+fn regret_matching(regrets: &[f32]) -> Vec<f32> {
+    let total: f32 = regrets.iter().sum();
+    if total <= 0.0 {
+        return vec![1.0 / regrets.len() as f32; regrets.len()];
+    }
+    regrets.iter().map(|regret| regret / total).collect()
+}
+
+/// Scale weights to sum to one
+fn normalized(weights: Vec<f32>) -> Vec<f32> {
+    let total: f32 = weights.iter().sum();
+    weights.into_iter().map(|weight| weight / total).collect()
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+/// What each row is worth against a column strategy
+fn row_payoffs(matrix: &[Vec<f32>], column_strategy: &[f32]) -> Vec<f32> {
+    matrix.iter().map(|row| dot(row, column_strategy)).collect()
+}
+
+/// What each column is worth against a row strategy
+fn column_payoffs(matrix: &[Vec<f32>], row_strategy: &[f32]) -> Vec<f32> {
+    let columns = matrix.first().map_or(0, Vec::len);
+    (0..columns).map(|column| matrix.iter().zip(row_strategy).map(|(row, probability)| row[column] * probability).sum()).collect()
+}
+
+/// The round solved when none is given on the command line, small enough to solve in moments.
+fn small_round() -> RoundConfig {
+    let round = RoundConfig::builder().board_size(3, 2).max_turns(3).temp_turn_count(2).score_to_win(1).zone_tiles(1, 2).build();
+    // A fixed config known to be valid
+    round.unwrap()
+}
+
+/// Solve a round from the command line and print its value and the top of each side's strategy
+///
+/// Takes `--config PATH` for the round to solve (a 3x2 board over 3 turns by default), `--rules PATH`
+/// for a rhai rule set to solve it under instead of the classic rules, `--iterations N` and
+/// `--max-states N` to tune the solve, and `--top N` for how many actions of each strategy to print.
+pub fn run_cli(args: &[String]) -> Result<(), String> {
+    let mut round = small_round();
+    let mut rules = Rules::default();
+    let mut config = SolverConfig::default();
+    let mut top = 5;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let flag = arg.as_str();
+        let value = args.next().ok_or_else(|| format!("{} needs a value", flag))?;
+        match flag {
+            "--config" => round = RoundConfig::from_file(Path::new(value)).map_err(|error| error.to_string())?,
+            "--rules" => rules = rules_from_path(value)?,
+            "--iterations" => config.iterations = parse_flag(flag, value)?,
+            "--max-states" => config.max_states = parse_flag(flag, value)?,
+            "--top" => top = parse_flag(flag, value)?,
+            _ => return Err(format!("unknown option {}", flag))
+        }
+    }
+
+    let mut solver = Solver::new(config);
+    println!("solving under the {} rules", rules.name());
+    let stage = solver.solve(&round, rules).map_err(|error| error.to_string())?;
+    print!("{}", stage.summary(top));
+    println!("{} positions solved", solver.states());
+    Ok(())
+}
+
+impl Stage {
+    /// The value and the `top` most likely actions of each side, one per line
+    pub fn summary(&self, top: usize) -> String {
+        let mut summary = format!(
+            "value {:.3} to the resistance, exploitability {:.4}\n", self.value, self.exploitability
+        );
+        summary.push_str("resistance:\n");
+        for (action, probability) in self.resistance.iter().take(top) {
+            summary.push_str(&format!(
+                "  {:>6.1}%  public {:?} private {:?}\n", probability * 100.0, action.public_coord, action.private_coord
+            ));
+        }
+        summary.push_str("suppression:\n");
+        for (action, probability) in self.suppression.iter().take(top) {
+            summary.push_str(&format!("  {:>6.1}%  zone {:?}\n", probability * 100.0, action.suppression_zone));
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::solver::{run_cli, small_round, solve_matrix, SolveError, Solver, SolverConfig};
+    use crate::gameplay::round_config::RoundConfig;
+    use crate::gameplay::rules::Rules;
+    use crate::scripting::ScriptLimits;
+    use crate::scripting::rules::ScriptedRuleSet;
+
+    /// Tests that regret matching finds the mixed equilibrium of matching pennies
+    #[test]
+    fn test_matching_pennies() {
+        let (rows, columns, value, exploitability) = solve_matrix(&[vec![1.0, 0.0], vec![0.0, 1.0]], 2_000);
+        assert!((rows[0] - 0.5).abs() < 0.01 && (columns[0] - 0.5).abs() < 0.01, "{:?} {:?}", rows, columns);
+        assert!((value - 0.5).abs() < 0.01);
+        assert!(exploitability < 0.01);
+    }
+
+    /// Tests solving a round the resistance can't win
+    #[test]
+    fn test_solve_forced_loss() {
+        // Temporary tiles take longer to settle than the round lasts
+        let config = RoundConfig::builder().board_size(2, 1).zone_tiles(1, 1).max_turns(1).score_to_win(1).build().unwrap();
+        let stage = Solver::new(SolverConfig::default()).solve(&config, Rules::default()).unwrap();
+        assert!(stage.value.abs() < 1e-3, "{:?}", stage);
+        assert!(stage.exploitability.abs() < 1e-3);
+    }
+
+    /// Tests solving a round that needs a mixed strategy
+    #[test]
+    fn test_solve_small_round() {
+        let config = RoundConfig::builder().board_size(3, 1).zone_tiles(1, 1).max_turns(2).temp_turn_count(2).score_to_win(1).build().unwrap();
+        let mut solver = Solver::new(SolverConfig::default());
+        let stage = solver.solve(&config, Rules::default()).unwrap();
+        assert!(stage.value > 0.0 && stage.value < 1.0, "{:?}", stage);
+        assert!(stage.exploitability < 0.05, "{:?}", stage);
+        assert!(stage.suppression.len() > 1 && stage.resistance.len() > 1);
+        let total: f32 = stage.resistance.iter().map(|(_, probability)| probability).sum();
+        assert!((total - 1.0).abs() < 1e-3);
+        assert!(solver.states() > 1);
+    }
+
+    /// Tests solving the default round from the command line, and the summary it prints
+    #[test]
+    fn test_cli_default_round() {
+        let stage = Solver::new(SolverConfig::default()).solve(&small_round(), Rules::default()).unwrap();
+        let summary = stage.summary(2);
+        assert!(summary.starts_with(&format!("value {:.3}", stage.value)));
+        assert_eq!(summary.lines().count(), 1 + 1 + 2 + 1 + 2);
+        assert!(run_cli(&["--iterations".to_string(), "200".to_string()]).is_ok());
+        assert!(run_cli(&["--config".to_string(), "missing.toml".to_string()]).is_err());
+    }
+
+    /// Tests solving under a rule script, and that one failing stops the solve
+    #[test]
+    fn test_solve_scripted_rules() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/scripts/rules/landslide.rhai");
+        let args = |rules: &str| ["--iterations", "200", "--rules", rules].map(str::to_string);
+        assert!(run_cli(&args(path)).is_ok());
+        assert!(run_cli(&args("scripts/rules/missing.rhai")).is_err());
+
+        let script = ScriptedRuleSet::from_source("broken.rhai", "fn check_outcome(round) { throw \"no outcome\"; }", &ScriptLimits::default()).unwrap();
+        let result = Solver::new(SolverConfig::default()).solve(&small_round(), Rules::new(script));
+        assert!(matches!(&result, Err(SolveError::RulesFailed(error)) if error.rule_set == "broken.rhai"), "{:?}", result);
+    }
+
+    /// Tests the limits on what will be solved
+    #[test]
+    fn test_solve_limits() {
+        let mut solver = Solver::new(SolverConfig::default());
+        assert_eq!(solver.solve(&RoundConfig::default(), Rules::default()), Err(SolveError::BoardTooLarge { tiles: 81, max: 12 }));
+
+        let config = RoundConfig::builder().board_size(3, 2).zone_tiles(1, 2).max_turns(4).build().unwrap();
+        let mut solver = Solver::new(SolverConfig { max_states: 10, ..SolverConfig::default() });
+        assert_eq!(solver.solve(&config, Rules::default()), Err(SolveError::TooManyStates { max: 10 }));
+    }
+}
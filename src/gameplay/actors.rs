@@ -1,17 +1,38 @@
-/** Handles game logic for a given round
+/*! Handles game logic for a given round
 *
 *
  */
 
+use serde::{Serialize, Deserialize};
+
+use crate::gameplay::suppression_zone::{Rotation, ZoneShape};
 use crate::gameplay::tilemap::{Coord};
 use std::vec;
 
 /// Convenience abstraction for using the two different actions in generics.
 pub(crate) trait Action {}
 
+/// The two sides of a round.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Side {
+    Resistance,
+    Suppression
+}
+
+impl Side {
+    /// The side playing against this one.
+    pub fn opponent(&self) -> Side {
+        match self {
+            Side::Resistance => Side::Suppression,
+            Side::Suppression => Side::Resistance
+        }
+    }
+}
+
 /// A single round action taken by the resistance player.
 #[derive(Debug)]
 #[derive(Copy, Clone)]
+#[derive(Serialize, Deserialize)]
 pub(crate) struct ResistanceAction {
     pub public_coord: Coord,
     pub private_coord: Coord
@@ -26,10 +47,19 @@ impl PartialEq for ResistanceAction {
 /// A single round action taken by the suppressing player
 #[derive(Debug)]
 #[derive(Clone)]
+#[derive(Serialize, Deserialize)]
 pub(crate) struct SuppressionAction {
     pub suppression_zone: Vec<Coord>
 }
 impl Action for SuppressionAction {}
+impl SuppressionAction {
+    /// Build a zone by placing a shape template on the board
+    ///
+    /// Returns `None` if the placed shape would overflow a `Coord`.
+    pub fn shaped(shape: ZoneShape, rotation: Rotation, origin: Coord) -> Option<SuppressionAction> {
+        shape.place(origin, rotation).map(|zone| SuppressionAction { suppression_zone: zone })
+    }
+}
 impl PartialEq for SuppressionAction {
     fn eq(&self, other: &Self) -> bool {
         self.suppression_zone == other.suppression_zone
@@ -37,7 +67,7 @@ impl PartialEq for SuppressionAction {
 }
 
 /// The base actor trait shared by the two player types.
-trait Actor<ActionType: Action> {
+pub(crate) trait Actor<ActionType: Action> {
     fn take_action(&mut self, action: ActionType) {
         self.writable_action_queue().push(action)
     }
@@ -52,7 +82,7 @@ trait Actor<ActionType: Action> {
     fn writable_action_queue(&mut self) -> &mut Vec<ActionType>;
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub(crate) struct ResistanceActor {
     action_queue: Vec<ResistanceAction>
 }
@@ -76,6 +106,7 @@ impl Actor<ResistanceAction> for ResistanceActor {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SuppressionActor {
     action_queue: Vec<SuppressionAction>
 }
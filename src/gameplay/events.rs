@@ -0,0 +1,208 @@
+/*! Events emitted by a round as its state changes
+*
+* Anything that has to react to the round - the renderer, stats - turns on the round's event queue
+* and drains it each frame, rather than diffing the whole state. Events for hidden tiles are flagged,
+* so whatever passes them on to the suppressor can leave them out.
+*/
+
+use std::fmt;
+
+use crate::gameplay::actors::Side;
+use crate::gameplay::round_manager::{ActionError, GameOutcome, RoundResult, RoundState};
+use crate::gameplay::tilemap::Coord;
+
+/// A change to the round, in the order it happened.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum RoundEvent {
+    /// A turn was resolved with these results. Emitted after the tile events of the turn.
+    TurnResolved { turn: u32, results: Vec<RoundResult> },
+    /// The resistance placed a temporary tile, `hidden` if only the resistance can see it.
+    TemporaryPlaced { coord: Coord, hidden: bool },
+    /// A hidden tile was exposed and is now an ordinary temporary tile.
+    TemporaryRevealed(Coord),
+    /// A temporary tile ran out of time and was removed, `hidden` if it was never revealed.
+    TemporaryExpired { coord: Coord, hidden: bool },
+    /// A temporary tile became a permanent resistance tile.
+    PermanentCaptured(Coord),
+    /// A region of empty tiles walled off by the resistance became permanent resistance tiles.
+    RegionCaptured(Vec<Coord>),
+    /// The suppressor reclaimed a besieged permanent tile, which is now a suppressor tile.
+    TileReclaimed(Coord),
+    /// The round ended.
+    GameOver(GameOutcome),
+    /// An action was rejected, leaving the round unchanged.
+    ActionRejected { side: Side, error: ActionError },
+    /// The round was rewound to how it stood after `turn` turns, every later event no longer holds.
+    /// Listeners should rebuild what they show from the round as it now is.
+    Rewound { turn: u32 }
+}
+
+/** Keeps a round's events until they are drained
+
+A bus belongs to a single round. Cloning or forking a round gives the copy a fresh bus with queueing
+off, so moves made on the copy never turn up among the original's events. Rewinding a round keeps its
+bus, queued events included.
+
+*Fields*
+- `queue` (Option<Vec<RoundEvent>>): the events waiting to be drained, `None` while queueing is off
+*/
+#[derive(Default)]
+pub(crate) struct EventBus {
+    queue: Option<Vec<RoundEvent>>
+}
+
+impl EventBus {
+    /// Start keeping events until they are drained
+    pub fn enable_queue(&mut self) {
+        self.queue.get_or_insert_with(Vec::new);
+    }
+
+    /// Take every queued event, oldest first
+    pub fn drain(&mut self) -> Vec<RoundEvent> {
+        self.queue.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Queue an event, if queueing is on
+    pub fn emit(&mut self, event: RoundEvent) {
+        if let Some(queue) = &mut self.queue {
+            queue.push(event);
+        }
+    }
+}
+
+impl Clone for EventBus {
+    /// Copies get a bus of their own with queueing off.
+    fn clone(&self) -> Self {
+        EventBus::default()
+    }
+}
+
+impl PartialEq for EventBus {
+    /// What has been queued for listeners isn't part of a round's state.
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventBus")
+            .field("queued", &self.queue.as_ref().map(Vec::len))
+            .finish()
+    }
+}
+
+impl RoundState {
+    /// Start queueing this round's events for `drain_events`
+    pub(crate) fn enable_event_queue(&mut self) {
+        self.events_mut().enable_queue();
+    }
+
+    /// Take every event queued since the last drain, oldest first
+    pub(crate) fn drain_events(&mut self) -> Vec<RoundEvent> {
+        self.events_mut().drain()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gameplay::actors::{ResistanceAction, Side, SuppressionAction};
+    use crate::gameplay::events::RoundEvent;
+    use crate::gameplay::round_config::RoundConfig;
+    use crate::gameplay::round_manager::{ActionError, GameOutcome, RoundResult, RoundState};
+
+    /// Plays a full turn, ignoring the results
+    fn play_turn(state: &mut RoundState, zone: Vec<(i8, i8)>, public_coord: (i8, i8), private_coord: (i8, i8)) {
+        state.intake_suppression_action(SuppressionAction { suppression_zone: zone }).unwrap();
+        state.intake_resistance_action(ResistanceAction { public_coord, private_coord }).unwrap();
+    }
+
+    /// Tests the events of a turn placing a public and a hidden tile
+    #[test]
+    fn test_events_for_placed_tiles() {
+        let mut state = RoundState::default();
+        state.enable_event_queue();
+        play_turn(&mut state, vec![(8, 8)], (0, 0), (4, 4));
+        assert_eq!(state.drain_events(), vec![
+            RoundEvent::TemporaryPlaced { coord: (0, 0), hidden: false },
+            RoundEvent::TemporaryPlaced { coord: (4, 4), hidden: true },
+            RoundEvent::TurnResolved {
+                turn: 1,
+                results: vec![RoundResult::ResistanceGainsTemporary((0, 0)), RoundResult::PrivateTileGained((4, 4))]
+            }
+        ]);
+        assert!(state.drain_events().is_empty());
+    }
+
+    /// Tests that expiries, captures and the end of the round are all announced
+    #[test]
+    fn test_events_for_expiry_capture_and_game_over() {
+        let config = RoundConfig::builder().temp_turn_count(1).build().unwrap();
+        let mut state = RoundState::new(config);
+        state.enable_event_queue();
+        play_turn(&mut state, vec![(8, 8)], (0, 0), (4, 4));
+        let events = state.drain_events();
+        assert!(events.contains(&RoundEvent::TemporaryExpired { coord: (0, 0), hidden: false }));
+        assert!(events.contains(&RoundEvent::TemporaryExpired { coord: (4, 4), hidden: true }));
+
+        // On a single column the end tile only has one neighbor to surround it
+        let config = RoundConfig::builder().board_size(1, 4).score_to_win(1).zone_tiles(1, 1).build().unwrap();
+        let mut state = RoundState::new(config);
+        state.enable_event_queue();
+        play_turn(&mut state, vec![(0, 3)], (0, 0), (0, 1));
+        assert_eq!(state.drain_events(), vec![
+            RoundEvent::TemporaryPlaced { coord: (0, 0), hidden: false },
+            RoundEvent::TemporaryPlaced { coord: (0, 1), hidden: true },
+            RoundEvent::PermanentCaptured((0, 0)),
+            RoundEvent::TemporaryRevealed((0, 1)),
+            RoundEvent::TurnResolved {
+                turn: 1,
+                results: vec![
+                    RoundResult::ResistanceGainsTemporary((0, 0)),
+                    RoundResult::PrivateTileGained((0, 1)),
+                    RoundResult::ResistanceGainsPoint((0, 0)),
+                    RoundResult::PrivateTileRevealed((0, 1))
+                ]
+            },
+            RoundEvent::GameOver(GameOutcome::ResistanceWins { score: 1, turn: 1 })
+        ]);
+    }
+
+    /// Tests that rejected actions are announced, and not to copies of the round
+    #[test]
+    fn test_events_for_rejected_action() {
+        let mut state = RoundState::default();
+        state.enable_event_queue();
+        let bad = ResistanceAction { public_coord: (9, 0), private_coord: (0, 0) };
+        assert!(state.intake_resistance_action(bad).is_err());
+        assert_eq!(state.drain_events(), vec![
+            RoundEvent::ActionRejected { side: Side::Resistance, error: ActionError::OutOfBounds((9, 0)) }
+        ]);
+
+        // Copies of the round start with queueing off, and don't queue on the original's behalf
+        let mut copy = state.clone();
+        play_turn(&mut copy, vec![(8, 8)], (0, 0), (4, 4));
+        assert!(copy.drain_events().is_empty());
+        assert!(state.drain_events().is_empty());
+    }
+
+    /// Tests that the queue survives a rewind, and hears about it
+    #[test]
+    fn test_queue_survives_rewind() {
+        let mut state = RoundState::default();
+        state.enable_event_queue();
+        play_turn(&mut state, vec![(8, 8)], (0, 0), (4, 4));
+        play_turn(&mut state, vec![(8, 8)], (1, 0), (5, 4));
+        state.drain_events();
+
+        state.undo_turn().unwrap();
+        play_turn(&mut state, vec![(8, 8)], (2, 0), (6, 4));
+        let resolved = RoundEvent::TurnResolved {
+            turn: 2,
+            results: vec![RoundResult::ResistanceGainsTemporary((2, 0)), RoundResult::PrivateTileGained((6, 4))]
+        };
+        let queued = state.drain_events();
+        assert_eq!(queued.first(), Some(&RoundEvent::Rewound { turn: 1 }));
+        assert!(queued.contains(&resolved));
+    }
+}
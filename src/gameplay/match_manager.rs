@@ -0,0 +1,436 @@
+/*! Match management logic
+*
+* A match is a sequence of rounds between two players who take turns playing the resistance. The
+* first seat plays the resistance in the first round, the roles swap every round after that, and
+* the match goes to whoever wins the majority of a best-of-N. Matches that run out of rounds without
+* a majority are settled on the players' `Standing`s.
+*/
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use serde::{Serialize, Deserialize};
+
+use crate::gameplay::actors::{ResistanceAction, Side, SuppressionAction};
+use crate::gameplay::round_config::RoundConfig;
+use crate::gameplay::round_manager::{ActionError, GameOutcome, IntakeStatus, RoundState};
+use crate::gameplay::rules::Rules;
+
+/// The two players of a match, told apart by where they sat down rather than the side they play.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Seat {
+    First,
+    Second
+}
+
+impl Seat {
+    /// The player sitting opposite this one.
+    pub fn other(&self) -> Seat {
+        match self {
+            Seat::First => Seat::Second,
+            Seat::Second => Seat::First
+        }
+    }
+}
+
+/** The rules a match is played under
+
+*Fields*
+- `round` (RoundConfig): the rules every round of the match is played under
+- `best_of` (u32): the most rounds the match can last, the first seat to win a majority wins the match
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MatchConfig {
+    pub round: RoundConfig,
+    pub best_of: u32
+}
+
+impl Default for MatchConfig {
+    /// A best of three under the default round rules.
+    fn default() -> Self {
+        Self {
+            round: RoundConfig::default(),
+            best_of: 3
+        }
+    }
+}
+
+/// How a finished round counts towards the match.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoundSummary {
+    /// The seat that played the resistance.
+    pub resistance: Seat,
+    /// How the round ended.
+    pub outcome: GameOutcome,
+    /// The resistance's permanent tiles at the end of the round.
+    pub score: u32,
+    /// The number of turns the round lasted.
+    pub turns: u32
+}
+
+impl RoundSummary {
+    /// The seat that won the round, or `None` for a draw.
+    pub fn winner(&self) -> Option<Seat> {
+        match self.outcome {
+            GameOutcome::ResistanceWins { .. } => Some(self.resistance),
+            GameOutcome::SuppressionWins { .. } | GameOutcome::SuppressionReclaims { .. } => Some(self.resistance.other()),
+            GameOutcome::Draw(_) => None
+        }
+    }
+}
+
+/** A seat's record over the rounds finished so far
+
+Standings are compared on rounds won, then on the points scored as the resistance, then on the fewest
+turns used as the resistance. The greater standing is the better one.
+
+*Fields*
+- `rounds_won` (u32): the rounds this seat won, from either side
+- `resistance_points` (u32): the permanent tiles this seat captured across its resistance rounds
+- `turns_used` (u32): the turns its resistance rounds lasted, fewer being better
+*/
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Standing {
+    pub rounds_won: u32,
+    pub resistance_points: u32,
+    pub turns_used: u32
+}
+
+impl Ord for Standing {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.rounds_won.cmp(&other.rounds_won)
+            .then(self.resistance_points.cmp(&other.resistance_points))
+            .then(other.turns_used.cmp(&self.turns_used))
+    }
+}
+
+impl PartialOrd for Standing {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// The final result of a match.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MatchOutcome {
+    /// The seat won a majority of the rounds, or came out ahead on the tiebreaks.
+    Winner(Seat),
+    /// The seats finished with identical standings.
+    Draw
+}
+
+/// The reasons a match can't be started or can't take an action.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatchError {
+    /// The match would be over before it started.
+    NoRounds,
+    /// The round rules are invalid.
+    InvalidConfig(String),
+    /// The seat tried to play the side its opponent has this round.
+    WrongSide { seat: Seat, side: Side },
+    /// The current round rejected the action.
+    Action(ActionError),
+    /// The match is over.
+    MatchOver(MatchOutcome)
+}
+
+impl fmt::Display for MatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatchError::NoRounds => write!(f, "best_of must be at least 1"),
+            MatchError::InvalidConfig(error) => write!(f, "invalid round config: {}", error),
+            MatchError::WrongSide { seat, side } => write!(f, "{:?} isn't playing {:?} this round", seat, side),
+            MatchError::Action(error) => write!(f, "{}", error),
+            MatchError::MatchOver(outcome) => write!(f, "the match is over: {:?}", outcome)
+        }
+    }
+}
+
+impl std::error::Error for MatchError {}
+
+impl From<ActionError> for MatchError {
+    fn from(error: ActionError) -> Self {
+        MatchError::Action(error)
+    }
+}
+
+/** A match in progress
+
+*Fields*
+- `config` (MatchConfig): the rules the match is played under
+- `rounds` (Vec<RoundState>): every round played so far, the last being the one in progress
+- `outcome` (Option<MatchOutcome>): set once the match is decided, after which no more rounds start
+- `rules` (Rules): the rule set every round is played under, not serialized like a round's own
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchState {
+    config: MatchConfig,
+    rounds: Vec<RoundState>,
+    outcome: Option<MatchOutcome>,
+    #[serde(skip)]
+    rules: Rules
+}
+
+impl MatchState {
+    /// Start a match under the classic rules, with its first round ready to be played
+    pub fn new(config: MatchConfig) -> Result<MatchState, MatchError> {
+        MatchState::with_rules(config, Rules::default())
+    }
+
+    /// Start a match under a variant rule set, with its first round ready to be played
+    pub(crate) fn with_rules(config: MatchConfig, rules: Rules) -> Result<MatchState, MatchError> {
+        if config.best_of == 0 {
+            return Err(MatchError::NoRounds);
+        }
+        config.round.validate().map_err(|error| MatchError::InvalidConfig(error.to_string()))?;
+        let first_round = RoundState::with_rules(config.round.clone(), rules.clone());
+        Ok(MatchState { config, rounds: vec![first_round], outcome: None, rules })
+    }
+
+    /// The rules this match is played under.
+    pub fn config(&self) -> &MatchConfig {
+        &self.config
+    }
+
+    /// Every round played so far, the last being the one in progress or the final round.
+    pub fn rounds(&self) -> &[RoundState] {
+        &self.rounds
+    }
+
+    /// The round being played, or the final round once the match is over.
+    pub fn current_round(&self) -> &RoundState {
+        // A match always has at least its first round
+        self.rounds.last().unwrap()
+    }
+
+    /// The index of the current round, `0` being the first.
+    pub fn round_index(&self) -> u32 {
+        self.rounds.len() as u32 - 1
+    }
+
+    /// The outcome of the match, or `None` while it is still being played.
+    pub fn outcome(&self) -> Option<MatchOutcome> {
+        self.outcome
+    }
+
+    /// Whether the match has been decided.
+    pub fn is_over(&self) -> bool {
+        self.outcome.is_some()
+    }
+
+    /// The side a seat plays in the given round
+    pub fn side_of(seat: Seat, round_index: u32) -> Side {
+        let first_plays_resistance = round_index.is_multiple_of(2);
+        match (seat, first_plays_resistance) {
+            (Seat::First, true) | (Seat::Second, false) => Side::Resistance,
+            _ => Side::Suppression
+        }
+    }
+
+    /// The seat playing a side in the current round
+    pub fn seat_playing(&self, side: Side) -> Seat {
+        if Self::side_of(Seat::First, self.round_index()) == side { Seat::First } else { Seat::Second }
+    }
+
+    /// How every finished round went, in the order they were played
+    pub fn summaries(&self) -> Vec<RoundSummary> {
+        self.rounds.iter().enumerate().filter_map(|(index, round)| {
+            round.outcome().map(|outcome| RoundSummary {
+                resistance: if Self::side_of(Seat::First, index as u32) == Side::Resistance { Seat::First } else { Seat::Second },
+                outcome,
+                score: round.resistance_score(),
+                turns: round.current_turn()
+            })
+        }).collect()
+    }
+
+    /// A seat's record over the rounds finished so far
+    pub fn standing(&self, seat: Seat) -> Standing {
+        let mut standing = Standing::default();
+        for summary in self.summaries() {
+            if summary.winner() == Some(seat) {
+                standing.rounds_won += 1;
+            }
+            if summary.resistance == seat {
+                standing.resistance_points += summary.score;
+                standing.turns_used += summary.turns;
+            }
+        }
+        standing
+    }
+
+    /// Intake the suppressor's action for the current round
+    ///
+    /// ## Arguments:
+    /// * `seat` - the seat submitting the action, which has to be playing the suppressor this round
+    /// * `action` - the action to play
+    pub(crate) fn intake_suppression_action(&mut self, seat: Seat, action: SuppressionAction) -> Result<IntakeStatus, MatchError> {
+        self.check_seat(seat, Side::Suppression)?;
+        let status = self.current_round_mut().intake_suppression_action(action)?;
+        self.advance();
+        Ok(status)
+    }
+
+    /// Intake the resistance's action for the current round
+    ///
+    /// ## Arguments:
+    /// * `seat` - the seat submitting the action, which has to be playing the resistance this round
+    /// * `action` - the action to play
+    pub(crate) fn intake_resistance_action(&mut self, seat: Seat, action: ResistanceAction) -> Result<IntakeStatus, MatchError> {
+        self.check_seat(seat, Side::Resistance)?;
+        let status = self.current_round_mut().intake_resistance_action(action)?;
+        self.advance();
+        Ok(status)
+    }
+
+    /// Fail unless the match is open and the seat is playing the side
+    fn check_seat(&self, seat: Seat, side: Side) -> Result<(), MatchError> {
+        if let Some(outcome) = self.outcome {
+            return Err(MatchError::MatchOver(outcome));
+        }
+        if self.seat_playing(side) != seat {
+            return Err(MatchError::WrongSide { seat, side });
+        }
+        Ok(())
+    }
+
+    fn current_round_mut(&mut self) -> &mut RoundState {
+        // A match always has at least its first round
+        self.rounds.last_mut().unwrap()
+    }
+
+    /// Decide the match or start the next round once the current round is over
+    fn advance(&mut self) {
+        if !self.current_round().is_over() {
+            return;
+        }
+        self.outcome = self.check_outcome();
+        if self.outcome.is_none() {
+            self.rounds.push(RoundState::with_rules(self.config.round.clone(), self.rules.clone()));
+        }
+    }
+
+    /// Check whether a seat has clinched the match, or settle it once every round is played
+    fn check_outcome(&self) -> Option<MatchOutcome> {
+        let majority = self.config.best_of / 2 + 1;
+        let first = self.standing(Seat::First);
+        let second = self.standing(Seat::Second);
+        if first.rounds_won >= majority {
+            return Some(MatchOutcome::Winner(Seat::First));
+        }
+        if second.rounds_won >= majority {
+            return Some(MatchOutcome::Winner(Seat::Second));
+        }
+        if (self.rounds.len() as u32) < self.config.best_of {
+            return None;
+        }
+        match first.cmp(&second) {
+            Ordering::Greater => Some(MatchOutcome::Winner(Seat::First)),
+            Ordering::Less => Some(MatchOutcome::Winner(Seat::Second)),
+            Ordering::Equal => Some(MatchOutcome::Draw)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gameplay::actors::{ResistanceAction, Side, SuppressionAction};
+    use crate::gameplay::match_manager::{MatchConfig, MatchError, MatchOutcome, MatchState, Seat, Standing};
+    use crate::gameplay::round_config::RoundConfig;
+    use crate::gameplay::rules::{RuleSet, Rules};
+
+    /// A best-of with short rounds on a single column, where the end tile is captured in one turn
+    fn quick_match(best_of: u32) -> MatchState {
+        let round = RoundConfig::builder().board_size(1, 4).max_turns(2).score_to_win(1).zone_tiles(1, 1).build().unwrap();
+        MatchState::new(MatchConfig { round, best_of }).unwrap()
+    }
+
+    /// Plays a round to the end, with the resistance capturing the end tile on the first turn if `capture`
+    fn play_round(state: &mut MatchState, capture: bool) {
+        let resistance = state.seat_playing(Side::Resistance);
+        let suppression = resistance.other();
+        let index = state.round_index();
+        while state.round_index() == index && !state.is_over() {
+            let zone = if capture { vec![(0, 3)] } else { vec![(0, 0)] };
+            state.intake_suppression_action(suppression, SuppressionAction { suppression_zone: zone }).unwrap();
+            let action = ResistanceAction { public_coord: (0, 0), private_coord: (0, 1) };
+            state.intake_resistance_action(resistance, action).unwrap();
+        }
+    }
+
+    /// Tests that the seats swap sides every round
+    #[test]
+    fn test_roles_alternate() {
+        let mut state = quick_match(3);
+        assert_eq!(state.seat_playing(Side::Resistance), Seat::First);
+        let action = ResistanceAction { public_coord: (0, 0), private_coord: (0, 1) };
+        assert_eq!(
+            state.intake_resistance_action(Seat::Second, action),
+            Err(MatchError::WrongSide { seat: Seat::Second, side: Side::Resistance })
+        );
+        play_round(&mut state, true);
+        assert_eq!(state.round_index(), 1);
+        assert_eq!(state.seat_playing(Side::Resistance), Seat::Second);
+        assert_eq!(state.summaries()[0].winner(), Some(Seat::First));
+    }
+
+    /// Tests that the match ends as soon as a seat has a majority
+    #[test]
+    fn test_match_clinched_early() {
+        let mut state = quick_match(3);
+        play_round(&mut state, true);  // First wins as the resistance
+        play_round(&mut state, false);  // First wins as the suppressor
+        assert_eq!(state.outcome(), Some(MatchOutcome::Winner(Seat::First)));
+        assert_eq!(state.rounds().len(), 2);
+        assert!(matches!(
+            state.intake_resistance_action(Seat::First, ResistanceAction { public_coord: (0, 0), private_coord: (0, 0) }),
+            Err(MatchError::MatchOver(_))
+        ));
+    }
+
+    /// Tests that a split match is settled on the tiebreaks
+    #[test]
+    fn test_match_settled_on_tiebreaks() {
+        let mut state = quick_match(2);
+        play_round(&mut state, true);  // First wins as the resistance, scoring a point in one turn
+        play_round(&mut state, true);  // Second wins as the resistance the same way
+        assert_eq!(state.outcome(), Some(MatchOutcome::Draw));
+        assert_eq!(state.standing(Seat::First), Standing { rounds_won: 1, resistance_points: 1, turns_used: 1 });
+
+        let mut state = quick_match(2);
+        play_round(&mut state, false);  // Second wins as the suppressor
+        play_round(&mut state, true);  // Second wins again, as the resistance
+        assert_eq!(state.outcome(), Some(MatchOutcome::Winner(Seat::Second)));
+    }
+
+    /// The classic rules under another name
+    #[derive(Debug)]
+    struct Renamed;
+
+    impl RuleSet for Renamed {
+        fn name(&self) -> &str {
+            "renamed"
+        }
+    }
+
+    /// Tests that every round of a match is played under the match's rule set
+    #[test]
+    fn test_rounds_use_match_rules() {
+        let round = RoundConfig::builder().board_size(1, 4).max_turns(2).score_to_win(1).zone_tiles(1, 1).build().unwrap();
+        let mut state = MatchState::with_rules(MatchConfig { round, best_of: 3 }, Rules::new(Renamed)).unwrap();
+        play_round(&mut state, true);
+        assert_eq!(state.rounds().len(), 2);
+        assert!(state.rounds().iter().all(|round| round.rules().name() == "renamed"));
+    }
+
+    /// Tests that standings compare on wins, then points, then fewer turns
+    #[test]
+    fn test_standing_order() {
+        let base = Standing { rounds_won: 1, resistance_points: 3, turns_used: 10 };
+        assert!(Standing { rounds_won: 2, resistance_points: 0, turns_used: 40 } > base);
+        assert!(Standing { resistance_points: 4, ..base } > base);
+        assert!(Standing { turns_used: 9, ..base } > base);
+        assert!(MatchState::new(MatchConfig { best_of: 0, ..MatchConfig::default() }).is_err());
+    }
+}
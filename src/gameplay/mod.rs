@@ -1,5 +1,13 @@
-/** All the gameplay logic exists proxied through this module. */
+/*! All the gameplay logic exists proxied through this module. */
 
 pub mod tilemap;
 pub mod actors;
-pub mod round_manager;
\ No newline at end of file
+pub mod round_manager;
+pub mod round_config;
+pub mod suppression_zone;
+pub mod snapshot;
+pub mod replay;
+pub mod events;
+pub mod views;
+pub mod match_manager;
+pub mod rules;
\ No newline at end of file
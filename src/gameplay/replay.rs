@@ -0,0 +1,447 @@
+/*! Recording and replaying whole rounds
+*
+* A replay is the header a round was started with (its rules and rule set, who played it and the
+* seed they played with) followed by every resolved pair of actions and the results the engine produced for
+* them. A `ReplayRecorder` attached to a `RoundState` writes one as the round is played, and a
+* `ReplayPlayer` plays it back on a fresh round, checking every turn comes out the same.
+*
+* Tournaments save a replay of every game to their archive, and `run_cli` checks one plays back.
+*/
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::{Serialize, Deserialize};
+
+use crate::gameplay::actors::{ResistanceAction, SuppressionAction};
+use crate::gameplay::round_config::RoundConfig;
+use crate::gameplay::round_manager::{ActionError, GameOutcome, IntakeStatus, RoundResult, RoundState};
+use crate::gameplay::rules::Rules;
+use crate::tournament::rules_from_path;
+
+/// The replay format written by this build. Bump it whenever the replay layout changes.
+pub const REPLAY_FORMAT_VERSION: u32 = 2;
+
+/// The version of the engine that recorded a replay.
+pub const ENGINE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/** Everything needed to set up a round before replaying it
+
+*Fields*
+- `format_version` (u32): the replay format the file was written in
+- `engine_version` (String): the version of the engine that recorded the round
+- `config` (RoundConfig): the rules the round was played under
+- `rule_set` (String): the name of the rule set the round was played under
+- `resistance_player` (String): the name of whoever played the resistance
+- `suppression_player` (String): the name of whoever played the suppressor
+- `seed` (u64): the seed any randomized player used, so their games can be reproduced
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayHeader {
+    pub format_version: u32,
+    pub engine_version: String,
+    pub config: RoundConfig,
+    pub rule_set: String,
+    pub resistance_player: String,
+    pub suppression_player: String,
+    pub seed: u64
+}
+
+/// A single resolved turn: the actions both sides played and what came of them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ReplayTurn {
+    pub suppression: SuppressionAction,
+    pub resistance: ResistanceAction,
+    pub results: Vec<RoundResult>
+}
+
+/// A recorded round, in the order it was played.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Replay {
+    pub header: ReplayHeader,
+    pub turns: Vec<ReplayTurn>,
+    pub outcome: Option<GameOutcome>  // Only set if the round was played to the end
+}
+
+/// Just enough of a replay to check its version before parsing the rest.
+#[derive(Deserialize)]
+struct ReplayVersion {
+    header: ReplayVersionHeader
+}
+
+#[derive(Deserialize)]
+struct ReplayVersionHeader {
+    format_version: u32
+}
+
+/// Everything that can go wrong loading or playing back a replay.
+#[derive(Debug)]
+pub(crate) enum ReplayError {
+    /// The replay was written in an incompatible format.
+    UnsupportedVersion { found: u32, supported: u32 },
+    /// The replay's config doesn't describe a playable round.
+    InvalidConfig(String),
+    /// The replay was recorded under a different rule set than the one given to play it back.
+    RulesMismatch { recorded: String, given: String },
+    /// An action in the replay was rejected when it was played back.
+    Rejected { turn: u32, error: ActionError },
+    /// A turn played back with different results than were recorded.
+    Diverged { turn: u32, recorded: Vec<RoundResult>, replayed: Vec<RoundResult> },
+    /// The round played back to a different outcome than was recorded.
+    OutcomeMismatch { recorded: Option<GameOutcome>, replayed: Option<GameOutcome> },
+    /// The replay couldn't be written or parsed.
+    Serde(String),
+    /// The replay file couldn't be read or written.
+    Io(std::io::Error)
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::UnsupportedVersion { found, supported } => write!(
+                f, "replay format version {} isn't supported, expected {}", found, supported
+            ),
+            ReplayError::InvalidConfig(message) => write!(f, "replay has an invalid config: {}", message),
+            ReplayError::RulesMismatch { recorded, given } => write!(
+                f, "replay was recorded under the {} rules, not the {} rules", recorded, given
+            ),
+            ReplayError::Rejected { turn, error } => write!(f, "turn {} of the replay was rejected: {}", turn, error),
+            ReplayError::Diverged { turn, recorded, replayed } => write!(
+                f, "turn {} diverged from the recording: recorded {:?}, replayed {:?}", turn, recorded, replayed
+            ),
+            ReplayError::OutcomeMismatch { recorded, replayed } => write!(
+                f, "replay ended with {:?} but was recorded as {:?}", replayed, recorded
+            ),
+            ReplayError::Serde(message) => write!(f, "couldn't serialize replay: {}", message),
+            ReplayError::Io(error) => write!(f, "couldn't access replay: {}", error)
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<std::io::Error> for ReplayError {
+    fn from(error: std::io::Error) -> Self {
+        ReplayError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for ReplayError {
+    fn from(error: serde_json::Error) -> Self {
+        ReplayError::Serde(error.to_string())
+    }
+}
+
+impl ReplayHeader {
+    /// A header for a round about to be played by this build of the engine
+    ///
+    /// ## Arguments:
+    /// * `config` - the rules the round is played under
+    /// * `rule_set` - the name of the rule set the round is played under
+    /// * `resistance_player` - the name of whoever plays the resistance
+    /// * `suppression_player` - the name of whoever plays the suppressor
+    /// * `seed` - the seed randomized players were given
+    pub fn new(config: RoundConfig, rule_set: &str, resistance_player: &str, suppression_player: &str, seed: u64) -> ReplayHeader {
+        ReplayHeader {
+            format_version: REPLAY_FORMAT_VERSION,
+            engine_version: ENGINE_VERSION.to_string(),
+            config,
+            rule_set: rule_set.to_string(),
+            resistance_player: resistance_player.to_string(),
+            suppression_player: suppression_player.to_string(),
+            seed
+        }
+    }
+}
+
+impl Replay {
+    /// Serialize the replay to JSON
+    pub fn to_json(&self) -> Result<String, ReplayError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Parse a replay from JSON, checking its format version
+    pub fn from_json(source: &str) -> Result<Replay, ReplayError> {
+        let version: ReplayVersion = serde_json::from_str(source)?;
+        if version.header.format_version != REPLAY_FORMAT_VERSION {
+            return Err(ReplayError::UnsupportedVersion { found: version.header.format_version, supported: REPLAY_FORMAT_VERSION });
+        }
+        Ok(serde_json::from_str(source)?)
+    }
+
+    /// Save the replay to a file
+    pub fn save_file(&self, path: &Path) -> Result<(), ReplayError> {
+        fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+
+    /// Load a replay from a file
+    pub fn load_file(path: &Path) -> Result<Replay, ReplayError> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+}
+
+/// Writes a replay as a round is played, one entry per resolved turn.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ReplayRecorder {
+    replay: Replay
+}
+
+impl ReplayRecorder {
+    /// Start an empty recording under the given header
+    pub fn new(header: ReplayHeader) -> ReplayRecorder {
+        ReplayRecorder { replay: Replay { header, turns: Vec::new(), outcome: None } }
+    }
+
+    /// Record a resolved turn, called by the round as it resolves each one
+    pub(crate) fn record(&mut self, suppression: SuppressionAction, resistance: ResistanceAction, results: Vec<RoundResult>) {
+        self.replay.turns.push(ReplayTurn { suppression, resistance, results });
+    }
+
+    /// Record the round's outcome, or clear it if the round was rewound
+    pub(crate) fn set_outcome(&mut self, outcome: Option<GameOutcome>) {
+        self.replay.outcome = outcome;
+    }
+
+    /// Drop every turn after the given number, for rounds that were rewound
+    pub(crate) fn truncate(&mut self, turns: u32) {
+        self.replay.turns.truncate(turns as usize);
+    }
+
+    /// Finish recording, handing back the replay
+    pub fn into_replay(self) -> Replay {
+        self.replay
+    }
+}
+
+impl RoundState {
+    /// Start recording this round to a replay
+    ///
+    /// Turns already played are replayed on a fresh round to recover their results, so the recording
+    /// always covers the whole round. Any recorder already attached is replaced.
+    ///
+    /// ## Arguments:
+    /// * `resistance_player` - the name of whoever plays the resistance
+    /// * `suppression_player` - the name of whoever plays the suppressor
+    /// * `seed` - the seed randomized players were given
+    pub(crate) fn attach_recorder(&mut self, resistance_player: &str, suppression_player: &str, seed: u64) {
+        let header = ReplayHeader::new(self.config().clone(), self.rules().name(), resistance_player, suppression_player, seed);
+        let mut backfill = RoundState::with_rules(self.config().clone(), self.rules().clone());
+        backfill.set_recorder(Some(ReplayRecorder::new(header)));
+        for (suppression, resistance) in self.turn_history() {
+            // The history was accepted once, so it's accepted again
+            let _ = backfill.intake_suppression_action(suppression.clone());
+            let _ = backfill.intake_resistance_action(*resistance);
+        }
+        self.set_recorder(backfill.take_recorder());
+    }
+
+    /// Stop recording, handing back the replay recorded so far
+    pub(crate) fn detach_recorder(&mut self) -> Option<Replay> {
+        self.take_recorder().map(ReplayRecorder::into_replay)
+    }
+}
+
+/// Plays a replay back on a fresh round, one turn at a time, checking it against the recording.
+pub(crate) struct ReplayPlayer {
+    replay: Replay,
+    state: RoundState,
+    next_turn: usize
+}
+
+impl ReplayPlayer {
+    /// Set up a fresh round under the replay's header, for replays recorded under the classic rules
+    pub fn new(replay: Replay) -> Result<ReplayPlayer, ReplayError> {
+        ReplayPlayer::with_rules(replay, Rules::default())
+    }
+
+    /// Set up a fresh round under the replay's header and the rule set it was recorded under
+    pub(crate) fn with_rules(replay: Replay, rules: Rules) -> Result<ReplayPlayer, ReplayError> {
+        let format_version = replay.header.format_version;
+        if format_version != REPLAY_FORMAT_VERSION {
+            return Err(ReplayError::UnsupportedVersion { found: format_version, supported: REPLAY_FORMAT_VERSION });
+        }
+        if rules.name() != replay.header.rule_set {
+            return Err(ReplayError::RulesMismatch { recorded: replay.header.rule_set.clone(), given: rules.name().to_string() });
+        }
+        replay.header.config.validate().map_err(|error| ReplayError::InvalidConfig(error.to_string()))?;
+        let state = RoundState::with_rules(replay.header.config.clone(), rules);
+        Ok(ReplayPlayer { replay, state, next_turn: 0 })
+    }
+
+    /// Play back the next turn, checking its results match the recording
+    ///
+    /// Returns `Ok(false)` once there are no turns left to play.
+    pub fn step(&mut self) -> Result<bool, ReplayError> {
+        let turn = match self.replay.turns.get(self.next_turn) {
+            Some(turn) => turn,
+            None => return Ok(false)
+        };
+        let index = self.next_turn as u32;
+        let status = self.state.intake_suppression_action(turn.suppression.clone())
+            .and_then(|_| self.state.intake_resistance_action(turn.resistance))
+            .map_err(|error| ReplayError::Rejected { turn: index, error })?;
+        let replayed = match status {
+            IntakeStatus::TurnResolved(results) => results,
+            IntakeStatus::AwaitingOpponent => Vec::new()
+        };
+        if replayed != turn.results {
+            return Err(ReplayError::Diverged { turn: index, recorded: turn.results.clone(), replayed });
+        }
+        self.next_turn += 1;
+        Ok(true)
+    }
+
+    /// Play back every remaining turn and check the round ends the way it was recorded
+    ///
+    /// Returns the round as it stands at the end of the replay.
+    pub fn verify(mut self) -> Result<RoundState, ReplayError> {
+        while self.step()? {}
+        if self.state.outcome() != self.replay.outcome {
+            return Err(ReplayError::OutcomeMismatch { recorded: self.replay.outcome, replayed: self.state.outcome() });
+        }
+        Ok(self.state)
+    }
+}
+
+/// Play a replay file back from the command line, checking it against its recording
+///
+/// Takes the path of the replay and `--rules PATH` for the rhai rule set it was recorded under, if
+/// it wasn't the classic rules.
+pub fn run_cli(args: &[String]) -> Result<(), String> {
+    let mut rules = None;
+    let mut path = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--rules" => rules = Some(rules_from_path(args.next().ok_or("--rules needs a value")?)?),
+            flag if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+            file => path = Some(file.to_string())
+        }
+    }
+
+    let path = path.ok_or("no replay given")?;
+    let replay = Replay::load_file(Path::new(&path)).map_err(|error| error.to_string())?;
+    let header = replay.header.clone();
+    let player = match rules {
+        Some(rules) => ReplayPlayer::with_rules(replay, rules),
+        None => ReplayPlayer::new(replay)
+    };
+    let state = player.and_then(ReplayPlayer::verify).map_err(|error| error.to_string())?;
+    println!(
+        "{} (resistance) v {} (suppression), seed {}, under the {} rules: {} turns played back",
+        header.resistance_player, header.suppression_player, header.seed, header.rule_set, state.current_turn()
+    );
+    match state.outcome() {
+        Some(outcome) => println!("ended with {:?}", outcome),
+        None => println!("the round wasn't played to the end")
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gameplay::actors::{ResistanceAction, SuppressionAction};
+    use crate::gameplay::replay::{run_cli, Replay, ReplayError, ReplayPlayer, REPLAY_FORMAT_VERSION};
+    use crate::gameplay::round_config::RoundConfig;
+    use crate::gameplay::round_manager::{RoundResult, RoundState};
+    use crate::gameplay::rules::Rules;
+    use crate::scripting::ScriptLimits;
+    use crate::scripting::rules::ScriptedRuleSet;
+
+    /// Plays a short round to the end with a recorder attached from the start
+    fn recorded_round() -> (RoundState, Replay) {
+        let config = RoundConfig::builder().board_size(5, 5).max_turns(4).build().unwrap();
+        let mut state = RoundState::new(config);
+        state.attach_recorder("resistance", "suppressor", 7);
+        for turn in 0..4 {
+            state.intake_suppression_action(SuppressionAction { suppression_zone: vec![(4, turn)] }).unwrap();
+            state.intake_resistance_action(ResistanceAction { public_coord: (turn, 0), private_coord: (turn, 2) }).unwrap();
+        }
+        let replay = state.detach_recorder().unwrap();
+        (state, replay)
+    }
+
+    /// Tests that a recorded round plays back to the same state
+    #[test]
+    fn test_replay_round_trip() {
+        let (state, replay) = recorded_round();
+        assert_eq!(replay.turns.len(), 4);
+        assert_eq!(replay.outcome, state.outcome());
+        assert_eq!(replay.header.seed, 7);
+        assert_eq!(replay.header.rule_set, "classic");
+
+        let parsed = Replay::from_json(&replay.to_json().unwrap()).unwrap();
+        assert_eq!(parsed, replay);
+        let replayed = ReplayPlayer::new(parsed).unwrap().verify().unwrap();
+        assert_eq!(replayed.tiles(), state.tiles());
+    }
+
+    /// Tests that attaching mid-round still records the turns already played
+    #[test]
+    fn test_attach_recorder_backfills() {
+        let (mut state, replay) = recorded_round();
+        state.rewind_to(2).unwrap();
+        state.attach_recorder("resistance", "suppressor", 7);
+        assert_eq!(state.detach_recorder().unwrap().turns, replay.turns[..2]);
+    }
+
+    /// Tests that tampered results and actions are caught when playing back
+    #[test]
+    fn test_replay_detects_divergence() {
+        let (_, replay) = recorded_round();
+
+        let mut tampered = replay.clone();
+        tampered.turns[1].results.push(RoundResult::ResistanceGainsPoint((0, 0)));
+        assert!(matches!(ReplayPlayer::new(tampered).unwrap().verify(), Err(ReplayError::Diverged { turn: 1, .. })));
+
+        let mut tampered = replay.clone();
+        tampered.turns[2].resistance.public_coord = (9, 9);
+        assert!(matches!(ReplayPlayer::new(tampered).unwrap().verify(), Err(ReplayError::Rejected { turn: 2, .. })));
+
+        let mut tampered = replay;
+        tampered.outcome = None;
+        assert!(matches!(ReplayPlayer::new(tampered).unwrap().verify(), Err(ReplayError::OutcomeMismatch { .. })));
+    }
+
+    /// Tests that a variant round only plays back under the rule set it was recorded with
+    #[test]
+    fn test_replay_keeps_rule_set() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/scripts/rules/landslide.rhai");
+        let landslide = || Rules::new(ScriptedRuleSet::load(std::path::Path::new(path), &ScriptLimits::default()).unwrap());
+        let mut state = RoundState::with_rules(RoundConfig::builder().board_size(5, 5).max_turns(4).build().unwrap(), landslide());
+        state.attach_recorder("resistance", "suppressor", 7);
+        for turn in 0..4 {
+            state.intake_suppression_action(SuppressionAction { suppression_zone: vec![(4, turn)] }).unwrap();
+            state.intake_resistance_action(ResistanceAction { public_coord: (turn, 0), private_coord: (turn, 2) }).unwrap();
+        }
+        let replay = state.detach_recorder().unwrap();
+        assert_eq!(replay.header.rule_set, "landslide");
+
+        let replayed = ReplayPlayer::with_rules(replay.clone(), landslide()).unwrap().verify().unwrap();
+        assert_eq!(replayed.tiles(), state.tiles());
+
+        let file = std::env::temp_dir().join("deepphase_replay_rules_test.json");
+        replay.save_file(&file).unwrap();
+        let args = |rules: &[&str]| [&[file.to_str().unwrap()], rules].concat().iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert!(run_cli(&args(&["--rules", path])).is_ok());
+        assert!(run_cli(&args(&[])).unwrap_err().contains("recorded under the landslide rules"));
+        std::fs::remove_file(file).unwrap();
+        // Never quietly played back under the classic rules
+        assert!(matches!(
+            ReplayPlayer::new(replay),
+            Err(ReplayError::RulesMismatch { recorded, given }) if recorded == "landslide" && given == "classic"
+        ));
+    }
+
+    /// Tests that replays from another format version are refused
+    #[test]
+    fn test_replay_version_mismatch() {
+        let (_, mut replay) = recorded_round();
+        replay.header.format_version = REPLAY_FORMAT_VERSION + 1;
+        let result = Replay::from_json(&replay.to_json().unwrap());
+        assert!(matches!(result, Err(ReplayError::UnsupportedVersion { .. })));
+    }
+}
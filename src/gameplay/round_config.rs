@@ -0,0 +1,431 @@
+/*! Configuration for the rules of a single round
+*
+* Rounds are configured by a `RoundConfig`, which can be assembled with a `RoundConfigBuilder` or
+* loaded from a TOML, JSON or RON file so the rules can be balanced without recompiling.
+*/
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::{Serialize, Deserialize};
+
+use crate::gameplay::suppression_zone::{ZoneRules, ZoneShape};
+
+/// The largest board dimension that still fits every coordinate in a `Coord`.
+pub const MAX_BOARD_DIMENSION: u8 = i8::MAX as u8;
+
+/** The rules of a single round
+
+*Fields*
+- `max_turns` (u32): the number of turns the resistance player has to win
+- `temp_turn_count` (u32): the amount of turns a temp resistance tile has until it returns to normal
+- `score_to_win` (u32): the number of resistance perm tiles needed for the resistance player to win
+- `board_width` (u8): the number of columns on the board
+- `board_height` (u8): the number of rows on the board
+- `zone` (ZoneRules): the limits placed on the suppressor's zone each turn
+- `capture_rule` (CaptureRule): how temp resistance tiles become permanent
+- `reclaim` (ReclaimRules): whether and how the suppressor can take permanent tiles back
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoundConfig {
+    pub max_turns: u32,
+    pub temp_turn_count: u32,
+    pub score_to_win: u32,
+    pub board_width: u8,
+    pub board_height: u8,
+    pub zone: ZoneRules,
+    pub capture_rule: CaptureRule,
+    pub reclaim: ReclaimRules
+}
+
+/// The ways resistance tiles can be captured and made permanent.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CaptureRule {
+    /// A temp tile is captured once all four of its neighbors on the board are resistance tiles.
+    #[default]
+    FourNeighbor,
+    /// Any region of the board walled off from the edge by resistance tiles is captured whole,
+    /// along with the temp tiles walling it in.
+    Enclosure
+}
+
+/** How the suppressor can win permanent tiles back from the resistance
+
+A permanent tile is besieged on a turn when the suppression zone covers it while none of its
+neighbors is held by the resistance. A tile besieged for `siege_turns` turns in a row is reclaimed,
+becoming a `Suppressor` tile the resistance can never place on again.
+
+*Fields*
+- `siege_turns` (Option<u32>): the consecutive turns of siege it takes to reclaim a tile, `None` turns reclaiming off
+- `tiles_to_win` (Option<u32>): the number of reclaimed tiles that wins the round for the suppressor, `None` if it can't
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReclaimRules {
+    pub siege_turns: Option<u32>,
+    pub tiles_to_win: Option<u32>
+}
+
+/// The file formats a `RoundConfig` can be loaded from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+    Ron
+}
+
+/// Everything that can go wrong loading or validating a `RoundConfig`.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The round would be over before it started.
+    NoTurns,
+    /// Temp tiles would expire the moment they were placed.
+    NoTempDuration,
+    /// The resistance would win without doing anything.
+    NoScoreToWin,
+    /// The board has no tiles, or more than fit in a `Coord`.
+    InvalidBoardSize { width: u8, height: u8 },
+    /// The resistance can never hold enough perm tiles to win.
+    ScoreExceedsBoard { score_to_win: u32, tiles: u32 },
+    /// The zone limits allow no zone at all.
+    InvalidZoneLimits { min_tiles: u32, max_tiles: u32 },
+    /// The suppressor could cover the whole board in a single turn.
+    ZoneCoversBoard { max_tiles: u32, tiles: u32 },
+    /// An allowed zone shape can never be played within the zone limits.
+    ZoneShapeOutOfLimits(ZoneShape),
+    /// An allowed zone shape covers no tiles, so no zone can ever match it.
+    EmptyZoneShape(ZoneShape),
+    /// Tiles would be reclaimed without a siege, or the suppressor could never reclaim enough to win.
+    InvalidReclaimRules,
+    /// The file extension doesn't match a known format.
+    UnknownFormat(String),
+    /// The config file couldn't be read.
+    Io(std::io::Error),
+    /// The config file couldn't be parsed.
+    Parse { format: ConfigFormat, message: String }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::NoTurns => write!(f, "max_turns must be at least 1"),
+            ConfigError::NoTempDuration => write!(f, "temp_turn_count must be at least 1"),
+            ConfigError::NoScoreToWin => write!(f, "score_to_win must be at least 1"),
+            ConfigError::InvalidBoardSize { width, height } => write!(
+                f, "a {}x{} board is invalid, each side must be between 1 and {}", width, height, MAX_BOARD_DIMENSION
+            ),
+            ConfigError::ScoreExceedsBoard { score_to_win, tiles } => write!(
+                f, "score_to_win of {} can't be reached on a board of {} tiles", score_to_win, tiles
+            ),
+            ConfigError::InvalidZoneLimits { min_tiles, max_tiles } => write!(
+                f, "zone limits of {} to {} tiles allow no zone", min_tiles, max_tiles
+            ),
+            ConfigError::ZoneCoversBoard { max_tiles, tiles } => write!(
+                f, "a zone of {} tiles can cover the whole board of {} tiles", max_tiles, tiles
+            ),
+            ConfigError::ZoneShapeOutOfLimits(shape) => write!(
+                f, "zone shape {:?} doesn't fit within the zone tile limits", shape
+            ),
+            ConfigError::EmptyZoneShape(shape) => write!(f, "zone shape {:?} covers no tiles", shape),
+            ConfigError::InvalidReclaimRules => write!(
+                f, "reclaim siege_turns must be at least 1, and tiles_to_win must be between 1 and the tile count with reclaiming on"
+            ),
+            ConfigError::UnknownFormat(extension) => write!(f, "unknown config format '{}'", extension),
+            ConfigError::Io(error) => write!(f, "couldn't read config: {}", error),
+            ConfigError::Parse { format, message } => write!(f, "couldn't parse {:?} config: {}", format, message)
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(error: std::io::Error) -> Self {
+        ConfigError::Io(error)
+    }
+}
+
+impl Default for RoundConfig {
+    /// The default rules of a round.
+    fn default() -> Self {
+        Self {
+            max_turns: 20,
+            temp_turn_count: 3,
+            score_to_win: 5,
+            board_width: 9,
+            board_height: 9,
+            zone: ZoneRules::default(),
+            capture_rule: CaptureRule::default(),
+            reclaim: ReclaimRules::default()
+        }
+    }
+}
+
+impl ConfigFormat {
+    /// Get the format matching a file extension
+    pub fn from_extension(extension: &str) -> Option<ConfigFormat> {
+        match extension.to_ascii_lowercase().as_str() {
+            "toml" => Some(ConfigFormat::Toml),
+            "json" => Some(ConfigFormat::Json),
+            "ron" => Some(ConfigFormat::Ron),
+            _ => None
+        }
+    }
+}
+
+impl RoundConfig {
+    /// Start building a config from the defaults.
+    pub fn builder() -> RoundConfigBuilder {
+        RoundConfigBuilder::default()
+    }
+
+    /// The number of tiles on the board.
+    pub fn board_tiles(&self) -> u32 {
+        self.board_width as u32 * self.board_height as u32
+    }
+
+    /// Check that a round can actually be played with this config
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.max_turns == 0 {
+            return Err(ConfigError::NoTurns);
+        }
+        if self.temp_turn_count == 0 {
+            return Err(ConfigError::NoTempDuration);
+        }
+        if self.score_to_win == 0 {
+            return Err(ConfigError::NoScoreToWin);
+        }
+        let valid_side = |side: u8| (1..=MAX_BOARD_DIMENSION).contains(&side);
+        if !valid_side(self.board_width) || !valid_side(self.board_height) {
+            return Err(ConfigError::InvalidBoardSize { width: self.board_width, height: self.board_height });
+        }
+        let tiles = self.board_tiles();
+        if self.score_to_win > tiles {
+            return Err(ConfigError::ScoreExceedsBoard { score_to_win: self.score_to_win, tiles });
+        }
+        if self.zone.max_tiles == 0 || self.zone.min_tiles > self.zone.max_tiles {
+            return Err(ConfigError::InvalidZoneLimits { min_tiles: self.zone.min_tiles, max_tiles: self.zone.max_tiles });
+        }
+        if self.zone.max_tiles >= tiles {
+            return Err(ConfigError::ZoneCoversBoard { max_tiles: self.zone.max_tiles, tiles });
+        }
+        if let Some(shape) = self.zone.shapes.iter().find(|shape| shape.tile_count() == 0) {
+            return Err(ConfigError::EmptyZoneShape(*shape));
+        }
+        let zone_limits = self.zone.min_tiles..=self.zone.max_tiles;
+        if let Some(shape) = self.zone.shapes.iter().find(|shape| !zone_limits.contains(&shape.tile_count())) {
+            return Err(ConfigError::ZoneShapeOutOfLimits(*shape));
+        }
+        let reclaim = &self.reclaim;
+        let valid_siege = reclaim.siege_turns.is_none_or(|turns| turns > 0);
+        let valid_target = match reclaim.tiles_to_win {
+            Some(target) => reclaim.siege_turns.is_some() && (1..=tiles).contains(&target),
+            None => true
+        };
+        if !valid_siege || !valid_target {
+            return Err(ConfigError::InvalidReclaimRules);
+        }
+        Ok(())
+    }
+
+    /// Parse and validate a config in the given format
+    ///
+    /// ## Arguments:
+    /// * `source` - the contents of the config
+    /// * `format` - the format the contents are written in
+    pub fn from_str_as(source: &str, format: ConfigFormat) -> Result<RoundConfig, ConfigError> {
+        let parse_error = |message: String| ConfigError::Parse { format, message };
+        let config: RoundConfig = match format {
+            ConfigFormat::Toml => toml::from_str(source).map_err(|e| parse_error(e.to_string()))?,
+            ConfigFormat::Json => serde_json::from_str(source).map_err(|e| parse_error(e.to_string()))?,
+            ConfigFormat::Ron => ron::from_str(source).map_err(|e| parse_error(e.to_string()))?
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Load and validate a config file, picking the format from its extension
+    ///
+    /// ## Arguments:
+    /// * `path` - a `.toml`, `.json` or `.ron` file
+    pub fn from_file(path: &Path) -> Result<RoundConfig, ConfigError> {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+        let format = ConfigFormat::from_extension(extension)
+            .ok_or_else(|| ConfigError::UnknownFormat(extension.to_string()))?;
+        let source = fs::read_to_string(path)?;
+        Self::from_str_as(&source, format)
+    }
+}
+
+/// Builds a `RoundConfig` from the defaults, validating it on `build`.
+#[derive(Debug, Clone, Default)]
+pub struct RoundConfigBuilder {
+    config: RoundConfig
+}
+
+impl RoundConfigBuilder {
+    pub fn max_turns(mut self, max_turns: u32) -> Self {
+        self.config.max_turns = max_turns;
+        self
+    }
+
+    pub fn temp_turn_count(mut self, temp_turn_count: u32) -> Self {
+        self.config.temp_turn_count = temp_turn_count;
+        self
+    }
+
+    pub fn score_to_win(mut self, score_to_win: u32) -> Self {
+        self.config.score_to_win = score_to_win;
+        self
+    }
+
+    pub fn board_size(mut self, width: u8, height: u8) -> Self {
+        self.config.board_width = width;
+        self.config.board_height = height;
+        self
+    }
+
+    pub fn zone_tiles(mut self, min_tiles: u32, max_tiles: u32) -> Self {
+        self.config.zone.min_tiles = min_tiles;
+        self.config.zone.max_tiles = max_tiles;
+        self
+    }
+
+    pub fn zone_contiguous(mut self, contiguous: bool) -> Self {
+        self.config.zone.contiguous = contiguous;
+        self
+    }
+
+    pub fn zone_shapes(mut self, shapes: Vec<ZoneShape>) -> Self {
+        self.config.zone.shapes = shapes;
+        self
+    }
+
+    pub fn capture_rule(mut self, capture_rule: CaptureRule) -> Self {
+        self.config.capture_rule = capture_rule;
+        self
+    }
+
+    pub fn reclaim(mut self, siege_turns: u32, tiles_to_win: Option<u32>) -> Self {
+        self.config.reclaim = ReclaimRules { siege_turns: Some(siege_turns), tiles_to_win };
+        self
+    }
+
+    /// Validate and return the config
+    pub fn build(self) -> Result<RoundConfig, ConfigError> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gameplay::round_config::{ConfigError, ConfigFormat, RoundConfig};
+    use crate::gameplay::suppression_zone::ZoneShape;
+
+    /// Tests that the defaults are a playable config
+    #[test]
+    fn test_default_config_is_valid() {
+        assert!(RoundConfig::default().validate().is_ok());
+    }
+
+    /// Tests that reclaim rules need a siege length, and a reachable target if they have one
+    #[test]
+    fn test_validate_reclaim_rules() {
+        assert!(RoundConfig::builder().reclaim(3, Some(2)).build().is_ok());
+        assert!(matches!(RoundConfig::builder().reclaim(0, None).build(), Err(ConfigError::InvalidReclaimRules)));
+        assert!(matches!(RoundConfig::builder().reclaim(3, Some(0)).build(), Err(ConfigError::InvalidReclaimRules)));
+        let mut config = RoundConfig::default();
+        config.reclaim.tiles_to_win = Some(1);
+        assert!(matches!(config.validate(), Err(ConfigError::InvalidReclaimRules)));
+    }
+
+    /// Tests that the builder only overrides what it is given
+    #[test]
+    fn test_builder_overrides() {
+        let config = RoundConfig::builder()
+            .max_turns(12)
+            .score_to_win(3)
+            .build()
+            .unwrap();
+        assert_eq!(config.max_turns, 12);
+        assert_eq!(config.score_to_win, 3);
+        assert_eq!(config.temp_turn_count, RoundConfig::default().temp_turn_count);
+    }
+
+    /// Tests that a win score bigger than the board is rejected
+    #[test]
+    fn test_builder_rejects_unreachable_score() {
+        let result = RoundConfig::builder().board_size(2, 2).zone_tiles(1, 1).score_to_win(5).build();
+        assert!(matches!(result, Err(ConfigError::ScoreExceedsBoard { score_to_win: 5, tiles: 4 })));
+    }
+
+    /// Tests that the impossible combos are rejected
+    #[test]
+    fn test_builder_rejects_impossible_configs() {
+        assert!(matches!(RoundConfig::builder().max_turns(0).build(), Err(ConfigError::NoTurns)));
+        assert!(matches!(RoundConfig::builder().temp_turn_count(0).build(), Err(ConfigError::NoTempDuration)));
+        assert!(matches!(RoundConfig::builder().board_size(0, 4).build(), Err(ConfigError::InvalidBoardSize { .. })));
+        assert!(matches!(RoundConfig::builder().board_size(200, 4).build(), Err(ConfigError::InvalidBoardSize { .. })));
+        assert!(matches!(RoundConfig::builder().zone_tiles(3, 2).build(), Err(ConfigError::InvalidZoneLimits { .. })));
+        assert!(matches!(
+            RoundConfig::builder().board_size(2, 2).score_to_win(1).zone_tiles(1, 4).build(),
+            Err(ConfigError::ZoneCoversBoard { .. })
+        ));
+        assert!(matches!(
+            RoundConfig::builder().zone_tiles(1, 4).zone_shapes(vec![ZoneShape::Cross]).build(),
+            Err(ConfigError::ZoneShapeOutOfLimits(ZoneShape::Cross))
+        ));
+        assert!(matches!(
+            RoundConfig::builder().zone_tiles(0, 4).zone_shapes(vec![ZoneShape::Line(0)]).build(),
+            Err(ConfigError::EmptyZoneShape(ZoneShape::Line(0)))
+        ));
+    }
+
+    /// Tests that zone shapes can be loaded from a file
+    #[test]
+    fn test_parse_zone_shapes() {
+        let toml = "[zone]\nmax_tiles = 5\ncontiguous = false\nshapes = [\"Square\", { Line = 3 }]\n";
+        let config = RoundConfig::from_str_as(toml, ConfigFormat::Toml).unwrap();
+        assert_eq!(config.zone.shapes, vec![ZoneShape::Square, ZoneShape::Line(3)]);
+        assert!(!config.zone.contiguous);
+    }
+
+    /// Tests that every supported format parses, filling in missing fields from the defaults
+    #[test]
+    fn test_parse_formats() {
+        let toml = "max_turns = 30\n[zone]\nmax_tiles = 5\n";
+        let json = r#"{ "max_turns": 30, "zone": { "max_tiles": 5 } }"#;
+        let ron = "(max_turns: 30, zone: (max_tiles: 5))";
+        for (source, format) in [(toml, ConfigFormat::Toml), (json, ConfigFormat::Json), (ron, ConfigFormat::Ron)] {
+            let config = RoundConfig::from_str_as(source, format).unwrap();
+            assert_eq!(config.max_turns, 30);
+            assert_eq!(config.zone.max_tiles, 5);
+            assert_eq!(config.score_to_win, RoundConfig::default().score_to_win);
+        }
+    }
+
+    /// Tests that parsed configs are validated too
+    #[test]
+    fn test_parse_validates() {
+        let result = RoundConfig::from_str_as("score_to_win = 0", ConfigFormat::Toml);
+        assert!(matches!(result, Err(ConfigError::NoScoreToWin)));
+        let result = RoundConfig::from_str_as("max_turns = \"many\"", ConfigFormat::Toml);
+        assert!(matches!(result, Err(ConfigError::Parse { format: ConfigFormat::Toml, .. })));
+    }
+
+    /// Tests that files are loaded by extension
+    #[test]
+    fn test_from_file() {
+        let path = std::env::temp_dir().join("deepphase_round_config_test.json");
+        std::fs::write(&path, r#"{ "temp_turn_count": 4 }"#).unwrap();
+        let config = RoundConfig::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(config.temp_turn_count, 4);
+
+        let result = RoundConfig::from_file(std::path::Path::new("rules.yaml"));
+        assert!(matches!(result, Err(ConfigError::UnknownFormat(_))));
+    }
+}
@@ -1,158 +1,528 @@
-/** Round management logic
+/*! Round management logic
 *
 *
 */
 
-use std::ops::Index;
-use crate::gameplay::actors::{ResistanceActor, ResistanceAction, SuppressionActor, SuppressionAction, Action};
-use crate::gameplay::round_manager::RoundResult::{ResistanceGainsPoint, ResistanceGainsTemporary};
-use crate::gameplay::tilemap::{Coord, CoordinateSystem, Tile};
+use std::fmt;
 
+use serde::{Serialize, Deserialize};
+
+use crate::gameplay::actors::{Actor, ResistanceActor, ResistanceAction, Side, SuppressionActor, SuppressionAction};
+use crate::gameplay::round_manager::RoundResult::{ResistanceGainsPoint, ResistanceGainsTemporary, PrivateTileGained, PrivateTileRevealed, ResistanceEnclosesRegion, SuppressorReclaims};
+use crate::gameplay::events::{EventBus, RoundEvent};
+use crate::gameplay::replay::ReplayRecorder;
+use crate::gameplay::round_config::RoundConfig;
+use crate::gameplay::rules::{RuleError, Rules};
+use crate::gameplay::suppression_zone::ZoneError;
+use crate::gameplay::tilemap::{Board, Coord, CoordinateSystem, TileKind, TileMap};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RoundState {
     resistance: ResistanceActor,
     suppression: SuppressionActor,
-    max_turns: u32,  // the number of turns the resistance player has to win
+    config: RoundConfig,  // the rules this round is played under
     current_turn: u32,
-    temp_turn_count: u32,  // the amount of turns a temp resistance tile has until it returns to normal
     turn_buffer: (Option<SuppressionAction>, Option<ResistanceAction>),  // the buffer processing received turns before locking them in
     turn_history: Vec<(SuppressionAction, ResistanceAction)>,
-    resistance_perm_tiles: Vec<Coord>,
-    resistance_temp_tiles: Vec<(Coord, u32)>,  // The coordinate and the number of turns until it returns to normal
-    score_to_win: u32  // The number of resistance perm tiles needed for resistance player to win
+    tiles: TileMap,  // every occupied tile on the board, temp timers included
+    sieges: Vec<(Coord, u32)>,  // the perm tiles under siege and the consecutive turns they've been besieged
+    outcome: Option<GameOutcome>,  // Set once the round has ended, after which no more actions are taken
+    #[serde(skip)]
+    recorder: Option<ReplayRecorder>,  // Records every resolved turn to a replay when attached
+    #[serde(skip)]
+    events: EventBus,  // Queues every change to the round for whoever drains it
+    #[serde(skip)]
+    rules: Rules  // The rule set the round is played under, snapshots keep its name and restore it from the caller
 }
 
-#[derive(PartialEq)]
-enum RoundResult {
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) enum RoundResult {
     ResistanceGainsPoint(Coord),
-    ResistanceGainsTemporary(Coord)
+    ResistanceGainsTemporary(Coord),
+    PrivateTileGained(Coord),  // A hidden temp tile only the resistance knows about
+    PrivateTileRevealed(Coord),  // A hidden tile was exposed and is now an ordinary temp tile
+    ResistanceEnclosesRegion(Vec<Coord>),  // Empty tiles walled off by resistance, all now permanent
+    SuppressorReclaims(Coord)  // A besieged perm tile was taken back and is now a suppressor tile
+}
+
+/// The final result of a round, computed after every resolved turn.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameOutcome {
+    /// The resistance reached `score_to_win` permanent tiles before the clock ran out.
+    ResistanceWins { score: u32, turn: u32 },
+    /// The suppressor held the resistance under `score_to_win` until `max_turns` ran out.
+    SuppressionWins { score: u32 },
+    /// The suppressor reclaimed the reclaim rules' `tiles_to_win` before the resistance won.
+    SuppressionReclaims { reclaimed: u32, turn: u32 },
+    /// Neither side has a clean claim on the round.
+    Draw(DrawReason)
+}
+
+/// The ways in which a round can end without a winner.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DrawReason {
+    /// The resistance reached `score_to_win` on the same turn the suppressor reclaimed enough tiles to win.
+    BothSidesScored,
+    /// A variant rule set called the round a draw for reasons of its own.
+    DeclaredByRules
+}
+
+/// What happened to an action the round accepted.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum IntakeStatus {
+    /// The action is buffered until the other side submits theirs for this turn.
+    AwaitingOpponent,
+    /// The action completed the buffer and the turn was resolved with these results.
+    TurnResolved(Vec<RoundResult>)
+}
+
+/// The reasons an action can be rejected by the round.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ActionError {
+    /// A coordinate in the action is off the board.
+    OutOfBounds(Coord),
+    /// The public coordinate lands on a tile the resistance already holds permanently.
+    OccupiedByPermanent(Coord),
+    /// The suppression zone breaks the zone rules.
+    InvalidZone(ZoneError),
+    /// This side already submitted an action for the current turn.
+    DuplicateSubmission,
+    /// The round is over.
+    GameOver(GameOutcome),
+    /// The rule set failed to resolve the turn this action completed.
+    RuleFailed(RuleError)
+}
+
+impl fmt::Display for ActionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActionError::OutOfBounds(coord) => write!(f, "{:?} is off the board", coord),
+            ActionError::OccupiedByPermanent(coord) => write!(f, "{:?} is already a permanent resistance tile", coord),
+            ActionError::InvalidZone(error) => write!(f, "invalid suppression zone: {}", error),
+            ActionError::DuplicateSubmission => write!(f, "an action was already submitted for this turn"),
+            ActionError::GameOver(outcome) => write!(f, "the round is over: {:?}", outcome),
+            ActionError::RuleFailed(error) => write!(f, "{}", error)
+        }
+    }
+}
+
+impl std::error::Error for ActionError {}
+
+impl From<ZoneError> for ActionError {
+    fn from(error: ZoneError) -> Self {
+        ActionError::InvalidZone(error)
+    }
+}
+
+/// The reasons a round can't be rewound to a turn.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RewindError {
+    /// The round hasn't played that many turns.
+    TurnNotPlayed { turn: u32, played: u32 },
+    /// A turn in the history was rejected when it was replayed.
+    InvalidHistory { turn: u32, error: ActionError }
+}
+
+impl fmt::Display for RewindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RewindError::TurnNotPlayed { turn, played } => write!(f, "can't rewind to turn {}, only {} turns were played", turn, played),
+            RewindError::InvalidHistory { turn, error } => write!(f, "turn {} of the history can't be replayed: {}", turn, error)
+        }
+    }
 }
 
+impl std::error::Error for RewindError {}
+
 impl RoundState {
+    /// Start a new round played under the given rules
+    pub fn new(config: RoundConfig) -> RoundState {
+        RoundState::with_rules(config, Rules::default())
+    }
+
+    /// Start a new round played under a variant rule set
+    pub(crate) fn with_rules(config: RoundConfig, rules: Rules) -> RoundState {
+        RoundState {
+            resistance: ResistanceActor::new(),
+            suppression: SuppressionActor::new(),
+            tiles: TileMap::new(Board::from_config(&config)),
+            config,
+            current_turn: 0,
+            turn_buffer: (None, None),
+            turn_history: Vec::new(),
+            sieges: Vec::new(),
+            outcome: None,
+            recorder: None,
+            events: EventBus::default(),
+            rules,
+        }
+    }
+
+    /// Start a round from a position part way through, with no history behind it
+    ///
+    /// For players searching ahead from their view of a round, under the round's rule set and with
+    /// its sieges in progress.
+    pub(crate) fn from_position(config: RoundConfig, rules: Rules, tiles: TileMap, sieges: Vec<(Coord, u32)>, current_turn: u32) -> RoundState {
+        RoundState { tiles, sieges, current_turn, ..RoundState::with_rules(config, rules) }
+    }
+
+    /// The rules this round is played under.
+    pub fn config(&self) -> &RoundConfig {
+        &self.config
+    }
+
+    /// The rule set this round is played under.
+    pub(crate) fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    /// Swap the rule set the round is played under, for restoring rounds that were saved without it
+    pub(crate) fn set_rules(&mut self, rules: Rules) {
+        self.rules = rules;
+    }
+
+    /// The board this round is played on.
+    pub fn board(&self) -> &Board {
+        self.tiles.board()
+    }
+
+    /// The tiles currently on the board, hidden ones included.
+    pub fn tiles(&self) -> &TileMap {
+        &self.tiles
+    }
+
+    /// The number of permanent resistance tiles.
+    pub fn resistance_score(&self) -> u32 {
+        self.tiles.count(TileKind::Resistance) as u32
+    }
+
     /// Intake a suppression action to the buffer
-    fn intake_suppression_action(&mut self, action: SuppressionAction) {
+    ///
+    /// Duplicate tiles are removed from the zone before it is buffered. Rejected actions leave the
+    /// buffer untouched.
+    pub(crate) fn intake_suppression_action(&mut self, action: SuppressionAction) -> Result<IntakeStatus, ActionError> {
+        let status = self.buffer_suppression_action(action);
+        self.announce_rejection(Side::Suppression, &status);
+        status
+    }
+
+    fn buffer_suppression_action(&mut self, action: SuppressionAction) -> Result<IntakeStatus, ActionError> {
+        self.check_open()?;
+        if self.turn_buffer.0.is_some() {
+            return Err(ActionError::DuplicateSubmission);
+        }
+        let action = self.validate_suppression_action(&action)?;
         self.turn_buffer.0 = Some(action);
-        self.process_turn_buffer()
+        let status = self.process_turn_buffer();
+        if status.is_err() {
+            self.turn_buffer.0 = None;
+        }
+        status
     }
 
     /// Intake a resistance action to the buffer
-    fn intake_resistance_action(&mut self, action: ResistanceAction) {
+    ///
+    /// Rejected actions leave the buffer untouched.
+    pub(crate) fn intake_resistance_action(&mut self, action: ResistanceAction) -> Result<IntakeStatus, ActionError> {
+        let status = self.buffer_resistance_action(action);
+        self.announce_rejection(Side::Resistance, &status);
+        status
+    }
+
+    fn buffer_resistance_action(&mut self, action: ResistanceAction) -> Result<IntakeStatus, ActionError> {
+        self.check_open()?;
+        if self.turn_buffer.1.is_some() {
+            return Err(ActionError::DuplicateSubmission);
+        }
+        self.validate_resistance_action(&action)?;
         self.turn_buffer.1 = Some(action);
-        self.process_turn_buffer()
+        let status = self.process_turn_buffer();
+        if status.is_err() {
+            self.turn_buffer.1 = None;
+        }
+        status
     }
 
-    /// If the turn buffer is full - initiate turn change
-    fn process_turn_buffer(&mut self) {
-        // Todo: More idiomatic way to do this?
-        if self.turn_buffer.0 != None && self.turn_buffer.1 != None {
-            self.resolve_turn()
+    /// Check a suppression action against this round's board and zone rules
+    ///
+    /// Returns the action with its zone deduplicated, which is what would be played.
+    pub(crate) fn validate_suppression_action(&self, action: &SuppressionAction) -> Result<SuppressionAction, ActionError> {
+        self.rules.validate_suppression_action(self, action)
+    }
+
+    /// Check a resistance action against this round's board
+    pub(crate) fn validate_resistance_action(&self, action: &ResistanceAction) -> Result<(), ActionError> {
+        self.rules.validate_resistance_action(self, action)
+    }
+
+    /// Emit `ActionRejected` if the action was rejected
+    fn announce_rejection(&mut self, side: Side, status: &Result<IntakeStatus, ActionError>) {
+        if let Err(error) = status {
+            self.events.emit(RoundEvent::ActionRejected { side, error: error.clone() });
         }
     }
 
-    /// Resolve the current turn
-    fn resolve_turn(&mut self) {
-        // let results = self.round_results();
-    }
-
-    /// Get the number of neighbors still suppressed
-    fn number_of_suppressed_neighbors(&self, coord: &Coord, temps: &Vec<Coord>) -> u8 {
-        let total_neighbors = Tile::neighbors_of(coord);
-        let surrounding_resistance: Vec<Coord> = total_neighbors.iter().cloned().filter(
-            |neighbor| {
-                let is_temp = temps.iter().any(|x| x.eq(neighbor));
-                if self.resistance_perm_tiles.contains(neighbor) || is_temp {
-                    // This coordinate is either a perm or temp resistance territory
-                    return true;
-                }
-                return false;
-            }
-        ).collect();
-        return (total_neighbors.len() - surrounding_resistance.len()) as u8
+    /// Fail with `GameOver` if the round has ended
+    fn check_open(&self) -> Result<(), ActionError> {
+        match self.outcome {
+            Some(outcome) => Err(ActionError::GameOver(outcome)),
+            None => Ok(())
+        }
+    }
+
+    /// The outcome of the round, or `None` while it is still being played.
+    pub fn outcome(&self) -> Option<GameOutcome> {
+        self.outcome
+    }
+
+    /// Whether the round has ended.
+    pub fn is_over(&self) -> bool {
+        self.outcome.is_some()
+    }
+
+    /// The number of turns resolved so far.
+    pub fn current_turn(&self) -> u32 {
+        self.current_turn
+    }
+
+    /// Every resolved pair of actions, in the order they were played.
+    pub(crate) fn turn_history(&self) -> &[(SuppressionAction, ResistanceAction)] {
+        &self.turn_history
+    }
+
+    /// The actions buffered for the turn in progress, suppression first.
+    pub(crate) fn turn_buffer(&self) -> &(Option<SuppressionAction>, Option<ResistanceAction>) {
+        &self.turn_buffer
+    }
+
+    /// The tiles on the board, for rule sets to change.
+    pub(crate) fn tiles_mut(&mut self) -> &mut TileMap {
+        &mut self.tiles
+    }
+
+    /// The perm tiles under siege and the consecutive turns they've been besieged.
+    pub(crate) fn sieges(&self) -> &[(Coord, u32)] {
+        &self.sieges
+    }
+
+    /// Replace the siege counts, for rule sets to advance.
+    pub(crate) fn set_sieges(&mut self, sieges: Vec<(Coord, u32)>) {
+        self.sieges = sieges;
+    }
+
+    /// Attach a replay recorder, replacing any already attached
+    pub(crate) fn set_recorder(&mut self, recorder: Option<ReplayRecorder>) {
+        self.recorder = recorder;
     }
 
-    /// Compare a resistance and suppression action
-    fn round_results(&mut self, resistance: &ResistanceAction, suppression: &SuppressionAction) -> Vec<RoundResult> {
-        let mut results: Vec<RoundResult> = Vec::new();
-        let mut temps: Vec<Coord> = self.resistance_temp_tiles.iter().map(|t| t.0).collect();
-        let already_resistance_temp = self.resistance_temp_tiles.iter().any(|coord | coord.0 == resistance.public_coord);
-        if !already_resistance_temp && !suppression.suppression_zone.contains(&resistance.public_coord) {
-            // The public coordinate is outside the suppression zone, add it to the temporaries
-            results.push(ResistanceGainsTemporary(resistance.public_coord));
-            temps.push(resistance.public_coord)
+    /// Detach the replay recorder, if one is attached
+    pub(crate) fn take_recorder(&mut self) -> Option<ReplayRecorder> {
+        self.recorder.take()
+    }
+
+    /// The bus this round's events are emitted on.
+    pub(crate) fn events_mut(&mut self) -> &mut EventBus {
+        &mut self.events
+    }
+
+    /// Create an independent round as it stood after the given number of turns
+    ///
+    /// The board is reconstructed by replaying the history from a fresh round with the same config,
+    /// so the fork can be played on without affecting this round. Any half-buffered turn is left out.
+    ///
+    /// ## Arguments:
+    /// * `turn` - the number of turns to replay, `0` being the start of the round
+    pub fn fork_at(&self, turn: u32) -> Result<RoundState, RewindError> {
+        let played = self.turn_history.len() as u32;
+        if turn > played {
+            return Err(RewindError::TurnNotPlayed { turn, played });
         }
 
-        for coord in &temps {
-            let still_suppressed = self.number_of_suppressed_neighbors(&coord, &temps);
-            if still_suppressed == 0 {
-                // This is now a perm because it is totally surrounded
-                results.push(ResistanceGainsPoint(*coord))
-            }
+        let mut fork = RoundState::with_rules(self.config.clone(), self.rules.clone());
+        for (index, (suppression, resistance)) in self.turn_history.iter().take(turn as usize).enumerate() {
+            fork.intake_suppression_action(suppression.clone())
+                .and_then(|_| fork.intake_resistance_action(*resistance))
+                .map_err(|error| RewindError::InvalidHistory { turn: index as u32, error })?;
         }
+        Ok(fork)
+    }
 
-        return results
+    /// Rewind the round to how it stood after the given number of turns
+    ///
+    /// Every later turn is dropped from the history, so play continues from there as a new branch.
+    /// An attached recorder is kept, dropping the turns that were rewound, and so is the round's event
+    /// queue, which is told of the rewind.
+    pub fn rewind_to(&mut self, turn: u32) -> Result<(), RewindError> {
+        let mut fork = self.fork_at(turn)?;
+        if let Some(mut recorder) = self.recorder.take() {
+            recorder.truncate(turn);
+            recorder.set_outcome(fork.outcome);
+            fork.recorder = Some(recorder);
+        }
+        fork.events = std::mem::take(&mut self.events);
+        *self = fork;
+        self.events.emit(RoundEvent::Rewound { turn });
+        Ok(())
     }
 
-    fn process_results(&mut self, results: Vec<RoundResult>) {
-        for result in results {
-            match result {
-                RoundResult::ResistanceGainsPoint(coord) => {
-                    let now_perm = coord;
-                    let index = self.resistance_temp_tiles.iter().position(|x| x.0.eq(&coord)).unwrap();
-                    self.resistance_temp_tiles.remove(index);
-                    self.resistance_perm_tiles.push(now_perm);
-                },
-                RoundResult::ResistanceGainsTemporary(coord) => self.resistance_temp_tiles.push((coord, self.temp_turn_count))
+    /// Rewind the round by a single turn
+    pub fn undo_turn(&mut self) -> Result<(), RewindError> {
+        let played = self.turn_history.len() as u32;
+        if played == 0 {
+            return Err(RewindError::TurnNotPlayed { turn: 0, played });
+        }
+        self.rewind_to(played - 1)
+    }
+
+    /// If the turn buffer is full - initiate turn change
+    fn process_turn_buffer(&mut self) -> Result<IntakeStatus, ActionError> {
+        if self.turn_buffer.0.is_some() && self.turn_buffer.1.is_some() {
+            return Ok(IntakeStatus::TurnResolved(self.resolve_turn()?));
+        }
+        Ok(IntakeStatus::AwaitingOpponent)
+    }
+
+    /// Resolve the current turn
+    ///
+    /// Drains the turn buffer, computes and applies the results of the buffered pair, records the
+    /// pair in the turn history and both actors' queues, and then advances the timers. Returns every
+    /// result applied this turn, including the reveals at the end of the round.
+    ///
+    /// If the rule set fails, the board is put back the way it was and the buffer is left full.
+    fn resolve_turn(&mut self) -> Result<Vec<RoundResult>, ActionError> {
+        let (suppression, resistance) = match std::mem::take(&mut self.turn_buffer) {
+            (Some(suppression), Some(resistance)) => (suppression, resistance),
+            partial => {
+                // Not ready yet, put back whatever we had
+                self.turn_buffer = partial;
+                return Ok(Vec::new());
+            }
+        };
+
+        let checkpoint = (self.tiles.clone(), self.sieges.clone(), self.current_turn);
+        let (results, expired, outcome) = match self.apply_turn(&suppression, &resistance) {
+            Ok(resolved) => resolved,
+            Err(error) => {
+                (self.tiles, self.sieges, self.current_turn) = checkpoint;
+                self.turn_buffer = (Some(suppression), Some(resistance));
+                return Err(ActionError::RuleFailed(error));
             }
         };
+
+        self.resistance.take_action(resistance);
+        self.suppression.take_action(suppression.clone());
+        self.turn_history.push((suppression.clone(), resistance));
+        self.outcome = outcome;
+
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(suppression, resistance, results.clone());
+            recorder.set_outcome(self.outcome);
+        }
+        self.announce_turn(&results, expired);
+        Ok(results)
     }
 
-    /// Increment the relevant timers
-    fn decrement_timers(&mut self) {
-        // Decrement everything down to at or above 0
-        for tile in &mut self.resistance_temp_tiles {
-            tile.1 -= 1;
+    /// Run a pair of actions through the rules
+    ///
+    /// Returns the results applied, the temp tiles that expired and the outcome of the round after
+    /// the turn, or the first error the rule set raised. A turn that ends the round also reveals
+    /// every hidden tile.
+    #[allow(clippy::type_complexity)]
+    fn apply_turn(&mut self, suppression: &SuppressionAction, resistance: &ResistanceAction)
+        -> Result<(Vec<RoundResult>, Vec<(Coord, bool)>, Option<GameOutcome>), RuleError> {
+        let rules = self.rules.clone();
+        let mut results = rules.round_results(self, resistance, suppression)?;
+        rules.apply_results(self, &results)?;
+        let reclaims = rules.reclaim_results(self, suppression)?;
+        rules.apply_results(self, &reclaims)?;
+        results.extend(reclaims);
+
+        let expired = self.decrement_timers();
+        let outcome = rules.check_outcome(self)?;
+        if outcome.is_some() {
+            // Nothing stays hidden once the round is over
+            let reveals: Vec<RoundResult> = self.tiles.tiles_in_state(TileKind::HiddenResistance)
+                .map(|tile| PrivateTileRevealed(tile.coord()))
+                .collect();
+            rules.apply_results(self, &reveals)?;
+            results.extend(reveals);
         }
-        // Remove all 0s
-        self.resistance_temp_tiles = self.resistance_temp_tiles.iter().filter(|tile| {
-            if tile.1 == 0 {
-                return false;
-            }
-            return true;
-        }).cloned().collect();
-
-        // Process turn count
-        if self.current_turn == self.max_turns {
-            println!("Implement end_game!")
-            // end_game();
-        } else {
+        Ok((results, expired, outcome))
+    }
+
+    /// Emit the events for a resolved turn
+    fn announce_turn(&mut self, results: &[RoundResult], expired: Vec<(Coord, bool)>) {
+        for result in results {
+            let event = match result {
+                ResistanceGainsTemporary(coord) => RoundEvent::TemporaryPlaced { coord: *coord, hidden: false },
+                PrivateTileGained(coord) => RoundEvent::TemporaryPlaced { coord: *coord, hidden: true },
+                PrivateTileRevealed(coord) => RoundEvent::TemporaryRevealed(*coord),
+                ResistanceGainsPoint(coord) => RoundEvent::PermanentCaptured(*coord),
+                ResistanceEnclosesRegion(region) => RoundEvent::RegionCaptured(region.clone()),
+                SuppressorReclaims(coord) => RoundEvent::TileReclaimed(*coord)
+            };
+            self.events.emit(event);
+        }
+        for (coord, hidden) in expired {
+            self.events.emit(RoundEvent::TemporaryExpired { coord, hidden });
+        }
+        self.events.emit(RoundEvent::TurnResolved { turn: self.current_turn, results: results.to_vec() });
+        if let Some(outcome) = self.outcome {
+            self.events.emit(RoundEvent::GameOver(outcome));
+        }
+    }
+
+    /// Compute the results of a pair of actions under this round's rules
+    #[cfg(test)]
+    fn round_results(&self, resistance: &ResistanceAction, suppression: &SuppressionAction) -> Result<Vec<RoundResult>, RuleError> {
+        self.rules.clone().round_results(self, resistance, suppression)
+    }
+
+    /// Apply results to the board under this round's rules
+    #[cfg(test)]
+    fn process_results(&mut self, results: Vec<RoundResult>) -> Result<(), RuleError> {
+        self.rules.clone().apply_results(self, &results)
+    }
+
+    /// Count down the temp tiles under this round's rules, then move on to the next turn
+    ///
+    /// Returns the temp tiles that ran out, and whether each was still hidden.
+    fn decrement_timers(&mut self) -> Vec<(Coord, bool)> {
+        let expired = self.rules.clone().decay_timers(self);
+
+        // Process turn count, the outcome check handles the end of the round
+        if self.current_turn < self.config.max_turns {
             self.current_turn += 1;
         }
+        expired
     }
 }
 
 impl Default for RoundState {
     fn default() -> Self {
-        RoundState {
-            resistance: ResistanceActor::new(),
-            suppression: SuppressionActor::new(),
-            max_turns: 20,
-            current_turn: 0,
-            temp_turn_count: 3,
-            turn_buffer: (None, None),
-            turn_history: Vec::new(),
-            resistance_perm_tiles: Vec::new(),
-            resistance_temp_tiles: Vec::new(),
-            score_to_win: 5,
-        }
+        RoundState::new(RoundConfig::default())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::gameplay::actors::{ResistanceAction, SuppressionAction};
-    use crate::gameplay::round_manager::{RoundResult, RoundState};
-    use crate::gameplay::tilemap::{Coord, CoordinateSystem, Tile};
+    use proptest::prelude::*;
 
-    /** Todo: Would be cool to have a macro like matches! but over an iterable for any-like query
+    use crate::ai::{take_turn, PlayerError};
+    use crate::ai::baseline::GreedyPlayer;
+    use crate::gameplay::actors::{Actor, ResistanceAction, Side, SuppressionAction};
+    use crate::gameplay::round_config::{CaptureRule, RoundConfig};
+    use crate::gameplay::round_manager::{ActionError, GameOutcome, IntakeStatus, RewindError, RoundResult, RoundState};
+    use crate::gameplay::rules::{Classic, RuleError, RuleSet, Rules};
+    use crate::gameplay::suppression_zone::ZoneError;
+    use crate::gameplay::tilemap::{Coord, CoordinateSystem, Tile, TileKind, TileState};
+
+    /// Puts a tile straight onto the board of a round
+    fn place(state: &mut RoundState, coord: Coord, tile: TileState) {
+        state.tiles.set(coord, tile).unwrap();
+    }
+
+    /* Todo: Would be cool to have a macro like matches! but over an iterable for any-like query
     macro_rule! matches_any {
         ($iter:item, $e:expr) => {
 
@@ -163,7 +533,7 @@ mod tests {
     /// Tests that round_results will not generate any results for successful suppression
     #[test]
     fn test_round_results_successful_suppression() {
-        let mut state = RoundState::default();
+        let state = RoundState::default();
         let resistance_action = ResistanceAction {
             public_coord: (0, 0),
             private_coord: (0, 1)
@@ -173,14 +543,14 @@ mod tests {
                 (0, 0), (0, 1), (1, 0), (1, 1)
             ]
         };
-        let results = state.round_results(&resistance_action, &suppression_action);
+        let results = state.round_results(&resistance_action, &suppression_action).unwrap();
         assert_eq!(results.len(), 0);
     }
 
     /// Tests whether round_results will generate the correct enum for a successful temp resistance
     #[test]
     fn test_round_results_successful_resistance_placement() {
-        let mut state = RoundState::default();
+        let state = RoundState::default();
         let resistance_action = ResistanceAction {
             public_coord: (0, 0),
             private_coord: (0, 1)
@@ -190,35 +560,112 @@ mod tests {
                 (1, 1), (1, 1), (2, 0), (2, 2)
             ]
         };
-        let results = state.round_results(&resistance_action, &suppression_action);
-        assert_eq!(results.len(), 1);
-        if let RoundResult::ResistanceGainsTemporary(coord) = results.last().unwrap() {
+        let results = state.round_results(&resistance_action, &suppression_action).unwrap();
+        assert_eq!(results.len(), 2);
+        if let RoundResult::ResistanceGainsTemporary(coord) = results.first().unwrap() {
             assert_eq!(coord, &resistance_action.public_coord)
         }
     }
 
+    /// Tests that a private coordinate outside the suppression zone becomes a hidden tile
+    #[test]
+    fn test_round_results_private_tile_gained() {
+        let state = RoundState::default();
+        let resistance_action = ResistanceAction {
+            public_coord: (0, 0),
+            private_coord: (4, 4)
+        };
+        let suppression_action = SuppressionAction {
+            suppression_zone: vec![(0, 0), (0, 1)]
+        };
+        let results = state.round_results(&resistance_action, &suppression_action).unwrap();
+        assert_eq!(results, vec![RoundResult::PrivateTileGained((4, 4))]);
+    }
+
+    /// Tests that a private coordinate inside the suppression zone is not placed
+    #[test]
+    fn test_round_results_private_tile_suppressed() {
+        let state = RoundState::default();
+        let resistance_action = ResistanceAction {
+            public_coord: (0, 0),
+            private_coord: (4, 4)
+        };
+        let suppression_action = SuppressionAction {
+            suppression_zone: vec![(4, 4)]
+        };
+        let results = state.round_results(&resistance_action, &suppression_action).unwrap();
+        assert_eq!(results, vec![RoundResult::ResistanceGainsTemporary((0, 0))]);
+    }
+
+    /// Tests that the suppression zone landing on a hidden tile reveals it
+    #[test]
+    fn test_round_results_suppression_reveals_private_tile() {
+        let mut state = RoundState::default();
+        place(&mut state, (4, 4), TileState::HiddenResistance(3));
+        let resistance_action = ResistanceAction {
+            public_coord: (0, 0),
+            private_coord: (0, 0)
+        };
+        let suppression_action = SuppressionAction {
+            suppression_zone: vec![(0, 0), (4, 4)]
+        };
+        let results = state.round_results(&resistance_action, &suppression_action).unwrap();
+        assert_eq!(results, vec![RoundResult::PrivateTileRevealed((4, 4))]);
+    }
+
+    /// Tests that hidden tiles complete surrounds and are revealed once captured
+    #[test]
+    fn test_round_results_private_tile_captured() {
+        let mut state = RoundState::default();
+        let control_coord: Coord = (4, 4);
+        let mut neighbors = state.board().neighbors_of(&control_coord);
+        let final_neighbor = neighbors.pop().unwrap();
+        place(&mut state, control_coord, TileState::HiddenResistance(3));
+        for neighbor in neighbors {
+            place(&mut state, neighbor, TileState::TemporaryResistance(3));
+        }
+        let resistance_action = ResistanceAction {
+            public_coord: (5, 5),
+            private_coord: final_neighbor
+        };
+        let suppression_action = SuppressionAction {
+            suppression_zone: vec![(5, 5)]
+        };
+        let results = state.round_results(&resistance_action, &suppression_action).unwrap();
+        assert_eq!(results, vec![
+            RoundResult::PrivateTileGained(final_neighbor),
+            RoundResult::PrivateTileRevealed(control_coord),
+            RoundResult::ResistanceGainsPoint(control_coord)
+        ]);
+        state.process_results(results).unwrap();
+        assert_eq!(state.tiles.state_at(&control_coord), Some(TileState::Resistance));
+        assert_eq!(state.tiles.count(TileKind::HiddenResistance), 1);
+    }
+
     /// Tests that round_results will generate the correct results for surround a temp tile
     #[test]
     fn test_round_results_resistance_gains_point() {
         let mut state = RoundState::default();
-        let control_coord = ((0, 0), state.temp_turn_count);
+        let control_coord = ((4, 4), state.config.temp_turn_count);
         let mut neighbors_of_control: Vec<(Coord, u32)> = Tile::neighbors_of(&control_coord.0).iter().map(
-            |neighbor| (*neighbor, state.temp_turn_count)
+            |neighbor| (*neighbor, state.config.temp_turn_count)
         ).collect();
         let final_neighbor = neighbors_of_control.pop().unwrap().0;
-        state.resistance_temp_tiles.push(control_coord);
-        state.resistance_temp_tiles.extend(neighbors_of_control.iter());
+        place(&mut state, control_coord.0, TileState::TemporaryResistance(control_coord.1));
+        for neighbor in neighbors_of_control {
+            place(&mut state, neighbor.0, TileState::TemporaryResistance(neighbor.1));
+        }
         let resistance_action = ResistanceAction {
             public_coord: final_neighbor,
-            private_coord: (0, 0)
+            private_coord: (4, 4)
         };
         // Something clearly out of the way
         let suppression_action = SuppressionAction {
             suppression_zone: vec![
-                (-10, -10), (-10, 9), (-9, -9), (-9, -10)
+                (8, 8), (8, 7), (7, 7), (7, 8)
             ]
         };
-        let results = state.round_results(&resistance_action, &suppression_action);
+        let results = state.round_results(&resistance_action, &suppression_action).unwrap();
         assert!(results.iter().any(|result| *result == RoundResult::ResistanceGainsPoint(control_coord.0)));
     }
 
@@ -227,12 +674,12 @@ mod tests {
     fn test_process_results_resistance_gains_temp() {
         let mut state = RoundState::default();
         let control_coord = (0, 0);
-        assert!(!state.resistance_temp_tiles.iter().any(|coord| coord.0 == control_coord));
+        assert_eq!(state.tiles.state_at(&control_coord), None);
         let results: Vec<RoundResult> = vec![
             RoundResult::ResistanceGainsTemporary(control_coord)
         ];
-        state.process_results(results);
-        assert!(state.resistance_temp_tiles.contains(&(control_coord, state.temp_turn_count)));
+        state.process_results(results).unwrap();
+        assert_eq!(state.tiles.state_at(&control_coord), Some(TileState::TemporaryResistance(state.config.temp_turn_count)));
     }
 
     /// Tests that process_results will move a point from temp to perm given correct result
@@ -240,14 +687,25 @@ mod tests {
     fn test_process_results_resistance_gains_point() {
         let mut state = RoundState::default();
         let control_coord = (0, 0);
-        state.resistance_temp_tiles.push((control_coord, state.temp_turn_count));
+        let temp_turn_count = state.config.temp_turn_count;
+        place(&mut state, control_coord, TileState::TemporaryResistance(temp_turn_count));
         let results: Vec<RoundResult> = vec![
             RoundResult::ResistanceGainsPoint(control_coord)
         ];
-        assert!(!state.resistance_perm_tiles.contains(&control_coord));
-        state.process_results(results);
-        assert!(!state.resistance_temp_tiles.iter().any(|coord| coord.0 == control_coord));
-        assert!(state.resistance_perm_tiles.contains(&control_coord));
+        assert_eq!(state.tiles.count(TileKind::Resistance), 0);
+        state.process_results(results).unwrap();
+        assert_eq!(state.tiles.count(TileKind::TemporaryResistance), 0);
+        assert_eq!(state.tiles.state_at(&control_coord), Some(TileState::Resistance));
+    }
+
+    /// Tests that process_results turns a revealed hidden tile into a temp tile with the same timer
+    #[test]
+    fn test_process_results_private_tile_revealed() {
+        let mut state = RoundState::default();
+        place(&mut state, (4, 4), TileState::HiddenResistance(2));
+        state.process_results(vec![RoundResult::PrivateTileRevealed((4, 4))]).unwrap();
+        assert_eq!(state.tiles.count(TileKind::HiddenResistance), 0);
+        assert_eq!(state.tiles.state_at(&(4, 4)), Some(TileState::TemporaryResistance(2)));
     }
 
     /// Tests that decrement_timers progresses the turn count
@@ -267,11 +725,507 @@ mod tests {
             ((0, 1), 2),
             ((0, 2), 3)
         ];
-        state.resistance_temp_tiles.extend(temp_tiles);
+        for (coord, turns) in temp_tiles {
+            place(&mut state, coord, TileState::TemporaryResistance(turns));
+        }
         state.decrement_timers();
-        assert_eq!(state.resistance_temp_tiles.len(), 2);
+        assert_eq!(state.tiles.count(TileKind::TemporaryResistance), 2);
         state.decrement_timers();
-        assert_eq!(state.resistance_temp_tiles.len(), 1);
+        assert_eq!(state.tiles.count(TileKind::TemporaryResistance), 1);
+    }
+
+    /// Tests that a single buffered action does not resolve the turn
+    #[test]
+    fn test_intake_waits_for_both_actions() {
+        let mut state = RoundState::default();
+        let status = state.intake_resistance_action(ResistanceAction {
+            public_coord: (0, 0),
+            private_coord: (0, 1)
+        });
+        assert_eq!(status, Ok(IntakeStatus::AwaitingOpponent));
+        assert_eq!(state.current_turn, 0);
+        assert!(state.turn_buffer.1.is_some());
+        assert!(state.turn_history.is_empty());
+    }
+
+    /// Tests that a full turn buffer resolves into a new game state
+    #[test]
+    fn test_resolve_turn_applies_buffered_pair() {
+        let mut state = RoundState::default();
+        let resistance_action = ResistanceAction {
+            public_coord: (0, 0),
+            private_coord: (0, 1)
+        };
+        let suppression_action = SuppressionAction {
+            suppression_zone: vec![(2, 2), (2, 3), (3, 2), (3, 3)]
+        };
+        state.intake_suppression_action(suppression_action.clone()).unwrap();
+        let status = state.intake_resistance_action(resistance_action).unwrap();
+
+        assert_eq!(status, IntakeStatus::TurnResolved(vec![
+            RoundResult::ResistanceGainsTemporary((0, 0)),
+            RoundResult::PrivateTileGained((0, 1))
+        ]));
+        assert_eq!(state.current_turn, 1);
+        assert_eq!(state.turn_buffer, (None, None));
+        assert_eq!(state.turn_history, vec![(suppression_action.clone(), resistance_action)]);
+        assert_eq!(state.resistance.last_action(), Some(&resistance_action));
+        assert_eq!(state.suppression.last_action(), Some(&suppression_action));
+        // The new temporary has already been through one timer decrement
+        assert_eq!(state.tiles.state_at(&(0, 0)), Some(TileState::TemporaryResistance(state.config.temp_turn_count - 1)));
+    }
+
+    /// Plays a full turn that keeps the resistance out of the way of the suppressor
+    fn play_quiet_turn(state: &mut RoundState) {
+        state.intake_suppression_action(SuppressionAction {
+            suppression_zone: vec![(0, 0)]
+        }).unwrap();
+        state.intake_resistance_action(ResistanceAction {
+            public_coord: (0, 0),
+            private_coord: (0, 0)
+        }).unwrap();
+    }
+
+    /// Tests that the suppressor wins once the turns run out
+    #[test]
+    fn test_outcome_suppression_wins_on_timeout() {
+        let mut state = RoundState::new(RoundConfig::builder().max_turns(2).build().unwrap());
+        play_quiet_turn(&mut state);
+        assert_eq!(state.outcome(), None);
+        play_quiet_turn(&mut state);
+        assert_eq!(state.outcome(), Some(GameOutcome::SuppressionWins { score: 0 }));
+    }
+
+    /// Tests that the resistance wins as soon as it reaches the winning score
+    #[test]
+    fn test_outcome_resistance_wins_on_score() {
+        let mut state = RoundState::new(RoundConfig::builder().score_to_win(2).build().unwrap());
+        place(&mut state, (5, 5), TileState::Resistance);
+        place(&mut state, (6, 6), TileState::Resistance);
+        play_quiet_turn(&mut state);
+        assert_eq!(state.outcome(), Some(GameOutcome::ResistanceWins { score: 2, turn: 1 }));
+    }
+
+    /// Tests that reaching the winning score on the final turn still wins
+    #[test]
+    fn test_outcome_win_on_final_turn() {
+        let mut state = RoundState::new(RoundConfig::builder().max_turns(1).score_to_win(1).build().unwrap());
+        place(&mut state, (5, 5), TileState::Resistance);
+        play_quiet_turn(&mut state);
+        assert_eq!(state.outcome(), Some(GameOutcome::ResistanceWins { score: 1, turn: 1 }));
+    }
+
+    /// Tests that no more actions are taken once the round has ended
+    #[test]
+    fn test_intake_rejected_after_game_over() {
+        let mut state = RoundState::new(RoundConfig::builder().max_turns(1).build().unwrap());
+        play_quiet_turn(&mut state);
+        assert!(state.is_over());
+        let status = state.intake_resistance_action(ResistanceAction {
+            public_coord: (1, 1),
+            private_coord: (1, 1)
+        });
+        assert_eq!(status, Err(ActionError::GameOver(GameOutcome::SuppressionWins { score: 0 })));
+        assert_eq!(state.turn_buffer, (None, None));
+        assert_eq!(state.turn_history.len(), 1);
+    }
+
+    /// Tests that every hidden tile is revealed when the round ends
+    #[test]
+    fn test_private_tiles_revealed_at_round_end() {
+        let mut state = RoundState::new(RoundConfig::builder().max_turns(1).build().unwrap());
+        state.intake_suppression_action(SuppressionAction {
+            suppression_zone: vec![(0, 0)]
+        }).unwrap();
+        let status = state.intake_resistance_action(ResistanceAction {
+            public_coord: (0, 0),
+            private_coord: (4, 4)
+        });
+        assert_eq!(status, Ok(IntakeStatus::TurnResolved(vec![
+            RoundResult::PrivateTileGained((4, 4)),
+            RoundResult::PrivateTileRevealed((4, 4))
+        ])));
+        assert!(state.is_over());
+        assert_eq!(state.tiles.count(TileKind::HiddenResistance), 0);
+        assert_eq!(state.tiles.state_at(&(4, 4)).map(|tile| tile.kind()), Some(TileKind::TemporaryResistance));
+    }
+
+    /// Tests that tiles on the edge of the board are surrounded by their on-board neighbors alone
+    #[test]
+    fn test_round_results_edge_tile_gains_point() {
+        let mut state = RoundState::default();
+        let corner: Coord = (0, 0);
+        place(&mut state, corner, TileState::TemporaryResistance(3));
+        place(&mut state, (0, 1), TileState::TemporaryResistance(3));
+        let resistance_action = ResistanceAction {
+            public_coord: (1, 0),
+            private_coord: (1, 0)
+        };
+        let suppression_action = SuppressionAction {
+            suppression_zone: vec![(8, 8)]
+        };
+        let results = state.round_results(&resistance_action, &suppression_action).unwrap();
+        assert!(results.contains(&RoundResult::ResistanceGainsPoint(corner)));
+    }
+
+    /// Tests that coordinates off the board are never placed
+    #[test]
+    fn test_round_results_ignores_out_of_bounds() {
+        let state = RoundState::default();
+        let resistance_action = ResistanceAction {
+            public_coord: (-1, 0),
+            private_coord: (i8::MAX, i8::MAX)
+        };
+        let suppression_action = SuppressionAction {
+            suppression_zone: vec![(8, 8)]
+        };
+        let results = state.round_results(&resistance_action, &suppression_action).unwrap();
+        assert!(results.is_empty());
+    }
+
+    /// Tests that zones breaking the zone rules are not buffered
+    #[test]
+    fn test_intake_rejects_invalid_zone() {
+        let mut state = RoundState::default();
+        let status = state.intake_suppression_action(SuppressionAction {
+            suppression_zone: vec![(0, 0), (1, 0), (2, 0), (3, 0), (4, 0)]
+        });
+        assert_eq!(status, Err(ActionError::InvalidZone(ZoneError::TooManyTiles { count: 5, max: 4 })));
+        assert_eq!(state.turn_buffer.0, None);
+    }
+
+    /// Tests that duplicate zone tiles are removed before the zone is buffered
+    #[test]
+    fn test_intake_dedupes_zone() {
+        let mut state = RoundState::default();
+        let status = state.intake_suppression_action(SuppressionAction {
+            suppression_zone: vec![(1, 1), (1, 1), (1, 2)]
+        });
+        assert_eq!(status, Ok(IntakeStatus::AwaitingOpponent));
+        assert_eq!(state.turn_buffer.0, Some(SuppressionAction { suppression_zone: vec![(1, 1), (1, 2)] }));
+    }
+
+    /// Tests that resistance actions off the board are rejected
+    #[test]
+    fn test_intake_rejects_out_of_bounds() {
+        let mut state = RoundState::default();
+        let status = state.intake_resistance_action(ResistanceAction {
+            public_coord: (0, 0),
+            private_coord: (9, 0)
+        });
+        assert_eq!(status, Err(ActionError::OutOfBounds((9, 0))));
+        let status = state.intake_suppression_action(SuppressionAction {
+            suppression_zone: vec![(-1, 0)]
+        });
+        assert_eq!(status, Err(ActionError::InvalidZone(ZoneError::OutOfBounds((-1, 0)))));
+        assert_eq!(state.turn_buffer, (None, None));
+    }
+
+    /// Tests that the public coordinate can't land on a permanent tile
+    #[test]
+    fn test_intake_rejects_public_coord_on_perm() {
+        let mut state = RoundState::default();
+        place(&mut state, (3, 3), TileState::Resistance);
+        let status = state.intake_resistance_action(ResistanceAction {
+            public_coord: (3, 3),
+            private_coord: (0, 0)
+        });
+        assert_eq!(status, Err(ActionError::OccupiedByPermanent((3, 3))));
     }
 
-}
\ No newline at end of file
+    /// Tests that a second action from the same side in one turn is rejected instead of overwriting
+    #[test]
+    fn test_intake_rejects_duplicate_submission() {
+        let mut state = RoundState::default();
+        let first = ResistanceAction {
+            public_coord: (0, 0),
+            private_coord: (0, 0)
+        };
+        state.intake_resistance_action(first).unwrap();
+        let status = state.intake_resistance_action(ResistanceAction {
+            public_coord: (1, 1),
+            private_coord: (1, 1)
+        });
+        assert_eq!(status, Err(ActionError::DuplicateSubmission));
+        assert_eq!(state.turn_buffer.1, Some(first));
+    }
+
+    /// Plays a full turn with the given actions
+    fn play_turn(state: &mut RoundState, zone: Vec<Coord>, public_coord: Coord, private_coord: Coord) {
+        state.intake_suppression_action(SuppressionAction { suppression_zone: zone }).unwrap();
+        state.intake_resistance_action(ResistanceAction { public_coord, private_coord }).unwrap();
+    }
+
+    /// Sets up a round with all but the east wall around (4, 4) and (5, 4) placed
+    fn nearly_enclosed(rule: CaptureRule) -> RoundState {
+        let config = RoundConfig::builder().score_to_win(20).capture_rule(rule).build().unwrap();
+        let mut state = RoundState::new(config);
+        for coord in [(3, 4), (4, 3), (5, 3), (4, 5), (5, 5)] {
+            place(&mut state, coord, TileState::TemporaryResistance(3));
+        }
+        state
+    }
+
+    /// Tests that closing a wall captures the region inside it along with the wall
+    #[test]
+    fn test_enclosure_captures_region() {
+        let mut state = nearly_enclosed(CaptureRule::Enclosure);
+        place(&mut state, (5, 6), TileState::HiddenResistance(3));
+        state.intake_suppression_action(SuppressionAction { suppression_zone: vec![(8, 8)] }).unwrap();
+        let status = state.intake_resistance_action(ResistanceAction { public_coord: (6, 4), private_coord: (0, 8) });
+        let results = match status {
+            Ok(IntakeStatus::TurnResolved(results)) => results,
+            other => panic!("turn didn't resolve: {:?}", other)
+        };
+        assert!(results.contains(&RoundResult::ResistanceEnclosesRegion(vec![(4, 4), (5, 4)])));
+        for coord in [(4, 4), (5, 4), (3, 4), (4, 3), (5, 3), (4, 5), (5, 5), (6, 4)] {
+            assert_eq!(state.tiles.state_at(&coord), Some(TileState::Resistance), "{:?}", coord);
+        }
+        // Tiles outside the wall, hidden or not, are left alone
+        assert_eq!(state.tiles.state_at(&(5, 6)), Some(TileState::HiddenResistance(2)));
+        assert_eq!(state.tiles.state_at(&(0, 8)), Some(TileState::HiddenResistance(2)));
+        assert_eq!(state.resistance_score(), 8);
+    }
+
+    /// Tests that a region leaking to the edge through a diagonal gap isn't enclosed
+    #[test]
+    fn test_enclosure_needs_orthogonal_wall() {
+        let mut state = nearly_enclosed(CaptureRule::Enclosure);
+        // Close the wall diagonally, which leaves (6, 4) open
+        play_turn(&mut state, vec![(8, 8)], (6, 3), (6, 5));
+        assert_eq!(state.resistance_score(), 0);
+        assert_eq!(state.tiles.state_at(&(4, 4)), None);
+    }
+
+    /// Tests that the four-neighbor rule doesn't capture empty regions
+    #[test]
+    fn test_four_neighbor_ignores_regions() {
+        let mut state = nearly_enclosed(CaptureRule::FourNeighbor);
+        play_turn(&mut state, vec![(8, 8)], (6, 4), (0, 8));
+        assert_eq!(state.resistance_score(), 0);
+        assert_eq!(state.tiles.state_at(&(4, 4)), None);
+    }
+
+    /// Tests that a perm tile besieged long enough is reclaimed, winning the round for the suppressor
+    #[test]
+    fn test_reclaim_besieged_tile() {
+        let config = RoundConfig::builder().reclaim(2, Some(1)).build().unwrap();
+        let mut state = RoundState::new(config);
+        place(&mut state, (4, 4), TileState::Resistance);
+        play_turn(&mut state, vec![(4, 4)], (0, 0), (8, 0));
+        assert_eq!(state.tiles.state_at(&(4, 4)), Some(TileState::Resistance));
+        play_turn(&mut state, vec![(4, 4)], (0, 8), (8, 8));
+        assert_eq!(state.tiles.state_at(&(4, 4)), Some(TileState::Suppressor));
+        assert_eq!(state.resistance_score(), 0);
+        assert_eq!(state.outcome(), Some(GameOutcome::SuppressionReclaims { reclaimed: 1, turn: 2 }));
+    }
+
+    /// Tests that the siege is broken by a turn off or by a resistance neighbor, and is off by default
+    #[test]
+    fn test_reclaim_siege_interrupted() {
+        let config = RoundConfig::builder().reclaim(2, None).build().unwrap();
+        let mut state = RoundState::new(config);
+        place(&mut state, (4, 4), TileState::Resistance);
+        play_turn(&mut state, vec![(4, 4)], (0, 0), (8, 0));
+        play_turn(&mut state, vec![(2, 2)], (0, 8), (8, 8));
+        play_turn(&mut state, vec![(4, 4)], (1, 0), (7, 0));
+        assert_eq!(state.tiles.state_at(&(4, 4)), Some(TileState::Resistance));
+
+        // The hidden neighbor keeps the tile from being besieged, even though the suppressor can't see it
+        place(&mut state, (4, 5), TileState::HiddenResistance(3));
+        play_turn(&mut state, vec![(4, 4)], (1, 8), (7, 8));
+        assert_eq!(state.tiles.state_at(&(4, 4)), Some(TileState::Resistance));
+
+        let mut state = RoundState::default();
+        place(&mut state, (4, 4), TileState::Resistance);
+        for _ in 0..3 {
+            play_turn(&mut state, vec![(4, 4)], (0, 0), (8, 0));
+        }
+        assert_eq!(state.tiles.state_at(&(4, 4)), Some(TileState::Resistance));
+    }
+
+    /// Plays the same few turns on any round
+    fn play_opening(state: &mut RoundState) {
+        play_turn(state, vec![(0, 0)], (4, 4), (4, 5));
+        play_turn(state, vec![(4, 5), (4, 6)], (3, 4), (5, 4));
+        play_turn(state, vec![(0, 0)], (4, 3), (1, 1));
+    }
+
+    /// Tests that rewinding reconstructs the board as it stood after that turn
+    #[test]
+    fn test_rewind_to_reconstructs_board() {
+        let mut state = RoundState::default();
+        play_opening(&mut state);
+        let mut control = RoundState::default();
+        play_turn(&mut control, vec![(0, 0)], (4, 4), (4, 5));
+
+        state.rewind_to(1).unwrap();
+        assert_eq!(state.current_turn(), 1);
+        assert_eq!(state.tiles(), control.tiles());
+        assert_eq!(state.turn_history(), control.turn_history());
+        assert_eq!(state.resistance.last_action(), control.resistance.last_action());
+    }
+
+    /// Tests that a fork plays on independently of the round it came from
+    #[test]
+    fn test_fork_at_is_independent() {
+        let mut state = RoundState::default();
+        play_opening(&mut state);
+        let tiles_before = state.tiles().clone();
+
+        let mut fork = state.fork_at(2).unwrap();
+        play_turn(&mut fork, vec![(8, 8)], (7, 7), (7, 7));
+        assert_eq!(fork.current_turn(), 3);
+        assert_ne!(fork.tiles(), &tiles_before);
+        assert_eq!(state.tiles(), &tiles_before);
+        assert_eq!(state.current_turn(), 3);
+    }
+
+    /// Tests that a round can't be rewound past the turns it played
+    #[test]
+    fn test_rewind_to_unplayed_turn() {
+        let mut state = RoundState::default();
+        play_quiet_turn(&mut state);
+        assert_eq!(state.fork_at(2).err(), Some(RewindError::TurnNotPlayed { turn: 2, played: 1 }));
+        state.undo_turn().unwrap();
+        assert_eq!(state.current_turn(), 0);
+        assert_eq!(state.undo_turn(), Err(RewindError::TurnNotPlayed { turn: 0, played: 0 }));
+    }
+
+    /// Tests that a finished round can be rewound and played on
+    #[test]
+    fn test_rewind_reopens_finished_round() {
+        let mut state = RoundState::new(RoundConfig::builder().max_turns(2).build().unwrap());
+        play_quiet_turn(&mut state);
+        play_quiet_turn(&mut state);
+        assert!(state.is_over());
+        state.undo_turn().unwrap();
+        assert_eq!(state.outcome(), None);
+        play_quiet_turn(&mut state);
+        assert!(state.is_over());
+    }
+
+    /// One thing done to a round while fuzzing it
+    #[derive(Debug, Clone)]
+    enum Step {
+        /// Submit a suppression zone, legal or not
+        Suppress(Vec<Coord>),
+        /// Submit a resistance action, legal or not
+        Resist(Coord, Coord),
+        /// Have the greedy player take a side's turn, to get games that go somewhere
+        Greedy(Side)
+    }
+
+    /// A small config, under the classic rules unless `siege_turns` is drawn
+    fn any_config() -> impl Strategy<Value = RoundConfig> {
+        (3u8..7, 3u8..7, 1u32..16, 1u32..5, 1u32..8, 1u32..4, any::<bool>(), any::<bool>(), prop::option::of(1u32..4))
+            .prop_filter_map("invalid config", |(width, height, max_turns, temp_turn_count, score_to_win, zone, contiguous, enclosure, siege_turns)| {
+                let mut builder = RoundConfig::builder()
+                    .board_size(width, height)
+                    .max_turns(max_turns)
+                    .temp_turn_count(temp_turn_count)
+                    .score_to_win(score_to_win)
+                    .zone_tiles(1, zone)
+                    .zone_contiguous(contiguous)
+                    .capture_rule(if enclosure { CaptureRule::Enclosure } else { CaptureRule::FourNeighbor });
+                if let Some(siege_turns) = siege_turns {
+                    builder = builder.reclaim(siege_turns, None);
+                }
+                builder.build().ok()
+            })
+    }
+
+    /// A step, with coordinates that stray off the board now and again
+    fn any_step() -> impl Strategy<Value = Step> {
+        let coord = (-1i8..8, -1i8..8);
+        prop_oneof![
+            prop::collection::vec(coord.clone(), 0..5).prop_map(Step::Suppress),
+            (coord.clone(), coord).prop_map(|(public_coord, private_coord)| Step::Resist(public_coord, private_coord)),
+            Just(Step::Greedy(Side::Suppression)),
+            Just(Step::Greedy(Side::Resistance))
+        ]
+    }
+
+    /// Checks what has to hold for any round after anything is done to it
+    fn check_invariants(state: &RoundState, perms_before: usize) -> Result<(), TestCaseError> {
+        let config = state.config();
+        let perms = state.tiles().coords_in_state(TileKind::Resistance);
+        prop_assert_eq!(state.resistance_score() as usize, perms.len());
+        prop_assert_eq!(state.turn_history().len(), state.current_turn() as usize, "every turn is in the history");
+        for tile in state.tiles().iter() {
+            prop_assert!(state.board().contains(&tile.coord()), "{:?} is off the board", tile);
+            if let Some(timer) = tile.state().timer() {
+                prop_assert!((1..=config.temp_turn_count).contains(&timer), "{:?} has a timer out of range", tile);
+            }
+        }
+        if config.reclaim.siege_turns.is_none() {
+            // Nothing but a reclaim can take a perm tile away
+            prop_assert!(perms.len() >= perms_before, "perm tiles went from {} to {}", perms_before, perms.len());
+        }
+        prop_assert!(state.current_turn() <= config.max_turns);
+        Ok(())
+    }
+
+    /// Play steps on a round, checking the invariants after each one
+    fn play_steps(state: &mut RoundState, steps: Vec<Step>) -> Result<(), TestCaseError> {
+        for step in steps {
+            let before = state.clone();
+            // Rejections are part of the fuzzing, all that matters is the state they leave behind
+            let error = match step {
+                Step::Suppress(suppression_zone) => state.intake_suppression_action(SuppressionAction { suppression_zone }).err(),
+                Step::Resist(public_coord, private_coord) => {
+                    state.intake_resistance_action(ResistanceAction { public_coord, private_coord }).err()
+                },
+                Step::Greedy(side) => match take_turn(&mut GreedyPlayer, state, side) {
+                    Err(PlayerError::Rejected { error, .. }) => Some(error),
+                    _ => None
+                }
+            };
+            if let Some(ActionError::RuleFailed(_)) = error {
+                // A turn the rules call off leaves the round as it was
+                prop_assert_eq!(&*state, &before);
+            }
+            check_invariants(state, before.tiles().count(TileKind::Resistance))?;
+            if before.is_over() {
+                // A finished round stays as it finished
+                prop_assert_eq!(state.outcome(), before.outcome());
+                prop_assert_eq!(state.current_turn(), before.current_turn());
+            }
+        }
+        Ok(())
+    }
+
+    /// The classic rules, except that a point is also scored on a given tile every turn, whatever is on it
+    #[derive(Debug)]
+    struct Stray(Vec<Coord>);
+
+    impl RuleSet for Stray {
+        fn name(&self) -> &str {
+            "stray"
+        }
+
+        fn round_results(&self, state: &RoundState, resistance: &ResistanceAction, suppression: &SuppressionAction) -> Result<Vec<RoundResult>, RuleError> {
+            let mut results = Classic.round_results(state, resistance, suppression)?;
+            results.push(RoundResult::ResistanceGainsPoint(self.0[state.current_turn() as usize % self.0.len()]));
+            Ok(results)
+        }
+    }
+
+    proptest! {
+        /// Tests that no sequence of actions, legal or not, breaks the round
+        #[test]
+        fn test_fuzz_round_invariants(config in any_config(), steps in prop::collection::vec(any_step(), 0..80)) {
+            play_steps(&mut RoundState::new(config), steps)?;
+        }
+
+        /// Tests that a rule set scoring points where it shouldn't can't break the round either
+        #[test]
+        fn test_fuzz_bad_results(
+            config in any_config(),
+            strays in prop::collection::vec((-1i8..8, -1i8..8), 1..4),
+            steps in prop::collection::vec(any_step(), 0..80)
+        ) {
+            play_steps(&mut RoundState::with_rules(config, Rules::new(Stray(strays))), steps)?;
+        }
+    }
+}
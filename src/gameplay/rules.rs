@@ -0,0 +1,454 @@
+/*! The rules a round is played under
+*
+* Every rule of the game - which actions are legal, what a pair of actions does, how the results
+* change the board, how timers run down and when the round is over - is a hook on the `RuleSet`
+* trait. The hooks default to the classic rules, so a variant only overrides the parts it changes
+* and `Classic` is just the trait with nothing overridden.
+*/
+
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use crate::gameplay::actors::{ResistanceAction, SuppressionAction};
+use crate::gameplay::round_config::CaptureRule;
+use crate::gameplay::round_manager::{ActionError, DrawReason, GameOutcome, RoundResult, RoundState};
+use crate::gameplay::round_manager::RoundResult::{ResistanceGainsPoint, ResistanceGainsTemporary, PrivateTileGained, PrivateTileRevealed, ResistanceEnclosesRegion, SuppressorReclaims};
+use crate::gameplay::tilemap::{Coord, TileKind, TileMap, TileState};
+
+/// A rule set couldn't decide what happens next, so the turn was called off.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleError {
+    /// The name of the rule set that failed.
+    pub rule_set: String,
+    /// What went wrong.
+    pub message: String
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the {} rules failed: {}", self.rule_set, self.message)
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+/// The hooks a round calls into as it is played.
+///
+/// The fallible hooks are all run before a turn is committed, so a `RuleError` leaves the round as
+/// it was before the action that completed the turn.
+pub(crate) trait RuleSet: fmt::Debug + Send + Sync {
+    /// The name the rule set is known by.
+    fn name(&self) -> &str;
+
+    /// Check a suppression action, returning it as it would be played
+    ///
+    /// Classically the zone has to fit the board and the zone rules, and is deduplicated.
+    fn validate_suppression_action(&self, state: &RoundState, action: &SuppressionAction) -> Result<SuppressionAction, ActionError> {
+        let zone = state.config().zone.validate(&action.suppression_zone, state.board())?;
+        Ok(SuppressionAction { suppression_zone: zone })
+    }
+
+    /// Check a resistance action
+    ///
+    /// Classically both coordinates have to be on the board, and the public one can't land on a
+    /// permanent tile.
+    fn validate_resistance_action(&self, state: &RoundState, action: &ResistanceAction) -> Result<(), ActionError> {
+        for coord in [action.public_coord, action.private_coord] {
+            if !state.board().contains(&coord) {
+                return Err(ActionError::OutOfBounds(coord));
+            }
+        }
+        if state.tiles().state_at(&action.public_coord) == Some(TileState::Resistance) {
+            return Err(ActionError::OccupiedByPermanent(action.public_coord));
+        }
+        Ok(())
+    }
+
+    /// Compare a resistance and suppression action
+    ///
+    /// The private coordinate is placed as a hidden temp tile when it lands outside the suppression
+    /// zone. Hidden tiles count towards surrounds like any other temp tile, and are revealed when the
+    /// suppression zone lands on them, when the resistance places its public coordinate on them, or
+    /// when they are captured. Coordinates that are off the board are never placed.
+    fn round_results(&self, state: &RoundState, resistance: &ResistanceAction, suppression: &SuppressionAction) -> Result<Vec<RoundResult>, RuleError> {
+        let tiles = state.tiles();
+        let mut results: Vec<RoundResult> = Vec::new();
+        let mut temps: Vec<Coord> = tiles.coords_in_state(TileKind::TemporaryResistance);
+        let mut hidden: Vec<Coord> = tiles.coords_in_state(TileKind::HiddenResistance);
+        let mut revealed: Vec<Coord> = Vec::new();
+
+        for coord in &hidden {
+            if suppression.suppression_zone.contains(coord) || *coord == resistance.public_coord {
+                // The suppressor found it, or the resistance chose to go public with it
+                results.push(PrivateTileRevealed(*coord));
+                revealed.push(*coord);
+            }
+        }
+
+        let public_placeable = state.board().contains(&resistance.public_coord)
+            && tiles.state_at(&resistance.public_coord).is_none();
+        if public_placeable && !suppression.suppression_zone.contains(&resistance.public_coord) {
+            // The public coordinate is outside the suppression zone, add it to the temporaries
+            results.push(ResistanceGainsTemporary(resistance.public_coord));
+            temps.push(resistance.public_coord)
+        }
+
+        let private_coord = resistance.private_coord;
+        let already_taken = temps.contains(&private_coord) || tiles.state_at(&private_coord).is_some();
+        let private_placeable = state.board().contains(&private_coord) && !already_taken;
+        if private_placeable && !suppression.suppression_zone.contains(&private_coord) {
+            // The private coordinate slipped past the suppressor, hide it among the temporaries
+            results.push(PrivateTileGained(private_coord));
+            hidden.push(private_coord)
+        }
+
+        let all_temps: Vec<Coord> = temps.iter().chain(hidden.iter()).cloned().collect();
+        let captured: Vec<Coord> = match state.config().capture_rule {
+            CaptureRule::FourNeighbor => all_temps.iter().cloned()
+                .filter(|coord| number_of_suppressed_neighbors(state.tiles(), coord, &all_temps) == 0)
+                .collect(),
+            CaptureRule::Enclosure => {
+                let regions = enclosed_regions(state, &all_temps);
+                let walls: Vec<Coord> = all_temps.iter().cloned()
+                    .filter(|coord| state.board().neighbors_of(coord).iter().any(|n| regions.iter().any(|r| r.contains(n))))
+                    .collect();
+                results.extend(regions.into_iter().map(ResistanceEnclosesRegion));
+                walls
+            }
+        };
+        for coord in &captured {
+            if hidden.contains(coord) && !revealed.contains(coord) {
+                // Capturing a hidden tile gives it away
+                results.push(PrivateTileRevealed(*coord));
+                revealed.push(*coord);
+            }
+            // This is now a perm because it is totally surrounded
+            results.push(ResistanceGainsPoint(*coord))
+        }
+
+        Ok(results)
+    }
+
+    /// Apply results to the board, in order
+    ///
+    /// Only a temp tile can become a perm, so a point named anywhere else is an error. The turn it
+    /// came from is called off, undoing any results already applied.
+    fn apply_results(&self, state: &mut RoundState, results: &[RoundResult]) -> Result<(), RuleError> {
+        let temp_turn_count = state.config().temp_turn_count;
+        for result in results {
+            match result {
+                ResistanceGainsPoint(coord) => {
+                    if !state.tiles().state_at(coord).is_some_and(|tile| tile.kind() == TileKind::TemporaryResistance) {
+                        return Err(RuleError {
+                            rule_set: self.name().to_string(),
+                            message: format!("a point was scored at {:?}, which isn't a temp tile", coord)
+                        });
+                    }
+                    place_tile(state, *coord, TileState::Resistance);
+                },
+                ResistanceGainsTemporary(coord) => {
+                    place_tile(state, *coord, TileState::TemporaryResistance(temp_turn_count))
+                },
+                PrivateTileGained(coord) => {
+                    place_tile(state, *coord, TileState::HiddenResistance(temp_turn_count))
+                },
+                PrivateTileRevealed(coord) => {
+                    // The tile keeps its timer, it is just no longer hidden
+                    if let Some(TileState::HiddenResistance(turns)) = state.tiles().state_at(coord) {
+                        place_tile(state, *coord, TileState::TemporaryResistance(turns));
+                    }
+                },
+                ResistanceEnclosesRegion(region) => {
+                    for coord in region {
+                        place_tile(state, *coord, TileState::Resistance);
+                    }
+                },
+                SuppressorReclaims(coord) => {
+                    place_tile(state, *coord, TileState::Suppressor)
+                }
+            }
+        };
+        Ok(())
+    }
+
+    /// Advance the siege on every perm tile, reclaiming those besieged long enough
+    ///
+    /// Run after the round results are applied. A perm tile is besieged when the suppression zone
+    /// covers it and no neighbor on the board is held by the resistance, hidden tiles included. The
+    /// count resets as soon as a turn goes by without the siege. Does nothing unless the reclaim rules
+    /// set `siege_turns`.
+    fn reclaim_results(&self, state: &mut RoundState, suppression: &SuppressionAction) -> Result<Vec<RoundResult>, RuleError> {
+        let siege_turns = match state.config().reclaim.siege_turns {
+            Some(siege_turns) => siege_turns,
+            None => return Ok(Vec::new())
+        };
+        let mut results: Vec<RoundResult> = Vec::new();
+        let mut sieges: Vec<(Coord, u32)> = Vec::new();
+        for coord in state.tiles().coords_in_state(TileKind::Resistance) {
+            let besieged = suppression.suppression_zone.contains(&coord)
+                && state.board().neighbors_of(&coord).iter().all(|neighbor| !state.tiles().is_resistance(neighbor));
+            if !besieged {
+                continue;
+            }
+            let turns = state.sieges().iter().find(|(besieged, _)| *besieged == coord).map_or(0, |(_, turns)| *turns) + 1;
+            if turns >= siege_turns {
+                results.push(SuppressorReclaims(coord));
+            } else {
+                sieges.push((coord, turns));
+            }
+        }
+        state.set_sieges(sieges);
+        Ok(results)
+    }
+
+    /// Count down the temp tiles at the end of a turn
+    ///
+    /// Returns the temp tiles that ran out, and whether each was still hidden.
+    fn decay_timers(&self, state: &mut RoundState) -> Vec<(Coord, bool)> {
+        let hidden = state.tiles().coords_in_state(TileKind::HiddenResistance);
+        // Decrement everything down to at or above 0, removing the 0s
+        let expired = state.tiles_mut().tick_timers();
+        expired.into_iter().map(|coord| (coord, hidden.contains(&coord))).collect()
+    }
+
+    /// Check the win conditions against the current state of the round
+    fn check_outcome(&self, state: &RoundState) -> Result<Option<GameOutcome>, RuleError> {
+        let config = state.config();
+        let turn = state.current_turn();
+        let score = state.resistance_score();
+        let reached_score = score >= config.score_to_win;
+        let out_of_turns = turn >= config.max_turns;
+        let reclaimed = state.tiles().count(TileKind::Suppressor) as u32;
+        let reclaimed_enough = config.reclaim.tiles_to_win.is_some_and(|target| reclaimed >= target);
+        Ok(match (reached_score, out_of_turns, reclaimed_enough) {
+            (true, _, true) => Some(GameOutcome::Draw(DrawReason::BothSidesScored)),
+            (true, _, false) => Some(GameOutcome::ResistanceWins { score, turn }),
+            (false, _, true) => Some(GameOutcome::SuppressionReclaims { reclaimed, turn }),
+            (false, true, false) => Some(GameOutcome::SuppressionWins { score }),
+            (false, false, false) => None
+        })
+    }
+}
+
+/// The rules as the game was designed, configured only by the round's `RoundConfig`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Classic;
+
+impl RuleSet for Classic {
+    fn name(&self) -> &str {
+        "classic"
+    }
+}
+
+/// A shared handle to the rule set a round is played under.
+///
+/// Handles compare equal when their rule sets have the same name.
+#[derive(Clone)]
+pub(crate) struct Rules(Arc<dyn RuleSet>);
+
+impl Rules {
+    /// Wrap a rule set so rounds can share it
+    pub fn new(rules: impl RuleSet + 'static) -> Rules {
+        Rules(Arc::new(rules))
+    }
+}
+
+impl Default for Rules {
+    /// The classic rules.
+    fn default() -> Self {
+        Rules::new(Classic)
+    }
+}
+
+impl Deref for Rules {
+    type Target = dyn RuleSet;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+impl PartialEq for Rules {
+    fn eq(&self, other: &Self) -> bool {
+        self.name() == other.name()
+    }
+}
+
+impl fmt::Debug for Rules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Rules({})", self.name())
+    }
+}
+
+/// Get the number of neighbors still suppressed
+///
+/// Only neighbors on the board count, so tiles along the edge need fewer neighbors to be surrounded.
+/// Takes the tiles rather than the round so players can ask the same of their view of it.
+pub(crate) fn number_of_suppressed_neighbors(tiles: &TileMap, coord: &Coord, temps: &[Coord]) -> u8 {
+    let total_neighbors = tiles.board().neighbors_of(coord);
+    let surrounding_resistance: Vec<Coord> = total_neighbors.iter().cloned().filter(
+        |neighbor| {
+            // The coordinate counts when it's either a perm or temp resistance territory
+            tiles.state_at(neighbor) == Some(TileState::Resistance) || temps.contains(neighbor)
+        }
+    ).collect();
+    (total_neighbors.len() - surrounding_resistance.len()) as u8
+}
+
+/// Find every region of the board the resistance has walled off from the edge
+///
+/// Flood fills inwards from the tiles along the edge of the board that the resistance doesn't
+/// hold; whatever empty tiles the fill can't reach are enclosed. Suppressor tiles let the fill
+/// through but are never captured. Tiles only connect orthogonally, so a diagonal gap in a wall
+/// doesn't let the fill through. Each region is returned as its own sorted list of tiles.
+pub(crate) fn enclosed_regions(state: &RoundState, temps: &[Coord]) -> Vec<Vec<Coord>> {
+    let board = state.board();
+    let tiles = state.tiles();
+    let is_open = |coord: &Coord| !tiles.is_resistance(coord) && !temps.contains(coord);
+
+    let mut reached: BTreeSet<Coord> = BTreeSet::new();
+    let mut frontier: VecDeque<Coord> = board.coords().filter(|coord| board.is_edge(coord) && is_open(coord)).collect();
+    reached.extend(frontier.iter().cloned());
+    while let Some(coord) = frontier.pop_front() {
+        for neighbor in board.neighbors_of(&coord) {
+            if is_open(&neighbor) && reached.insert(neighbor) {
+                frontier.push_back(neighbor);
+            }
+        }
+    }
+
+    let mut regions: Vec<Vec<Coord>> = Vec::new();
+    for start in board.coords().filter(|coord| is_open(coord)) {
+        // Group what's left into connected regions, reusing `reached` to skip grouped tiles
+        if !reached.insert(start) {
+            continue;
+        }
+        let mut region: Vec<Coord> = vec![start];
+        let mut frontier: VecDeque<Coord> = VecDeque::from([start]);
+        while let Some(coord) = frontier.pop_front() {
+            for neighbor in board.neighbors_of(&coord) {
+                if is_open(&neighbor) && reached.insert(neighbor) {
+                    region.push(neighbor);
+                    frontier.push_back(neighbor);
+                }
+            }
+        }
+        region.retain(|coord| tiles.state_at(coord).is_none());
+        region.sort();
+        if !region.is_empty() {
+            regions.push(region);
+        }
+    }
+    regions
+}
+
+/// Put a tile from a result on the board
+fn place_tile(state: &mut RoundState, coord: Coord, tile: TileState) {
+    // Results only ever name coordinates on the board, so there is nothing to place otherwise
+    let _ = state.tiles_mut().set(coord, tile);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gameplay::actors::{ResistanceAction, SuppressionAction};
+    use crate::gameplay::round_config::RoundConfig;
+    use crate::gameplay::round_manager::{ActionError, GameOutcome, RoundResult, RoundState};
+    use crate::gameplay::rules::{RuleError, RuleSet, Rules};
+    use crate::gameplay::tilemap::{Coord, TileKind};
+
+    /// A variant where temp tiles never expire and the resistance wins on its first capture
+    #[derive(Debug)]
+    struct Evergreen;
+
+    impl RuleSet for Evergreen {
+        fn name(&self) -> &str {
+            "evergreen"
+        }
+
+        fn decay_timers(&self, _state: &mut RoundState) -> Vec<(Coord, bool)> {
+            Vec::new()
+        }
+
+        fn check_outcome(&self, state: &RoundState) -> Result<Option<GameOutcome>, RuleError> {
+            let score = state.resistance_score();
+            Ok((score > 0).then_some(GameOutcome::ResistanceWins { score, turn: state.current_turn() }))
+        }
+    }
+
+    /// A variant whose results can't be computed
+    #[derive(Debug)]
+    struct Broken;
+
+    impl RuleSet for Broken {
+        fn name(&self) -> &str {
+            "broken"
+        }
+
+        fn round_results(&self, _: &RoundState, _: &ResistanceAction, _: &SuppressionAction) -> Result<Vec<RoundResult>, RuleError> {
+            Err(RuleError { rule_set: self.name().to_string(), message: "no results".to_string() })
+        }
+    }
+
+    /// A variant that awards a point on a tile nothing was placed on
+    #[derive(Debug)]
+    struct Phantom;
+
+    impl RuleSet for Phantom {
+        fn name(&self) -> &str {
+            "phantom"
+        }
+
+        fn round_results(&self, _: &RoundState, _: &ResistanceAction, _: &SuppressionAction) -> Result<Vec<RoundResult>, RuleError> {
+            Ok(vec![RoundResult::ResistanceGainsPoint((4, 4))])
+        }
+    }
+
+    fn play_turn(state: &mut RoundState, zone: Vec<Coord>, public_coord: Coord, private_coord: Coord) -> Result<(), ActionError> {
+        state.intake_suppression_action(SuppressionAction { suppression_zone: zone })?;
+        state.intake_resistance_action(ResistanceAction { public_coord, private_coord })?;
+        Ok(())
+    }
+
+    /// Tests that a variant only changes the hooks it overrides
+    #[test]
+    fn test_variant_overrides_hooks() {
+        let config = RoundConfig::builder().temp_turn_count(1).build().unwrap();
+        let mut state = RoundState::with_rules(config, Rules::new(Evergreen));
+        play_turn(&mut state, vec![(8, 8)], (0, 0), (0, 1)).unwrap();
+        play_turn(&mut state, vec![(8, 8)], (1, 0), (1, 1)).unwrap();
+        // The first turn's tiles would have expired under the classic rules, so the corner is surrounded
+        assert_eq!(state.tiles().count(TileKind::TemporaryResistance), 3);
+        assert_eq!(state.outcome(), Some(GameOutcome::ResistanceWins { score: 1, turn: 2 }));
+
+        // The rules carry over to forks of the round
+        assert_eq!(state.fork_at(1).unwrap().rules().name(), "evergreen");
+    }
+
+    /// Tests that a failing rule set calls the turn off without changing the round
+    #[test]
+    fn test_rule_error_leaves_round_untouched() {
+        let mut state = RoundState::with_rules(RoundConfig::default(), Rules::new(Broken));
+        state.intake_suppression_action(SuppressionAction { suppression_zone: vec![(8, 8)] }).unwrap();
+        let before = state.clone();
+        let result = state.intake_resistance_action(ResistanceAction { public_coord: (0, 0), private_coord: (1, 1) });
+        assert!(matches!(result, Err(ActionError::RuleFailed(RuleError { .. }))));
+        assert_eq!(state, before);
+        // The suppressor's action is still waiting for a resistance action
+        assert!(state.turn_buffer().0.is_some());
+    }
+
+    /// Tests that a point on a tile that isn't temporary calls the turn off
+    #[test]
+    fn test_point_without_temp_is_rejected() {
+        let mut state = RoundState::with_rules(RoundConfig::default(), Rules::new(Phantom));
+        state.intake_suppression_action(SuppressionAction { suppression_zone: vec![(8, 8)] }).unwrap();
+        let before = state.clone();
+        let result = state.intake_resistance_action(ResistanceAction { public_coord: (0, 0), private_coord: (1, 1) });
+        assert!(matches!(result, Err(ActionError::RuleFailed(RuleError { ref rule_set, .. })) if rule_set == "phantom"));
+        assert_eq!(state, before);
+        assert_eq!(state.tiles().state_at(&(4, 4)), None);
+        assert_eq!(state.resistance_score(), 0);
+    }
+}
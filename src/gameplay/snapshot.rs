@@ -0,0 +1,308 @@
+/*! Saving and restoring a round in progress
+*
+* A snapshot is the complete `RoundState` tagged with a format version, so saved games and states
+* sent over the network can be checked for compatibility before they are restored.
+*
+* A rule set is code rather than data, so a snapshot only records the name of the one the round was
+* played under. Restoring a round played under anything but the classic rules takes the rule set
+* from the caller, and fails if it isn't the one the round was saved with.
+*/
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::{Serialize, Deserialize};
+
+use crate::gameplay::round_manager::RoundState;
+use crate::gameplay::rules::Rules;
+use crate::gameplay::tilemap::CoordinateSystem;
+
+/// The snapshot format written by this build. Bump it whenever `RoundState` changes shape.
+pub const SNAPSHOT_FORMAT_VERSION: u32 = 3;
+
+/// A versioned copy of a whole round, hidden tiles and buffered actions included.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoundSnapshot {
+    pub format_version: u32,
+    /// The name of the rule set the round was played under.
+    pub rule_set: String,
+    pub state: RoundState
+}
+
+/// Just enough of a snapshot to check its version before parsing the rest.
+#[derive(Deserialize)]
+struct SnapshotHeader {
+    format_version: u32
+}
+
+/// Everything that can go wrong saving or restoring a snapshot.
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The snapshot was written by an incompatible version of the engine.
+    UnsupportedVersion { found: u32, supported: u32 },
+    /// The snapshot couldn't be written or parsed.
+    Serde(String),
+    /// The snapshot parsed, but describes a round that can't exist.
+    InvalidState(String),
+    /// The round was saved under a different rule set than the one it is being restored with.
+    RulesMismatch { saved: String, given: String },
+    /// The snapshot file couldn't be read or written.
+    Io(std::io::Error)
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::UnsupportedVersion { found, supported } => write!(
+                f, "snapshot format version {} isn't supported, expected {}", found, supported
+            ),
+            SnapshotError::Serde(message) => write!(f, "couldn't serialize snapshot: {}", message),
+            SnapshotError::InvalidState(message) => write!(f, "snapshot holds an invalid round: {}", message),
+            SnapshotError::RulesMismatch { saved, given } => write!(
+                f, "snapshot was saved under the {} rules, not the {} rules", saved, given
+            ),
+            SnapshotError::Io(error) => write!(f, "couldn't access snapshot: {}", error)
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<std::io::Error> for SnapshotError {
+    fn from(error: std::io::Error) -> Self {
+        SnapshotError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(error: serde_json::Error) -> Self {
+        SnapshotError::Serde(error.to_string())
+    }
+}
+
+impl RoundSnapshot {
+    /// Take a snapshot of the round as it stands
+    pub fn of(state: &RoundState) -> RoundSnapshot {
+        RoundSnapshot { format_version: SNAPSHOT_FORMAT_VERSION, rule_set: state.rules().name().to_string(), state: state.clone() }
+    }
+
+    /// The round the snapshot holds, played under the rule set it was saved with
+    pub(crate) fn into_state(self, rules: Rules) -> Result<RoundState, SnapshotError> {
+        if rules.name() != self.rule_set {
+            return Err(SnapshotError::RulesMismatch { saved: self.rule_set, given: rules.name().to_string() });
+        }
+        let mut state = self.state;
+        state.set_rules(rules);
+        Ok(state)
+    }
+
+    /// Serialize the snapshot to JSON
+    pub fn to_json(&self) -> Result<String, SnapshotError> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Parse a snapshot from JSON, checking its version and contents
+    pub fn from_json(source: &str) -> Result<RoundSnapshot, SnapshotError> {
+        let header: SnapshotHeader = serde_json::from_str(source)?;
+        if header.format_version != SNAPSHOT_FORMAT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion { found: header.format_version, supported: SNAPSHOT_FORMAT_VERSION });
+        }
+        let snapshot: RoundSnapshot = serde_json::from_str(source)?;
+        snapshot.validate()?;
+        Ok(snapshot)
+    }
+
+    /// Check that the snapshot describes a round that could have been played
+    fn validate(&self) -> Result<(), SnapshotError> {
+        let state = &self.state;
+        state.config().validate().map_err(|error| SnapshotError::InvalidState(error.to_string()))?;
+        if state.board().width() != state.config().board_width || state.board().height() != state.config().board_height {
+            return Err(SnapshotError::InvalidState("board size doesn't match the config".to_string()));
+        }
+        if let Some(tile) = state.tiles().iter().find(|tile| !state.board().contains(&tile.coord())) {
+            return Err(SnapshotError::InvalidState(format!("tile {:?} is off the board", tile.coord())));
+        }
+        if state.current_turn() > state.config().max_turns {
+            return Err(SnapshotError::InvalidState("turn count is past max_turns".to_string()));
+        }
+        if state.turn_history().len() != state.current_turn() as usize {
+            return Err(SnapshotError::InvalidState(format!(
+                "turn count is {} but {} turns were played", state.current_turn(), state.turn_history().len()
+            )));
+        }
+        let timers = 1..=state.config().temp_turn_count;
+        if let Some(tile) = state.tiles().iter().find(|tile| tile.state().timer().is_some_and(|timer| !timers.contains(&timer))) {
+            return Err(SnapshotError::InvalidState(format!("tile {:?} has a timer out of range", tile.coord())));
+        }
+        Ok(())
+    }
+}
+
+impl RoundState {
+    /// Serialize the whole round to a versioned JSON snapshot
+    pub fn save_snapshot(&self) -> Result<String, SnapshotError> {
+        RoundSnapshot::of(self).to_json()
+    }
+
+    /// Restore a round played under the classic rules exactly as it was saved by `save_snapshot`
+    pub fn restore_snapshot(source: &str) -> Result<RoundState, SnapshotError> {
+        Self::restore_snapshot_with_rules(source, Rules::default())
+    }
+
+    /// Restore a round exactly as it was saved by `save_snapshot`, given the rule set it was played under
+    pub(crate) fn restore_snapshot_with_rules(source: &str, rules: Rules) -> Result<RoundState, SnapshotError> {
+        RoundSnapshot::from_json(source)?.into_state(rules)
+    }
+
+    /// Save the round to a snapshot file
+    pub fn save_snapshot_file(&self, path: &Path) -> Result<(), SnapshotError> {
+        fs::write(path, self.save_snapshot()?)?;
+        Ok(())
+    }
+
+    /// Restore a round played under the classic rules from a snapshot file
+    pub fn restore_snapshot_file(path: &Path) -> Result<RoundState, SnapshotError> {
+        Self::restore_snapshot(&fs::read_to_string(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use crate::gameplay::actors::{ResistanceAction, SuppressionAction};
+    use crate::gameplay::round_config::RoundConfig;
+    use crate::gameplay::round_manager::RoundState;
+    use crate::gameplay::rules::{RuleSet, Rules};
+    use crate::gameplay::snapshot::{RoundSnapshot, SnapshotError, SNAPSHOT_FORMAT_VERSION};
+    use crate::gameplay::tilemap::Coord;
+
+    /// A coordinate that is usually on a small board, but not always
+    fn any_coord() -> impl Strategy<Value = Coord> {
+        (-1i8..7, -1i8..7)
+    }
+
+    /// A turn of actions, legal or not
+    fn any_turn() -> impl Strategy<Value = (Vec<Coord>, Coord, Coord)> {
+        (prop::collection::vec(any_coord(), 0..6), any_coord(), any_coord())
+    }
+
+    /// Plays the turns on a small round, ignoring whatever gets rejected
+    fn play(turns: &[(Vec<Coord>, Coord, Coord)], contiguous: bool) -> RoundState {
+        let config = RoundConfig::builder()
+            .board_size(6, 6)
+            .max_turns(12)
+            .score_to_win(4)
+            .zone_contiguous(contiguous)
+            .build()
+            .unwrap();
+        let mut state = RoundState::new(config);
+        for (zone, public_coord, private_coord) in turns {
+            let _ = state.intake_suppression_action(SuppressionAction { suppression_zone: zone.clone() });
+            let _ = state.intake_resistance_action(ResistanceAction { public_coord: *public_coord, private_coord: *private_coord });
+        }
+        state
+    }
+
+    proptest! {
+        /// Tests that nothing is lost saving and restoring a randomly played round
+        #[test]
+        fn test_snapshot_round_trip(turns in prop::collection::vec(any_turn(), 0..30), contiguous in any::<bool>()) {
+            let state = play(&turns, contiguous);
+            let restored = RoundState::restore_snapshot(&state.save_snapshot().unwrap()).unwrap();
+            prop_assert_eq!(&restored, &state);
+            // The restored round keeps playing the same way as the original
+            let replayed = restored.fork_at(restored.current_turn()).unwrap();
+            prop_assert_eq!(replayed.tiles(), state.tiles());
+        }
+    }
+
+    /// A variant that plays like the classic rules under another name
+    #[derive(Debug)]
+    struct Variant;
+
+    impl RuleSet for Variant {
+        fn name(&self) -> &str {
+            "variant"
+        }
+    }
+
+    /// Tests that a variant round only comes back under the rule set it was saved with
+    #[test]
+    fn test_snapshot_keeps_rule_set() {
+        let mut state = RoundState::with_rules(RoundConfig::default(), Rules::new(Variant));
+        state.intake_suppression_action(SuppressionAction { suppression_zone: vec![(8, 8)] }).unwrap();
+        state.intake_resistance_action(ResistanceAction { public_coord: (0, 0), private_coord: (1, 1) }).unwrap();
+        let saved = state.save_snapshot().unwrap();
+        assert_eq!(RoundSnapshot::from_json(&saved).unwrap().rule_set, "variant");
+
+        let restored = RoundState::restore_snapshot_with_rules(&saved, Rules::new(Variant)).unwrap();
+        assert_eq!(restored, state);
+        assert_eq!(restored.rules().name(), "variant");
+        // Never quietly swapped for the classic rules
+        assert!(matches!(
+            RoundState::restore_snapshot(&saved),
+            Err(SnapshotError::RulesMismatch { saved, given }) if saved == "variant" && given == "classic"
+        ));
+    }
+
+    /// Tests that snapshots from another format version are refused
+    #[test]
+    fn test_snapshot_version_mismatch() {
+        let mut snapshot = RoundSnapshot::of(&RoundState::default());
+        snapshot.format_version = SNAPSHOT_FORMAT_VERSION + 1;
+        let result = RoundSnapshot::from_json(&snapshot.to_json().unwrap());
+        assert!(matches!(result, Err(SnapshotError::UnsupportedVersion { found, .. }) if found == SNAPSHOT_FORMAT_VERSION + 1));
+    }
+
+    /// Tests that malformed snapshots are refused
+    #[test]
+    fn test_snapshot_rejects_garbage() {
+        assert!(matches!(RoundState::restore_snapshot("{}"), Err(SnapshotError::Serde(_))));
+        let json = RoundState::default().save_snapshot().unwrap().replace("\"tiles\":[]", "\"tiles\":[{\"state\":\"Resistance\",\"coord\":[40,0]}]");
+        assert!(matches!(RoundState::restore_snapshot(&json), Err(SnapshotError::InvalidState(_))));
+    }
+
+    /// Edit the state held by a snapshot of a round, returning the edited snapshot
+    fn edit_snapshot(state: &RoundState, edit: impl FnOnce(&mut serde_json::Value)) -> String {
+        let mut snapshot: serde_json::Value = serde_json::from_str(&state.save_snapshot().unwrap()).unwrap();
+        edit(&mut snapshot["state"]);
+        snapshot.to_string()
+    }
+
+    /// Tests that a snapshot whose turn count disagrees with its history is rejected
+    #[test]
+    fn test_snapshot_rejects_turn_mismatch() {
+        let state = play(&[(vec![(0, 0)], (2, 2), (3, 3)), (vec![(5, 5)], (1, 2), (4, 4))], true);
+        assert_eq!(state.current_turn(), 2);
+        let json = edit_snapshot(&state, |state| state["current_turn"] = 1.into());
+        assert!(matches!(RoundState::restore_snapshot(&json), Err(SnapshotError::InvalidState(_))));
+    }
+
+    /// Tests that a snapshot holding a temp timer outside the config's range is rejected
+    #[test]
+    fn test_snapshot_rejects_bad_timer() {
+        let state = play(&[(vec![(0, 0)], (2, 2), (3, 3))], true);
+        let temp_turn_count = state.config().temp_turn_count;
+        for timer in [0, temp_turn_count + 1] {
+            let json = edit_snapshot(&state, |state| {
+                let tiles = state["tiles"]["tiles"].as_array_mut().unwrap();
+                let temp = tiles.iter_mut().find(|tile| tile["state"].get("TemporaryResistance").is_some()).unwrap();
+                temp["state"]["TemporaryResistance"] = timer.into();
+            });
+            assert!(matches!(RoundState::restore_snapshot(&json), Err(SnapshotError::InvalidState(_))), "timer {}", timer);
+        }
+    }
+
+    /// Tests saving to and restoring from a file
+    #[test]
+    fn test_snapshot_file() {
+        let state = play(&[(vec![(0, 0)], (2, 2), (3, 3))], true);
+        let path = std::env::temp_dir().join("deepphase_snapshot_test.json");
+        state.save_snapshot_file(&path).unwrap();
+        let restored = RoundState::restore_snapshot_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(restored, state);
+    }
+}
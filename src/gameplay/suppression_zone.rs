@@ -0,0 +1,275 @@
+/*! Rules for the shape and size of the suppression zone
+*
+* The suppressor may only cover a limited part of the board each turn. `ZoneRules` caps the number
+* of tiles, can require the zone to be contiguous, and can restrict it to a set of shape templates
+* which may be placed anywhere on the board in any rotation.
+*/
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use serde::{Serialize, Deserialize};
+
+use crate::gameplay::tilemap::{Board, Coord, CoordinateSystem, Tile};
+
+/// Shape templates the suppression zone can be restricted to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ZoneShape {
+    /// A 2x2 block anchored on its south-west corner.
+    Square,
+    /// A straight line of the given length, starting at the origin.
+    Line(u8),
+    /// A plus sign centered on the origin.
+    Cross,
+    /// Three tiles up from the origin plus one to the side of it.
+    L
+}
+
+/// A clockwise rotation applied to a shape template.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Rotation {
+    None,
+    Quarter,
+    Half,
+    ThreeQuarter
+}
+
+pub const ROTATIONS: [Rotation; 4] = [Rotation::None, Rotation::Quarter, Rotation::Half, Rotation::ThreeQuarter];
+
+/** Limits on the suppression zone
+
+*Fields*
+- `min_tiles` (u32): the fewest tiles a zone can cover
+- `max_tiles` (u32): the most tiles a zone can cover
+- `contiguous` (bool): whether every tile in the zone has to connect to the rest orthogonally
+- `shapes` (Vec<ZoneShape>): the templates the zone has to match, any shape is allowed when empty
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ZoneRules {
+    pub min_tiles: u32,
+    pub max_tiles: u32,
+    pub contiguous: bool,
+    pub shapes: Vec<ZoneShape>
+}
+
+/// The ways a suppression zone can break the `ZoneRules`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ZoneError {
+    TooFewTiles { count: u32, min: u32 },
+    TooManyTiles { count: u32, max: u32 },
+    OutOfBounds(Coord),
+    NotContiguous,
+    ShapeNotAllowed
+}
+
+impl fmt::Display for ZoneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZoneError::TooFewTiles { count, min } => write!(f, "zone covers {} tiles but needs at least {}", count, min),
+            ZoneError::TooManyTiles { count, max } => write!(f, "zone covers {} tiles but may cover at most {}", count, max),
+            ZoneError::OutOfBounds(coord) => write!(f, "zone tile {:?} is off the board", coord),
+            ZoneError::NotContiguous => write!(f, "zone tiles must all be connected"),
+            ZoneError::ShapeNotAllowed => write!(f, "zone doesn't match any of the allowed shapes")
+        }
+    }
+}
+
+impl std::error::Error for ZoneError {}
+
+impl Default for ZoneRules {
+    /// The default zone limits.
+    fn default() -> Self {
+        Self {
+            min_tiles: 1,
+            max_tiles: 4,
+            contiguous: true,
+            shapes: Vec::new()
+        }
+    }
+}
+
+impl Rotation {
+    /// Rotate an offset clockwise around the origin
+    pub fn apply(&self, offset: (i16, i16)) -> (i16, i16) {
+        let (x, y) = offset;
+        match self {
+            Rotation::None => (x, y),
+            Rotation::Quarter => (y, -x),
+            Rotation::Half => (-x, -y),
+            Rotation::ThreeQuarter => (-y, x)
+        }
+    }
+}
+
+impl ZoneShape {
+    /// The offsets of the template from its origin before any rotation.
+    pub fn offsets(&self) -> Vec<(i16, i16)> {
+        match self {
+            ZoneShape::Square => vec![(0, 0), (1, 0), (0, 1), (1, 1)],
+            ZoneShape::Line(length) => (0..*length as i16).map(|x| (x, 0)).collect(),
+            ZoneShape::Cross => vec![(0, 0), (0, 1), (1, 0), (0, -1), (-1, 0)],
+            ZoneShape::L => vec![(0, 0), (0, 1), (0, 2), (1, 0)]
+        }
+    }
+
+    /// The number of tiles the template covers.
+    pub fn tile_count(&self) -> u32 {
+        self.offsets().len() as u32
+    }
+
+    /// Place the template on a coordinate
+    ///
+    /// Returns `None` if any tile of the placed shape would overflow a `Coord`.
+    ///
+    /// ## Arguments:
+    /// * `origin` - where the template's origin lands
+    /// * `rotation` - the clockwise rotation applied around the origin
+    pub fn place(&self, origin: Coord, rotation: Rotation) -> Option<Vec<Coord>> {
+        self.offsets().into_iter().map(|offset| {
+            let (dx, dy) = rotation.apply(offset);
+            let x = i8::try_from(origin.0 as i16 + dx).ok()?;
+            let y = i8::try_from(origin.1 as i16 + dy).ok()?;
+            Some((x, y))
+        }).collect()
+    }
+
+    /// Whether the (deduplicated, sorted) zone is this shape in some rotation and position
+    fn matches(&self, zone: &[Coord]) -> bool {
+        let target = normalized(zone.iter().map(|c| (c.0 as i16, c.1 as i16)).collect());
+        ROTATIONS.iter().any(|rotation| {
+            let rotated = self.offsets().into_iter().map(|offset| rotation.apply(offset)).collect();
+            normalized(rotated) == target
+        })
+    }
+}
+
+/// Translate offsets so the smallest x and y sit at zero, then sort and dedupe them
+fn normalized(mut offsets: Vec<(i16, i16)>) -> Vec<(i16, i16)> {
+    let min_x = offsets.iter().map(|o| o.0).min().unwrap_or(0);
+    let min_y = offsets.iter().map(|o| o.1).min().unwrap_or(0);
+    for offset in offsets.iter_mut() {
+        *offset = (offset.0 - min_x, offset.1 - min_y);
+    }
+    offsets.sort();
+    offsets.dedup();
+    offsets
+}
+
+/// Whether every tile in the zone connects to every other tile orthogonally
+fn is_contiguous(zone: &[Coord]) -> bool {
+    let first = match zone.first() {
+        Some(first) => *first,
+        None => return true
+    };
+    let mut visited: Vec<Coord> = vec![first];
+    let mut frontier: VecDeque<Coord> = VecDeque::from([first]);
+    while let Some(coord) = frontier.pop_front() {
+        for neighbor in Tile::neighbors_of(&coord) {
+            if zone.contains(&neighbor) && !visited.contains(&neighbor) {
+                visited.push(neighbor);
+                frontier.push_back(neighbor);
+            }
+        }
+    }
+    visited.len() == zone.len()
+}
+
+impl ZoneRules {
+    /// Check a suppression zone against the rules
+    ///
+    /// Returns the zone sorted and with duplicate tiles removed, which is what gets played.
+    ///
+    /// ## Arguments:
+    /// * `zone` - the tiles the suppressor wants to cover
+    /// * `board` - the board the zone has to fit on
+    pub fn validate(&self, zone: &[Coord], board: &Board) -> Result<Vec<Coord>, ZoneError> {
+        let mut zone = zone.to_vec();
+        zone.sort();
+        zone.dedup();
+
+        if let Some(coord) = zone.iter().find(|coord| !board.contains(coord)) {
+            return Err(ZoneError::OutOfBounds(*coord));
+        }
+        let count = zone.len() as u32;
+        if count < self.min_tiles.max(1) {
+            return Err(ZoneError::TooFewTiles { count, min: self.min_tiles.max(1) });
+        }
+        if count > self.max_tiles {
+            return Err(ZoneError::TooManyTiles { count, max: self.max_tiles });
+        }
+        if self.contiguous && !is_contiguous(&zone) {
+            return Err(ZoneError::NotContiguous);
+        }
+        if !self.shapes.is_empty() && !self.shapes.iter().any(|shape| shape.matches(&zone)) {
+            return Err(ZoneError::ShapeNotAllowed);
+        }
+        Ok(zone)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gameplay::suppression_zone::{Rotation, ZoneError, ZoneRules, ZoneShape};
+    use crate::gameplay::tilemap::Board;
+
+    /// Tests that templates rotate clockwise around their origin
+    #[test]
+    fn test_shape_place_rotated() {
+        let line = ZoneShape::Line(3);
+        assert_eq!(line.place((4, 4), Rotation::None), Some(vec![(4, 4), (5, 4), (6, 4)]));
+        assert_eq!(line.place((4, 4), Rotation::Quarter), Some(vec![(4, 4), (4, 3), (4, 2)]));
+        assert_eq!(line.place((4, 4), Rotation::Half), Some(vec![(4, 4), (3, 4), (2, 4)]));
+        assert_eq!(ZoneShape::Square.place((i8::MAX, 0), Rotation::None), None);
+    }
+
+    /// Tests that duplicates are removed from valid zones
+    #[test]
+    fn test_validate_dedupes() {
+        let rules = ZoneRules::default();
+        let zone = rules.validate(&[(1, 1), (1, 1), (1, 2)], &Board::default()).unwrap();
+        assert_eq!(zone, vec![(1, 1), (1, 2)]);
+    }
+
+    /// Tests the size and bounds limits
+    #[test]
+    fn test_validate_limits() {
+        let board = Board::new(5, 5);
+        let rules = ZoneRules { min_tiles: 2, max_tiles: 3, contiguous: false, shapes: Vec::new() };
+        assert_eq!(rules.validate(&[(0, 0)], &board), Err(ZoneError::TooFewTiles { count: 1, min: 2 }));
+        assert_eq!(
+            rules.validate(&[(0, 0), (1, 0), (2, 0), (3, 0)], &board),
+            Err(ZoneError::TooManyTiles { count: 4, max: 3 })
+        );
+        assert_eq!(rules.validate(&[(0, 0), (5, 0)], &board), Err(ZoneError::OutOfBounds((5, 0))));
+        assert!(rules.validate(&[(0, 0), (4, 4)], &board).is_ok());
+        assert_eq!(ZoneRules::default().validate(&[], &board), Err(ZoneError::TooFewTiles { count: 0, min: 1 }));
+    }
+
+    /// Tests that scattered zones are rejected when contiguity is required
+    #[test]
+    fn test_validate_contiguous() {
+        let rules = ZoneRules::default();
+        let board = Board::default();
+        assert_eq!(rules.validate(&[(0, 0), (2, 0)], &board), Err(ZoneError::NotContiguous));
+        assert_eq!(rules.validate(&[(0, 0), (1, 1)], &board), Err(ZoneError::NotContiguous));
+        assert!(rules.validate(&[(0, 0), (1, 0), (1, 1)], &board).is_ok());
+    }
+
+    /// Tests that zones have to match an allowed template in any rotation
+    #[test]
+    fn test_validate_shapes() {
+        let rules = ZoneRules {
+            max_tiles: 5,
+            shapes: vec![ZoneShape::L, ZoneShape::Cross],
+            ..ZoneRules::default()
+        };
+        let board = Board::default();
+        let rotated_l = ZoneShape::L.place((4, 4), Rotation::ThreeQuarter).unwrap();
+        assert!(rules.validate(&rotated_l, &board).is_ok());
+        let cross = ZoneShape::Cross.place((4, 4), Rotation::None).unwrap();
+        assert!(rules.validate(&cross, &board).is_ok());
+        let square = ZoneShape::Square.place((4, 4), Rotation::None).unwrap();
+        assert_eq!(rules.validate(&square, &board), Err(ZoneError::ShapeNotAllowed));
+    }
+}
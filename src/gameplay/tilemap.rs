@@ -1,10 +1,18 @@
-/** Handles logic imminent to the tile map data structure
+/*! Handles logic imminent to the tile map data structure
 *
 *
 */
 
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Serialize, Deserialize};
+
+use crate::gameplay::round_config::RoundConfig;
+
 pub type Coord = (i8, i8);
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Direction {
     North,
     South,
@@ -12,37 +20,131 @@ pub enum Direction {
     West
 }
 
+pub const DIRECTIONS: [Direction; 4] = [Direction::North, Direction::East, Direction::South, Direction::West];
+
 pub trait CoordinateSystem {
     fn neighbors(&self) -> Vec<Coord>;
     fn neighbors_of(coord: &Coord) -> Vec<Coord>;
-    fn neighbor(&self, direction: Direction) -> Coord;
-    fn neighbor_of(coord: &Coord, direction: Direction) -> Coord;
+    fn neighbor(&self, direction: Direction) -> Option<Coord>;
+    fn neighbor_of(coord: &Coord, direction: Direction) -> Option<Coord>;
     fn coord(&self) -> Coord;
 }
 
-enum TileState {
-    TemporaryResistance(i8),  // Tile belongs to resistance until number of turns are over
+/// The playable area of a round, spanning `(0, 0)` to `(width - 1, height - 1)`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Board {
+    width: u8,
+    height: u8
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TileState {
+    TemporaryResistance(u32),  // Tile belongs to resistance until number of turns are over
+    HiddenResistance(u32),  // A temporary resistance tile the suppressor can't see yet
+    Resistance,
+    Suppressor
+}
+
+/// The kind of a `TileState`, ignoring any timer it carries.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TileKind {
+    TemporaryResistance,
+    HiddenResistance,
     Resistance,
     Suppressor
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Tile {
     state: TileState,
     coord: Coord
 }
 
+/// The occupied tiles of a board, keyed by coordinate. Unlisted coordinates are empty.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TileMap {
+    board: Board,
+    #[serde(with = "tiles_as_list")]
+    tiles: BTreeMap<Coord, Tile>
+}
+
+/// Stores the tiles as a list, since not every format can key a map by a `Coord`.
+mod tiles_as_list {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::gameplay::tilemap::{Coord, Tile};
+
+    pub fn serialize<S: Serializer>(tiles: &BTreeMap<Coord, Tile>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(tiles.values())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<Coord, Tile>, D::Error> {
+        let tiles: Vec<Tile> = Vec::deserialize(deserializer)?;
+        Ok(tiles.into_iter().map(|tile| (tile.coord, tile)).collect())
+    }
+}
+
+/// A tile was placed off the board.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct OutOfBounds(pub Coord);
+
+impl fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} is off the board", self.0)
+    }
+}
+
+impl std::error::Error for OutOfBounds {}
+
+impl TileState {
+    pub fn kind(&self) -> TileKind {
+        match self {
+            TileState::TemporaryResistance(_) => TileKind::TemporaryResistance,
+            TileState::HiddenResistance(_) => TileKind::HiddenResistance,
+            TileState::Resistance => TileKind::Resistance,
+            TileState::Suppressor => TileKind::Suppressor
+        }
+    }
+
+    /// The turns left before a temporary tile returns to normal.
+    pub fn timer(&self) -> Option<u32> {
+        match self {
+            TileState::TemporaryResistance(turns) | TileState::HiddenResistance(turns) => Some(*turns),
+            _ => None
+        }
+    }
+
+    /// Whether the tile belongs to the resistance, temporarily or not.
+    pub fn is_resistance(&self) -> bool {
+        !matches!(self, TileState::Suppressor)
+    }
+}
+
+impl Tile {
+    pub fn new(coord: Coord, state: TileState) -> Tile {
+        Tile { state, coord }
+    }
+
+    pub fn state(&self) -> TileState {
+        self.state
+    }
+}
+
 impl CoordinateSystem for Tile {
     /// Get all the neighbors in a clockwise ordering
     fn neighbors(&self) -> Vec<Coord> {
         Self::neighbors_of(&self.coord)
     }
 
-    /// Get all the neighbors in a clockwise ordering
+    /// Get all the neighbors in a clockwise ordering, skipping any that would overflow a `Coord`
     fn neighbors_of(coord: &Coord) -> Vec<Coord> {
-        let directions = [Direction::North, Direction::East, Direction::South, Direction::West];
         let mut neighbors: Vec<Coord>= Vec::new();
-        for direction in directions {
-            neighbors.push(Self::neighbor_of(coord, direction));
+        for direction in DIRECTIONS {
+            if let Some(neighbor) = Self::neighbor_of(coord, direction) {
+                neighbors.push(neighbor);
+            }
         }
         return neighbors
     }
@@ -51,17 +153,18 @@ impl CoordinateSystem for Tile {
     ///
     /// ## Arguments:
     /// * `direction` - the direction of the neighbor from this instances coordinate
-    fn neighbor(&self, direction: Direction) -> Coord {
+    fn neighbor(&self, direction: Direction) -> Option<Coord> {
         Self::neighbor_of(&self.coord, direction)
     }
 
-    fn neighbor_of(coord: &Coord, direction: Direction) -> Coord {
-        let (x, y) = coord.clone();
+    /// Get the neighbor for the given direction, or `None` if it would overflow a `Coord`
+    fn neighbor_of(coord: &Coord, direction: Direction) -> Option<Coord> {
+        let (x, y) = *coord;
         match direction {
-            Direction::North => (x, y + 1),
-            Direction::South => (x, y - 1),
-            Direction::East => (x + 1, y),
-            Direction::West => (x - 1, y)
+            Direction::North => Some((x, y.checked_add(1)?)),
+            Direction::South => Some((x, y.checked_sub(1)?)),
+            Direction::East => Some((x.checked_add(1)?, y)),
+            Direction::West => Some((x.checked_sub(1)?, y))
         }
     }
 
@@ -70,12 +173,177 @@ impl CoordinateSystem for Tile {
     }
 }
 
+impl Board {
+    /// Create a board of the given size
+    ///
+    /// Sides are clamped so every tile on the board fits in a `Coord`.
+    pub fn new(width: u8, height: u8) -> Board {
+        let max_side = i8::MAX as u8;
+        Board { width: width.min(max_side), height: height.min(max_side) }
+    }
+
+    /// Create the board described by a round config
+    pub fn from_config(config: &RoundConfig) -> Board {
+        Self::new(config.board_width, config.board_height)
+    }
+
+    pub fn width(&self) -> u8 {
+        self.width
+    }
+
+    pub fn height(&self) -> u8 {
+        self.height
+    }
+
+    /// The number of tiles on the board.
+    pub fn tile_count(&self) -> u32 {
+        self.width as u32 * self.height as u32
+    }
+
+    /// Whether the coordinate is on the board
+    pub fn contains(&self, coord: &Coord) -> bool {
+        let (x, y) = *coord;
+        x >= 0 && y >= 0 && (x as u8) < self.width && (y as u8) < self.height
+    }
+
+    /// Get the neighbor for the given direction, or `None` if it is off the board
+    ///
+    /// ## Arguments:
+    /// * `coord` - the coordinate to step from
+    /// * `direction` - the direction of the neighbor from `coord`
+    pub fn neighbor_of(&self, coord: &Coord, direction: Direction) -> Option<Coord> {
+        Tile::neighbor_of(coord, direction).filter(|neighbor| self.contains(neighbor))
+    }
+
+    /// Get all the neighbors on the board in a clockwise ordering
+    ///
+    /// Tiles on the edge of the board have fewer than four neighbors.
+    pub fn neighbors_of(&self, coord: &Coord) -> Vec<Coord> {
+        DIRECTIONS.iter().filter_map(|direction| self.neighbor_of(coord, *direction)).collect()
+    }
+
+    /// Whether the coordinate sits on the edge of the board
+    pub fn is_edge(&self, coord: &Coord) -> bool {
+        self.contains(coord) && self.neighbors_of(coord).len() < DIRECTIONS.len()
+    }
+
+    /// Iterate over every coordinate on the board, row by row
+    pub fn coords(&self) -> impl Iterator<Item = Coord> {
+        let (width, height) = (self.width as i8, self.height as i8);
+        (0..height).flat_map(move |y| (0..width).map(move |x| (x, y)))
+    }
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Board::from_config(&RoundConfig::default())
+    }
+}
+
+impl TileMap {
+    /// Create an empty tile map for the board
+    pub fn new(board: Board) -> TileMap {
+        TileMap { board, tiles: BTreeMap::new() }
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// The state of the tile at the coordinate, or `None` if it is empty
+    pub fn state_at(&self, coord: &Coord) -> Option<TileState> {
+        self.tiles.get(coord).map(|tile| tile.state)
+    }
+
+    /// Whether the coordinate holds a resistance tile of any kind
+    pub fn is_resistance(&self, coord: &Coord) -> bool {
+        self.state_at(coord).is_some_and(|state| state.is_resistance())
+    }
+
+    /// Put a tile on the board, returning the state it replaced
+    ///
+    /// ## Arguments:
+    /// * `coord` - where the tile goes, which has to be on the board
+    /// * `state` - the state of the new tile
+    pub fn set(&mut self, coord: Coord, state: TileState) -> Result<Option<TileState>, OutOfBounds> {
+        if !self.board.contains(&coord) {
+            return Err(OutOfBounds(coord));
+        }
+        Ok(self.tiles.insert(coord, Tile::new(coord, state)).map(|tile| tile.state))
+    }
+
+    /// Empty the tile at the coordinate, returning the state it had
+    pub fn remove(&mut self, coord: &Coord) -> Option<TileState> {
+        self.tiles.remove(coord).map(|tile| tile.state)
+    }
+
+    /// Iterate over the occupied tiles in coordinate order
+    pub fn iter(&self) -> impl Iterator<Item = &Tile> {
+        self.tiles.values()
+    }
+
+    /// Iterate over the tiles of the given kind in coordinate order
+    pub fn tiles_in_state(&self, kind: TileKind) -> impl Iterator<Item = &Tile> {
+        self.tiles.values().filter(move |tile| tile.state.kind() == kind)
+    }
+
+    /// The coordinates of the tiles of the given kind in coordinate order
+    pub fn coords_in_state(&self, kind: TileKind) -> Vec<Coord> {
+        self.tiles_in_state(kind).map(|tile| tile.coord).collect()
+    }
+
+    /// The number of tiles of the given kind
+    pub fn count(&self, kind: TileKind) -> usize {
+        self.tiles_in_state(kind).count()
+    }
+
+    /// The number of occupied tiles.
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    /// Count down the timer of every temporary tile, emptying those that run out
+    ///
+    /// Returns the coordinates of the tiles that were emptied.
+    pub fn tick_timers(&mut self) -> Vec<Coord> {
+        let mut expired: Vec<Coord> = Vec::new();
+        for tile in self.tiles.values_mut() {
+            match &mut tile.state {
+                TileState::TemporaryResistance(turns) | TileState::HiddenResistance(turns) => {
+                    *turns = turns.saturating_sub(1);
+                    if *turns == 0 {
+                        expired.push(tile.coord);
+                    }
+                },
+                _ => {}
+            }
+        }
+        for coord in &expired {
+            self.tiles.remove(coord);
+        }
+        expired
+    }
+}
+
+impl<'a> IntoIterator for &'a TileMap {
+    type Item = &'a Tile;
+    type IntoIter = std::collections::btree_map::Values<'a, Coord, Tile>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.tiles.values()
+    }
+}
+
 // -- TESTS -- //
 
 #[cfg(test)]
 mod tests {
     use std::iter::zip;
-    use crate::gameplay::tilemap::{TileState, Tile, Direction, CoordinateSystem, Coord};
+    use crate::gameplay::tilemap::{TileState, Tile, Direction, CoordinateSystem, Coord, Board, TileMap, TileKind, OutOfBounds};
 
     /// Tests whether the neighbor for the given direction returns expected result
     #[test]
@@ -85,10 +353,42 @@ mod tests {
         let east_neighbor = Tile::neighbor_of(&coord, Direction::East);
         let south_neighbor = Tile::neighbor_of(&coord, Direction::South);
         let west_neighbor = Tile::neighbor_of(&coord, Direction::West);
-        assert_eq!(north_neighbor, (0, 1));
-        assert_eq!(south_neighbor, (0, -1));
-        assert_eq!(east_neighbor, (1, 0));
-        assert_eq!(west_neighbor, (-1, 0));
+        assert_eq!(north_neighbor, Some((0, 1)));
+        assert_eq!(south_neighbor, Some((0, -1)));
+        assert_eq!(east_neighbor, Some((1, 0)));
+        assert_eq!(west_neighbor, Some((-1, 0)));
+    }
+
+    /// Tests that neighbors past the edge of the coordinate space don't wrap around
+    #[test]
+    fn validate_neighbor_overflow() {
+        assert_eq!(Tile::neighbor_of(&(i8::MAX, 0), Direction::East), None);
+        assert_eq!(Tile::neighbor_of(&(0, i8::MIN), Direction::South), None);
+        assert_eq!(Tile::neighbors_of(&(i8::MAX, i8::MAX)).len(), 2);
+    }
+
+    /// Tests that the board only reports neighbors within its bounds
+    #[test]
+    fn validate_board_neighbors() {
+        let board = Board::new(3, 3);
+        assert_eq!(board.neighbors_of(&(1, 1)).len(), 4);
+        assert_eq!(board.neighbors_of(&(0, 0)), vec![(0, 1), (1, 0)]);
+        assert_eq!(board.neighbors_of(&(2, 1)), vec![(2, 2), (2, 0), (1, 1)]);
+        assert_eq!(board.neighbor_of(&(2, 2), Direction::North), None);
+        assert!(board.is_edge(&(0, 1)));
+        assert!(!board.is_edge(&(1, 1)));
+    }
+
+    /// Tests the board bounds and iteration
+    #[test]
+    fn validate_board_bounds() {
+        let board = Board::new(4, 2);
+        assert!(board.contains(&(3, 1)));
+        assert!(!board.contains(&(4, 1)));
+        assert!(!board.contains(&(0, -1)));
+        assert_eq!(board.coords().count() as u32, board.tile_count());
+        assert!(board.coords().all(|coord| board.contains(&coord)));
+        assert_eq!(Board::new(255, 255).width(), i8::MAX as u8);
     }
 
     /// Tests that neighbors return in a clockwise ordering
@@ -100,7 +400,46 @@ mod tests {
             Direction::North, Direction::East, Direction::South, Direction::West
         ];
         for (neighbor, direction) in zip(neighbors, direction_order)  {
-            assert_eq!(neighbor, Tile::neighbor_of(&coord, direction))
+            assert_eq!(Some(neighbor), Tile::neighbor_of(&coord, direction))
         }
     }
-}
\ No newline at end of file
+
+    /// Tests placing, querying and removing tiles
+    #[test]
+    fn validate_tile_map_set_and_query() {
+        let mut tiles = TileMap::new(Board::new(3, 3));
+        assert_eq!(tiles.set((0, 0), TileState::Resistance), Ok(None));
+        assert_eq!(tiles.set((1, 0), TileState::TemporaryResistance(2)), Ok(None));
+        assert_eq!(tiles.set((3, 0), TileState::Resistance), Err(OutOfBounds((3, 0))));
+        assert_eq!(tiles.state_at(&(0, 0)), Some(TileState::Resistance));
+        assert_eq!(tiles.state_at(&(2, 2)), None);
+        assert_eq!(tiles.set((1, 0), TileState::Resistance), Ok(Some(TileState::TemporaryResistance(2))));
+        assert_eq!(tiles.coords_in_state(TileKind::Resistance), vec![(0, 0), (1, 0)]);
+        assert_eq!(tiles.remove(&(0, 0)), Some(TileState::Resistance));
+        assert_eq!(tiles.len(), 1);
+    }
+
+    /// Tests that only resistance tiles count as resistance
+    #[test]
+    fn validate_tile_map_is_resistance() {
+        let mut tiles = TileMap::new(Board::new(3, 3));
+        tiles.set((0, 0), TileState::HiddenResistance(1)).unwrap();
+        tiles.set((1, 0), TileState::Suppressor).unwrap();
+        assert!(tiles.is_resistance(&(0, 0)));
+        assert!(!tiles.is_resistance(&(1, 0)));
+        assert!(!tiles.is_resistance(&(2, 0)));
+    }
+
+    /// Tests that timers count down and expired tiles are emptied
+    #[test]
+    fn validate_tile_map_tick_timers() {
+        let mut tiles = TileMap::new(Board::new(3, 3));
+        tiles.set((0, 0), TileState::TemporaryResistance(1)).unwrap();
+        tiles.set((0, 1), TileState::HiddenResistance(2)).unwrap();
+        tiles.set((0, 2), TileState::Resistance).unwrap();
+        assert_eq!(tiles.tick_timers(), vec![(0, 0)]);
+        assert_eq!(tiles.state_at(&(0, 1)), Some(TileState::HiddenResistance(1)));
+        assert_eq!(tiles.tick_timers(), vec![(0, 1)]);
+        assert_eq!(tiles.iter().map(|tile| tile.state()).collect::<Vec<_>>(), vec![TileState::Resistance]);
+    }
+}
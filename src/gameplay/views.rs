@@ -0,0 +1,208 @@
+/*! What each side is allowed to know about a round
+*
+* `RoundState` is omniscient: it holds the resistance's hidden tiles and whatever either side has
+* buffered for the turn. Anything handed to a player - a network client or a bot - gets one of these
+* projections instead, so secrets never leave the round by way of the state itself.
+*/
+
+use serde::{Serialize, Deserialize};
+
+use crate::gameplay::actors::{ResistanceAction, SuppressionAction};
+use crate::gameplay::round_config::RoundConfig;
+use crate::gameplay::round_manager::{GameOutcome, RoundState};
+use crate::gameplay::rules::Rules;
+use crate::gameplay::tilemap::{Coord, CoordinateSystem, TileKind, TileMap};
+
+/** The round as the resistance sees it
+
+The resistance knows where all of its tiles are, hidden or not, but only learns the suppressor's zone
+once the turn is resolved.
+
+*Fields*
+- `config` (RoundConfig): the rules the round is played under
+- `current_turn` (u32): the number of turns resolved so far
+- `tiles` (TileMap): every tile on the board, hidden ones included
+- `sieges` (Vec<(Coord, u32)>): the perm tiles under siege and the turns they've been besieged
+- `buffered_action` (Option<ResistanceAction>): the resistance's action for this turn, if already submitted
+- `opponent_ready` (bool): whether the suppressor has submitted its action for this turn
+- `history` (Vec<(SuppressionAction, ResistanceAction)>): every resolved pair of actions
+- `outcome` (Option<GameOutcome>): how the round ended, if it has
+- `rules` (Rules): the rule set the round is played under, which isn't serialized
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ResistanceView {
+    pub config: RoundConfig,
+    pub current_turn: u32,
+    pub tiles: TileMap,
+    pub sieges: Vec<(Coord, u32)>,
+    pub buffered_action: Option<ResistanceAction>,
+    pub opponent_ready: bool,
+    pub history: Vec<(SuppressionAction, ResistanceAction)>,
+    pub outcome: Option<GameOutcome>,
+    #[serde(skip)]
+    pub rules: Rules
+}
+
+/** The round as the suppressor sees it
+
+Hidden tiles are left off the board until they are revealed, and the private half of every
+resistance action is left out of the history.
+
+*Fields*
+- `config` (RoundConfig): the rules the round is played under
+- `current_turn` (u32): the number of turns resolved so far
+- `tiles` (TileMap): every tile on the board the suppressor can see
+- `sieges` (Vec<(Coord, u32)>): the perm tiles under siege and the turns they've been besieged
+- `buffered_action` (Option<SuppressionAction>): the suppressor's action for this turn, if already submitted
+- `opponent_ready` (bool): whether the resistance has submitted its action for this turn
+- `history` (Vec<(SuppressionAction, Coord)>): every resolved zone with the public coordinate played against it
+- `outcome` (Option<GameOutcome>): how the round ended, if it has
+- `rules` (Rules): the rule set the round is played under, which isn't serialized
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SuppressionView {
+    pub config: RoundConfig,
+    pub current_turn: u32,
+    pub tiles: TileMap,
+    pub sieges: Vec<(Coord, u32)>,
+    pub buffered_action: Option<SuppressionAction>,
+    pub opponent_ready: bool,
+    pub history: Vec<(SuppressionAction, Coord)>,
+    pub outcome: Option<GameOutcome>,
+    #[serde(skip)]
+    pub rules: Rules
+}
+
+/// The view of either side, for code that serves both.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) enum PlayerView {
+    Resistance(ResistanceView),
+    Suppression(SuppressionView)
+}
+
+impl ResistanceView {
+    /// The round as far as the resistance can see it, for searching ahead from
+    pub(crate) fn position(&self) -> RoundState {
+        RoundState::from_position(self.config.clone(), self.rules.clone(), self.tiles.clone(), self.sieges.clone(), self.current_turn)
+    }
+}
+
+impl SuppressionView {
+    /// The round as far as the suppressor can see it, for searching ahead from
+    ///
+    /// Hidden tiles aren't in the view, so the position has none.
+    pub(crate) fn position(&self) -> RoundState {
+        RoundState::from_position(self.config.clone(), self.rules.clone(), self.tiles.clone(), self.sieges.clone(), self.current_turn)
+    }
+}
+
+impl RoundState {
+    /// What the resistance is allowed to know about the round
+    pub(crate) fn resistance_view(&self) -> ResistanceView {
+        let (suppression_buffer, resistance_buffer) = self.turn_buffer();
+        ResistanceView {
+            config: self.config().clone(),
+            current_turn: self.current_turn(),
+            tiles: self.tiles().clone(),
+            sieges: self.sieges().to_vec(),
+            buffered_action: *resistance_buffer,
+            opponent_ready: suppression_buffer.is_some(),
+            history: self.turn_history().to_vec(),
+            outcome: self.outcome(),
+            rules: self.rules().clone()
+        }
+    }
+
+    /// What the suppressor is allowed to know about the round
+    pub(crate) fn suppression_view(&self) -> SuppressionView {
+        let (suppression_buffer, resistance_buffer) = self.turn_buffer();
+        let mut tiles = TileMap::new(*self.board());
+        for tile in self.tiles().iter().filter(|tile| tile.state().kind() != TileKind::HiddenResistance) {
+            // Every tile in the round is on its board
+            let _ = tiles.set(tile.coord(), tile.state());
+        }
+        SuppressionView {
+            config: self.config().clone(),
+            current_turn: self.current_turn(),
+            tiles,
+            sieges: self.sieges().to_vec(),
+            buffered_action: suppression_buffer.clone(),
+            opponent_ready: resistance_buffer.is_some(),
+            history: self.turn_history().iter()
+                .map(|(suppression, resistance)| (suppression.clone(), resistance.public_coord))
+                .collect(),
+            outcome: self.outcome(),
+            rules: self.rules().clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gameplay::actors::{ResistanceAction, SuppressionAction};
+    use crate::gameplay::round_manager::RoundState;
+    use crate::gameplay::rules::Rules;
+    use crate::gameplay::tilemap::TileState;
+    use crate::scripting::ScriptLimits;
+    use crate::scripting::rules::ScriptedRuleSet;
+
+    /// Plays a turn placing a public tile at (0, 0) and a hidden one at (4, 4)
+    fn round_with_hidden_tile() -> RoundState {
+        let mut state = RoundState::default();
+        state.intake_suppression_action(SuppressionAction { suppression_zone: vec![(8, 8)] }).unwrap();
+        state.intake_resistance_action(ResistanceAction { public_coord: (0, 0), private_coord: (4, 4) }).unwrap();
+        state
+    }
+
+    /// Tests that the suppressor can't see hidden tiles or private coordinates
+    #[test]
+    fn test_suppression_view_redacts_hidden_tiles() {
+        let state = round_with_hidden_tile();
+        let view = state.suppression_view();
+        assert_eq!(view.tiles.state_at(&(0, 0)), Some(TileState::TemporaryResistance(2)));
+        assert_eq!(view.tiles.state_at(&(4, 4)), None);
+        assert_eq!(view.history, vec![(SuppressionAction { suppression_zone: vec![(8, 8)] }, (0, 0))]);
+        // Nothing in what would be sent to the suppressor gives the hidden tile away
+        assert!(!serde_json::to_string(&view).unwrap().contains("[4,4]"));
+
+        let view = state.resistance_view();
+        assert_eq!(view.tiles.state_at(&(4, 4)), Some(TileState::HiddenResistance(2)));
+        assert_eq!(view.history[0].1.private_coord, (4, 4));
+    }
+
+    /// Tests that neither side sees what the other has buffered for the turn
+    #[test]
+    fn test_views_redact_opponent_buffer() {
+        let mut state = round_with_hidden_tile();
+        let resistance = ResistanceAction { public_coord: (1, 1), private_coord: (6, 6) };
+        state.intake_resistance_action(resistance).unwrap();
+
+        let view = state.suppression_view();
+        assert!(view.opponent_ready);
+        assert_eq!(view.buffered_action, None);
+        assert!(!serde_json::to_string(&view).unwrap().contains("[6,6]"));
+
+        let view = state.resistance_view();
+        assert!(!view.opponent_ready);
+        assert_eq!(view.buffered_action, Some(resistance));
+
+    }
+
+    /// Tests that a position searched from a view keeps the round's rule set and sieges
+    #[test]
+    fn test_position_keeps_rules_and_sieges() {
+        let rules = ScriptedRuleSet::from_source("variant.rhai", "", &ScriptLimits::default()).unwrap();
+        let mut state = RoundState::with_rules(Default::default(), Rules::new(rules));
+        state.tiles_mut().set((2, 2), TileState::Resistance).unwrap();
+        state.set_sieges(vec![((2, 2), 1)]);
+
+        for position in [state.resistance_view().position(), state.suppression_view().position()] {
+            assert_eq!(position.rules().name(), "variant.rhai");
+            assert_eq!(position.sieges(), &[((2, 2), 1)]);
+            assert_eq!(position.current_turn(), state.current_turn());
+        }
+        // The rule set stays with the round rather than going out with the view
+        let json = serde_json::to_string(&state.suppression_view()).unwrap();
+        assert!(!json.contains("variant.rhai"));
+    }
+}
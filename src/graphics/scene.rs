@@ -1,4 +1,4 @@
-/** A graphics scene
+/*! A graphics scene
 *
 */
 
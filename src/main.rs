@@ -1,6 +1,9 @@
+pub mod ai;
 pub mod gameplay;
 pub mod networking;
 pub mod graphics;
+pub mod scripting;
+pub mod tournament;
 
 use std::env;
 use std::path::Path;
@@ -8,10 +11,19 @@ use std::path::Path;
 fn main() -> Result<(), String> {
     let args: Vec<_> = env::args().collect();
 
-    if args.len() < 2 {
-        println!("Usage: cargo run /path/to/image.(png|jpg)")
-    } else {
-        graphics::main_loop::run(Path::new(&args[1]))?;
+    match args.get(1).map(String::as_str) {
+        None => {
+            println!("Usage: cargo run /path/to/image.(png|jpg)");
+            println!("       cargo run tournament [--games N] [--threads N] [--seed N] [--best-of N] [--json] [--players a,b] [--rules rules.rhai] [--external NAME=COMMAND] [--time-limit SECONDS] [bot.rhai...]");
+            println!("       cargo run sweep [--games N] [--threads N] [--seed N] [--sample N] [--resistance BOT] [--suppression BOT] [--max-turns a,b] [--temp-turns a,b] [--score a,b] [--zone a,b] [--config base.toml] [--rules rules.rhai] [--top N] [--csv PATH] [--json PATH]");
+            println!("       cargo run replay [--rules rules.rhai] game.json");
+            println!("       cargo run solve [--config base.toml] [--rules rules.rhai] [--iterations N] [--max-states N] [--top N]");
+        },
+        Some("tournament") => tournament::run_cli(&args[2..])?,
+        Some("sweep") => tournament::sweep::run_cli(&args[2..])?,
+        Some("solve") => ai::solver::run_cli(&args[2..])?,
+        Some("replay") => gameplay::replay::run_cli(&args[2..])?,
+        Some(image) => graphics::main_loop::run(Path::new(image))?
     }
 
     Ok(())
@@ -1,4 +1,4 @@
-/** Networking logic for a proxy-server client-host setup */
+/*! Networking logic for a proxy-server client-host setup */
 
 use std::net::{TcpStream, TcpListener};
 use std::time::{Duration};
@@ -25,7 +25,7 @@ struct NetworkConfig {
     nodelay: bool
 }
 
-/**
+/*
 TODO: Use cfg_attr to break up possible network config overrides using local files for non-web builds
     and URLS for web builds
 */
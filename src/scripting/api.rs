@@ -0,0 +1,271 @@
+/*! The game as scripts see it
+*
+* Registers the game's types with a rhai `Engine`. Everything a script is handed is a copy, so a
+* script can look at the round as much as it likes but can only change it by what it returns.
+*
+* - `Coord`: made with `coord(x, y)`, read with `.x` and `.y`
+* - `Round`: the round so far, with `.turn`, `.max_turns`, `.temp_turn_count`, `.score`,
+*   `.score_to_win`, `.width`, `.height`, `.capture_rule`, `.zone_min` and `.zone_max`, and the
+*   queries `tile_at(c)`, `timer_at(c)`, `on_board(c)`, `is_edge(c)`, `neighbors(c)`, `tiles(kind)`,
+*   `count(kind)` and `valid_zone(coords)`. Tile kinds are the strings `"empty"`, `"temporary"`,
+*   `"hidden"`, `"resistance"` and `"suppressor"`
+* - `ResistanceView` and `SuppressionView`: what a bot's side can see of the round, with everything
+*   a `Round` has plus `.opponent_ready` and `.past_zones`, and then `.past_actions` for the
+*   resistance or `.past_public` for the suppressor
+* - `ResistanceAction`: `.public_coord` and `.private_coord`, made with `resistance_action(public, private)`
+* - `SuppressionAction`: `.zone` and `covers(c)`, made with `suppression_action(zone)`
+* - `RoundResult`: made with `gains_point(c)`, `gains_temporary(c)`, `private_gained(c)`,
+*   `private_revealed(c)`, `encloses_region(coords)` and `reclaims(c)`
+* - `GameOutcome`: made with `resistance_wins(score, turn)`, `suppression_wins(score)`,
+*   `suppression_reclaims(reclaimed, turn)` and `draw()`
+* - `classic_results(round, resistance, suppression)` and `classic_outcome(round)` run the classic
+*   rules, so a variant can start from them and change what it needs to
+*/
+
+use rhai::{Array, Dynamic, Engine, EvalAltResult, INT};
+
+use crate::gameplay::actors::{ResistanceAction, SuppressionAction};
+use crate::gameplay::round_config::{CaptureRule, RoundConfig};
+use crate::gameplay::round_manager::{DrawReason, GameOutcome, RoundResult, RoundState};
+use crate::gameplay::rules::{Classic, RuleSet};
+use crate::gameplay::tilemap::{Coord, TileKind, TileMap};
+use crate::gameplay::views::{ResistanceView, SuppressionView};
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// Register everything scripts can use with the engine
+pub(crate) fn register(engine: &mut Engine) {
+    register_coord(engine);
+    register_board::<RoundState>(engine, "Round");
+    register_views(engine);
+    register_actions(engine);
+    register_results(engine);
+    register_outcomes(engine);
+}
+
+/// Convert a number from a script, failing if it doesn't fit
+fn number<T: TryFrom<INT>>(value: INT, what: &str) -> ScriptResult<T> {
+    T::try_from(value).map_err(|_| format!("{} {} is out of range", what, value).into())
+}
+
+/// Convert an array of coordinates from a script
+fn coords(values: Array, what: &str) -> ScriptResult<Vec<Coord>> {
+    values.into_iter()
+        .map(|value| value.try_cast::<Coord>().ok_or_else(|| format!("{} can only hold coordinates", what).into()))
+        .collect()
+}
+
+/// Convert coordinates into an array for a script
+fn array(coords: impl IntoIterator<Item = Coord>) -> Array {
+    coords.into_iter().map(Dynamic::from).collect()
+}
+
+/// The name scripts use for the kind of a tile, or for no tile
+fn kind_name(kind: Option<TileKind>) -> &'static str {
+    match kind {
+        None => "empty",
+        Some(TileKind::TemporaryResistance) => "temporary",
+        Some(TileKind::HiddenResistance) => "hidden",
+        Some(TileKind::Resistance) => "resistance",
+        Some(TileKind::Suppressor) => "suppressor"
+    }
+}
+
+/// The kind of tile a script named, `None` being empty
+fn parse_kind(name: &str) -> ScriptResult<Option<TileKind>> {
+    match name {
+        "empty" => Ok(None),
+        "temporary" => Ok(Some(TileKind::TemporaryResistance)),
+        "hidden" => Ok(Some(TileKind::HiddenResistance)),
+        "resistance" => Ok(Some(TileKind::Resistance)),
+        "suppressor" => Ok(Some(TileKind::Suppressor)),
+        _ => Err(format!(
+            "unknown tile kind '{}', expected empty, temporary, hidden, resistance or suppressor", name
+        ).into())
+    }
+}
+
+fn register_coord(engine: &mut Engine) {
+    engine.register_type_with_name::<Coord>("Coord")
+        .register_fn("coord", |x: INT, y: INT| -> ScriptResult<Coord> {
+            Ok((number(x, "x coordinate")?, number(y, "y coordinate")?))
+        })
+        .register_get("x", |coord: &mut Coord| coord.0 as INT)
+        .register_get("y", |coord: &mut Coord| coord.1 as INT)
+        .register_fn("==", |a: Coord, b: Coord| a == b)
+        .register_fn("!=", |a: Coord, b: Coord| a != b)
+        .register_fn("to_string", |coord: &mut Coord| format!("({}, {})", coord.0, coord.1))
+        .register_fn("to_debug", |coord: &mut Coord| format!("({}, {})", coord.0, coord.1));
+}
+
+/// What every script-facing picture of the round can answer.
+trait BoardQueries {
+    fn round_config(&self) -> &RoundConfig;
+    fn tile_map(&self) -> &TileMap;
+    fn turn(&self) -> u32;
+}
+
+impl BoardQueries for RoundState {
+    fn round_config(&self) -> &RoundConfig {
+        self.config()
+    }
+
+    fn tile_map(&self) -> &TileMap {
+        self.tiles()
+    }
+
+    fn turn(&self) -> u32 {
+        self.current_turn()
+    }
+}
+
+impl BoardQueries for ResistanceView {
+    fn round_config(&self) -> &RoundConfig {
+        &self.config
+    }
+
+    fn tile_map(&self) -> &TileMap {
+        &self.tiles
+    }
+
+    fn turn(&self) -> u32 {
+        self.current_turn
+    }
+}
+
+impl BoardQueries for SuppressionView {
+    fn round_config(&self) -> &RoundConfig {
+        &self.config
+    }
+
+    fn tile_map(&self) -> &TileMap {
+        &self.tiles
+    }
+
+    fn turn(&self) -> u32 {
+        self.current_turn
+    }
+}
+
+/// Register a picture of the round with the queries scripts share across all of them
+fn register_board<T: BoardQueries + Clone + Send + Sync + 'static>(engine: &mut Engine, name: &str) {
+    engine.register_type_with_name::<T>(name)
+        .register_get("turn", |round: &mut T| round.turn() as INT)
+        .register_get("max_turns", |round: &mut T| round.round_config().max_turns as INT)
+        .register_get("temp_turn_count", |round: &mut T| round.round_config().temp_turn_count as INT)
+        .register_get("score", |round: &mut T| round.tile_map().count(TileKind::Resistance) as INT)
+        .register_get("score_to_win", |round: &mut T| round.round_config().score_to_win as INT)
+        .register_get("width", |round: &mut T| round.tile_map().board().width() as INT)
+        .register_get("height", |round: &mut T| round.tile_map().board().height() as INT)
+        .register_get("capture_rule", |round: &mut T| match round.round_config().capture_rule {
+            CaptureRule::FourNeighbor => "four_neighbor".to_string(),
+            CaptureRule::Enclosure => "enclosure".to_string()
+        })
+        .register_get("zone_min", |round: &mut T| round.round_config().zone.min_tiles.max(1) as INT)
+        .register_get("zone_max", |round: &mut T| round.round_config().zone.max_tiles as INT)
+        .register_fn("valid_zone", |round: &mut T, zone: Array| -> ScriptResult<bool> {
+            let zone = coords(zone, "a suppression zone")?;
+            Ok(round.round_config().zone.validate(&zone, round.tile_map().board()).is_ok())
+        })
+        .register_fn("tile_at", |round: &mut T, coord: Coord| {
+            kind_name(round.tile_map().state_at(&coord).map(|state| state.kind())).to_string()
+        })
+        .register_fn("timer_at", |round: &mut T, coord: Coord| {
+            round.tile_map().state_at(&coord).and_then(|state| state.timer()).unwrap_or(0) as INT
+        })
+        .register_fn("on_board", |round: &mut T, coord: Coord| round.tile_map().board().contains(&coord))
+        .register_fn("is_edge", |round: &mut T, coord: Coord| round.tile_map().board().is_edge(&coord))
+        .register_fn("neighbors", |round: &mut T, coord: Coord| array(round.tile_map().board().neighbors_of(&coord)))
+        .register_fn("tiles", |round: &mut T, kind: &str| -> ScriptResult<Array> {
+            let tiles = round.tile_map();
+            Ok(match parse_kind(kind)? {
+                Some(kind) => array(tiles.coords_in_state(kind)),
+                None => array(tiles.board().coords().filter(|coord| tiles.state_at(coord).is_none()))
+            })
+        })
+        .register_fn("count", |round: &mut T, kind: &str| -> ScriptResult<INT> {
+            let tiles = round.tile_map();
+            Ok(match parse_kind(kind)? {
+                Some(kind) => tiles.count(kind) as INT,
+                None => (tiles.board().tile_count() as usize - tiles.len()) as INT
+            })
+        });
+}
+
+fn register_views(engine: &mut Engine) {
+    register_board::<ResistanceView>(engine, "ResistanceView");
+    engine
+        .register_get("opponent_ready", |view: &mut ResistanceView| view.opponent_ready)
+        .register_get("past_zones", |view: &mut ResistanceView| -> Array {
+            view.history.iter().map(|(zone, _)| Dynamic::from(zone.clone())).collect()
+        })
+        .register_get("past_actions", |view: &mut ResistanceView| -> Array {
+            view.history.iter().map(|(_, action)| Dynamic::from(*action)).collect()
+        });
+
+    register_board::<SuppressionView>(engine, "SuppressionView");
+    engine
+        .register_get("opponent_ready", |view: &mut SuppressionView| view.opponent_ready)
+        .register_get("past_zones", |view: &mut SuppressionView| -> Array {
+            view.history.iter().map(|(zone, _)| Dynamic::from(zone.clone())).collect()
+        })
+        .register_get("past_public", |view: &mut SuppressionView| array(view.history.iter().map(|(_, coord)| *coord)));
+}
+
+fn register_actions(engine: &mut Engine) {
+    engine.register_type_with_name::<ResistanceAction>("ResistanceAction")
+        .register_fn("resistance_action", |public_coord: Coord, private_coord: Coord| {
+            ResistanceAction { public_coord, private_coord }
+        })
+        .register_get("public_coord", |action: &mut ResistanceAction| action.public_coord)
+        .register_get("private_coord", |action: &mut ResistanceAction| action.private_coord)
+        .register_fn("to_debug", |action: &mut ResistanceAction| format!("{:?}", action));
+
+    engine.register_type_with_name::<SuppressionAction>("SuppressionAction")
+        .register_fn("suppression_action", |zone: Array| -> ScriptResult<SuppressionAction> {
+            Ok(SuppressionAction { suppression_zone: coords(zone, "a suppression zone")? })
+        })
+        .register_get("zone", |action: &mut SuppressionAction| array(action.suppression_zone.iter().cloned()))
+        .register_fn("covers", |action: &mut SuppressionAction, coord: Coord| action.suppression_zone.contains(&coord))
+        .register_fn("to_debug", |action: &mut SuppressionAction| format!("{:?}", action));
+}
+
+fn register_results(engine: &mut Engine) {
+    engine.register_type_with_name::<RoundResult>("RoundResult")
+        .register_fn("gains_point", RoundResult::ResistanceGainsPoint)
+        .register_fn("gains_temporary", RoundResult::ResistanceGainsTemporary)
+        .register_fn("private_gained", RoundResult::PrivateTileGained)
+        .register_fn("private_revealed", RoundResult::PrivateTileRevealed)
+        .register_fn("encloses_region", |region: Array| -> ScriptResult<RoundResult> {
+            Ok(RoundResult::ResistanceEnclosesRegion(coords(region, "a region")?))
+        })
+        .register_fn("reclaims", RoundResult::SuppressorReclaims)
+        .register_fn("==", |a: RoundResult, b: RoundResult| a == b)
+        .register_fn("!=", |a: RoundResult, b: RoundResult| a != b)
+        .register_fn("to_debug", |result: &mut RoundResult| format!("{:?}", result))
+        .register_fn("classic_results", |round: RoundState, resistance: ResistanceAction, suppression: SuppressionAction| -> ScriptResult<Array> {
+            let results = Classic.round_results(&round, &resistance, &suppression).map_err(|error| error.to_string())?;
+            Ok(results.into_iter().map(Dynamic::from).collect())
+        });
+}
+
+fn register_outcomes(engine: &mut Engine) {
+    engine.register_type_with_name::<GameOutcome>("GameOutcome")
+        .register_fn("resistance_wins", |score: INT, turn: INT| -> ScriptResult<GameOutcome> {
+            Ok(GameOutcome::ResistanceWins { score: number(score, "score")?, turn: number(turn, "turn")? })
+        })
+        .register_fn("suppression_wins", |score: INT| -> ScriptResult<GameOutcome> {
+            Ok(GameOutcome::SuppressionWins { score: number(score, "score")? })
+        })
+        .register_fn("suppression_reclaims", |reclaimed: INT, turn: INT| -> ScriptResult<GameOutcome> {
+            Ok(GameOutcome::SuppressionReclaims { reclaimed: number(reclaimed, "reclaimed")?, turn: number(turn, "turn")? })
+        })
+        .register_fn("draw", || GameOutcome::Draw(DrawReason::DeclaredByRules))
+        .register_fn("==", |a: GameOutcome, b: GameOutcome| a == b)
+        .register_fn("!=", |a: GameOutcome, b: GameOutcome| a != b)
+        .register_fn("to_debug", |outcome: &mut GameOutcome| format!("{:?}", outcome))
+        .register_fn("classic_outcome", |round: RoundState| -> ScriptResult<Dynamic> {
+            let outcome = Classic.check_outcome(&round).map_err(|error| error.to_string())?;
+            Ok(outcome.map_or(Dynamic::UNIT, Dynamic::from))
+        });
+}
@@ -0,0 +1,236 @@
+/*! Bots written as rhai scripts
+*
+* A bot script defines the turn functions for the sides it can play:
+*
+* - `play_resistance(view)`, handed a `ResistanceView` and returning a `ResistanceAction`
+* - `play_suppression(view)`, handed a `SuppressionView` and returning a `SuppressionAction`, or just
+*   the array of coordinates to cover
+*
+* Bots only ever see their own side's view of the round. Each turn is a single call into the script
+* and gets the `ScriptLimits`' whole operation budget, so a bot that loops forever loses its turn
+* instead of hanging the game. Whatever the script returns is validated before it reaches the round.
+*/
+
+use std::fmt;
+use std::path::Path;
+
+use rhai::{Array, Dynamic};
+
+use crate::ai::{Player, PlayerError};
+use crate::gameplay::actors::{ResistanceAction, Side, SuppressionAction};
+use crate::gameplay::tilemap::Coord;
+use crate::gameplay::views::{ResistanceView, SuppressionView};
+use crate::scripting::{Script, ScriptError, ScriptLimits};
+
+const RESISTANCE_TURN: &str = "play_resistance";
+const SUPPRESSION_TURN: &str = "play_suppression";
+
+/// The ways a scripted bot can fail to take its turn.
+#[derive(Debug)]
+pub enum BotError {
+    /// The script has no turn function for the side.
+    CantPlay { bot: String, side: Side },
+    /// The script failed or returned something that isn't an action.
+    Script(ScriptError)
+}
+
+impl fmt::Display for BotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BotError::CantPlay { bot, side } => write!(f, "bot {} can't play the {:?} side", bot, side),
+            BotError::Script(error) => write!(f, "{}", error)
+        }
+    }
+}
+
+impl std::error::Error for BotError {}
+
+impl From<ScriptError> for BotError {
+    fn from(error: ScriptError) -> Self {
+        BotError::Script(error)
+    }
+}
+
+/// A bot whose turns are chosen by a script.
+#[derive(Debug)]
+pub(crate) struct ScriptedBot {
+    name: String,
+    script: Script
+}
+
+impl ScriptedBot {
+    /// Compile a bot from the source of a script
+    ///
+    /// ## Arguments:
+    /// * `name` - the name the bot is known by, and the script in errors
+    /// * `source` - the script itself
+    /// * `limits` - the limits each turn runs under
+    pub fn from_source(name: &str, source: &str, limits: &ScriptLimits) -> Result<ScriptedBot, ScriptError> {
+        let script = Script::compile(name, source, limits)?;
+        Ok(ScriptedBot { name: name.to_string(), script })
+    }
+
+    /// Load and compile a bot from a `.rhai` file, named after the file
+    pub fn load(path: &Path, limits: &ScriptLimits) -> Result<ScriptedBot, ScriptError> {
+        let script = Script::load(path, limits)?;
+        let name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        Ok(ScriptedBot { name, script })
+    }
+
+    /// Whether the script has a turn function for the side
+    pub fn plays(&self, side: Side) -> bool {
+        self.script.defines(match side {
+            Side::Resistance => RESISTANCE_TURN,
+            Side::Suppression => SUPPRESSION_TURN
+        })
+    }
+
+    /// Ask the script for the resistance's action
+    ///
+    /// The action is not checked against the round, `ai::take_turn` does that before intake.
+    pub fn resistance_action(&self, view: &ResistanceView) -> Result<ResistanceAction, BotError> {
+        if !self.plays(Side::Resistance) {
+            return Err(BotError::CantPlay { bot: self.name.clone(), side: Side::Resistance });
+        }
+        let returned = self.script.call(RESISTANCE_TURN, (view.clone(),))?;
+        returned.try_cast::<ResistanceAction>()
+            .ok_or_else(|| self.script.bad_return(RESISTANCE_TURN, "something other than a resistance action").into())
+    }
+
+    /// Ask the script for the suppressor's action
+    ///
+    /// The action is not checked against the round, `ai::take_turn` does that before intake.
+    pub fn suppression_action(&self, view: &SuppressionView) -> Result<SuppressionAction, BotError> {
+        if !self.plays(Side::Suppression) {
+            return Err(BotError::CantPlay { bot: self.name.clone(), side: Side::Suppression });
+        }
+        let returned = self.script.call(SUPPRESSION_TURN, (view.clone(),))?;
+        if returned.is::<SuppressionAction>() {
+            return Ok(returned.cast::<SuppressionAction>());
+        }
+        returned.try_cast::<Array>()
+            .and_then(|zone| zone.into_iter().map(Dynamic::try_cast::<Coord>).collect::<Option<Vec<Coord>>>())
+            .map(|suppression_zone| SuppressionAction { suppression_zone })
+            .ok_or_else(|| self.script.bad_return(SUPPRESSION_TURN, "something other than a suppression zone").into())
+    }
+
+}
+
+impl From<BotError> for PlayerError {
+    fn from(error: BotError) -> Self {
+        match error {
+            BotError::CantPlay { bot, side } => PlayerError::CantPlay { player: bot, side },
+            BotError::Script(error) => PlayerError::Failed { player: error_script(&error), message: error.to_string() }
+        }
+    }
+}
+
+/// The script an error came from
+fn error_script(error: &ScriptError) -> String {
+    match error {
+        ScriptError::Io { script, .. }
+        | ScriptError::Compile { script, .. }
+        | ScriptError::Runtime { script, .. }
+        | ScriptError::BadReturn { script, .. } => script.clone()
+    }
+}
+
+impl Player for ScriptedBot {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn plays(&self, side: Side) -> bool {
+        ScriptedBot::plays(self, side)
+    }
+
+    fn resistance_action(&mut self, view: &ResistanceView) -> Result<ResistanceAction, PlayerError> {
+        Ok(ScriptedBot::resistance_action(self, view)?)
+    }
+
+    fn suppression_action(&mut self, view: &SuppressionView) -> Result<SuppressionAction, PlayerError> {
+        Ok(ScriptedBot::suppression_action(self, view)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::ai::{play_round, take_turn, PlayedRound, PlayerError};
+    use crate::ai::baseline::GreedyPlayer;
+    use crate::gameplay::actors::{ResistanceAction, Side, SuppressionAction};
+    use crate::gameplay::round_config::RoundConfig;
+    use crate::gameplay::round_manager::{ActionError, RoundState};
+    use crate::gameplay::suppression_zone::ZoneError;
+    use crate::scripting::ScriptLimits;
+    use crate::scripting::bots::ScriptedBot;
+
+    fn bot(source: &str) -> ScriptedBot {
+        ScriptedBot::from_source("bot.rhai", source, &ScriptLimits::default()).unwrap()
+    }
+
+    fn sample(name: &str) -> ScriptedBot {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("scripts/bots").join(name);
+        ScriptedBot::load(&path, &ScriptLimits::default()).unwrap()
+    }
+
+    /// Tests that the sample bots can play a whole round against each other
+    #[test]
+    fn test_sample_bots_play_a_round() {
+        let mut resistance = sample("creeper.rhai");
+        let mut suppression = sample("shadow.rhai");
+        assert!(resistance.plays(Side::Resistance) && !resistance.plays(Side::Suppression));
+        assert!(suppression.plays(Side::Suppression) && !suppression.plays(Side::Resistance));
+
+        let mut state = RoundState::default();
+        while !state.is_over() {
+            take_turn(&mut suppression, &mut state, Side::Suppression).unwrap();
+            take_turn(&mut resistance, &mut state, Side::Resistance).unwrap();
+        }
+        assert!(state.current_turn() > 0);
+    }
+
+    /// Tests that a scripted bot can sit in for any other player
+    #[test]
+    fn test_scripted_bot_is_a_player() {
+        let mut state = RoundState::default();
+        let played = play_round(&mut state, &mut sample("creeper.rhai"), &mut GreedyPlayer);
+        assert!(matches!(played, PlayedRound::Finished(_)));
+    }
+
+    /// Tests that a bot's action is validated before it reaches the round
+    #[test]
+    fn test_invalid_actions_are_rejected() {
+        let mut bot = bot("fn play_suppression(view) { [coord(0, 0), coord(5, 5)] }");
+        let mut state = RoundState::default();
+        let error = take_turn(&mut bot, &mut state, Side::Suppression).unwrap_err();
+        assert!(matches!(error, PlayerError::Rejected { error: ActionError::InvalidZone(ZoneError::NotContiguous), .. }));
+        assert_eq!(state.turn_buffer().0, None);
+
+        assert!(matches!(take_turn(&mut bot, &mut state, Side::Resistance), Err(PlayerError::CantPlay { .. })));
+    }
+
+    /// Tests that a runaway bot loses its turn instead of hanging the game
+    #[test]
+    fn test_runaway_bot_is_stopped() {
+        let limits = ScriptLimits { max_operations: 1_000, ..ScriptLimits::default() };
+        let mut bot = ScriptedBot::from_source("bot.rhai", "fn play_suppression(view) { let n = 0; while true { n += 1; } }", &limits).unwrap();
+        let mut state = RoundState::default();
+        let error = take_turn(&mut bot, &mut state, Side::Suppression).unwrap_err();
+        assert!(matches!(error, PlayerError::Failed { .. }));
+        assert!(error.to_string().contains("ran out of operations"), "{}", error);
+    }
+
+    /// Tests that bots only see their own side's view of the round
+    #[test]
+    fn test_bot_sees_its_view() {
+        let bot = bot("fn play_suppression(view) { [coord(view.count(\"hidden\"), view.past_public.len())] }");
+        let mut state = RoundState::new(RoundConfig::default());
+        state.intake_suppression_action(SuppressionAction { suppression_zone: vec![(8, 8)] }).unwrap();
+        state.intake_resistance_action(ResistanceAction { public_coord: (0, 0), private_coord: (4, 4) }).unwrap();
+        let action = bot.suppression_action(&state.suppression_view()).unwrap();
+        // The hidden tile at (4, 4) can't be seen, but the public tile played at (0, 0) can
+        assert_eq!(action.suppression_zone, vec![(0, 1)]);
+    }
+}
@@ -0,0 +1,180 @@
+/*! Rhai scripting for game variants
+*
+* Scripts run in a sandboxed `Engine`: every call is capped by the `ScriptLimits`, modules can't be
+* imported so there is no way to reach the filesystem, and `eval` is turned off. Whatever goes wrong
+* in a script comes back as a `ScriptError` naming the script, the function and where in the source
+* it happened.
+*/
+
+pub mod api;
+pub mod bots;
+pub mod rules;
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, FuncArgs, Position, Scope, AST};
+
+/** Limits on how much work a script can do
+
+*Fields*
+- `max_operations` (u64): the most operations a single call into the script can run
+- `max_call_levels` (usize): the deepest the script's functions can call each other
+- `max_string_size` (usize): the longest string the script can build
+- `max_array_size` (usize): the largest array the script can build
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptLimits {
+    pub max_operations: u64,
+    pub max_call_levels: usize,
+    pub max_string_size: usize,
+    pub max_array_size: usize
+}
+
+impl Default for ScriptLimits {
+    /// Enough for anything that looks at every tile of the largest board a few times over.
+    fn default() -> Self {
+        Self {
+            max_operations: 500_000,
+            max_call_levels: 32,
+            max_string_size: 4096,
+            max_array_size: 1 << 16
+        }
+    }
+}
+
+/// Everything that can go wrong loading or running a script.
+#[derive(Debug)]
+pub enum ScriptError {
+    /// The script file couldn't be read.
+    Io { script: String, error: std::io::Error },
+    /// The script doesn't parse.
+    Compile { script: String, line: Option<usize>, column: Option<usize>, message: String },
+    /// The script failed while running one of its functions, including running past its limits.
+    Runtime { script: String, function: String, line: Option<usize>, column: Option<usize>, message: String },
+    /// A function returned something the engine can't use.
+    BadReturn { script: String, function: String, message: String }
+}
+
+/// Format a position in a script as `:line:column`, leaving out what isn't known
+fn location(line: &Option<usize>, column: &Option<usize>) -> String {
+    match (line, column) {
+        (Some(line), Some(column)) => format!(":{}:{}", line, column),
+        (Some(line), None) => format!(":{}", line),
+        _ => String::new()
+    }
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::Io { script, error } => write!(f, "couldn't read script {}: {}", script, error),
+            ScriptError::Compile { script, line, column, message } => write!(
+                f, "{}{}: syntax error: {}", script, location(line, column), message
+            ),
+            ScriptError::Runtime { script, function, line, column, message } => write!(
+                f, "{}{}: error in {}(): {}", script, location(line, column), function, message
+            ),
+            ScriptError::BadReturn { script, function, message } => write!(
+                f, "{}: {}() returned {}", script, function, message
+            )
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// Split a rhai position into a line and column
+fn line_and_column(position: Position) -> (Option<usize>, Option<usize>) {
+    (position.line(), position.position())
+}
+
+/// Build an engine with the game's API registered and the sandbox in place
+pub(crate) fn sandboxed_engine(limits: &ScriptLimits) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(limits.max_operations);
+    engine.set_max_call_levels(limits.max_call_levels);
+    engine.set_max_string_size(limits.max_string_size);
+    engine.set_max_array_size(limits.max_array_size);
+    engine.set_max_map_size(limits.max_array_size);
+    engine.set_module_resolver(DummyModuleResolver::new());
+    engine.disable_symbol("eval");
+    engine.on_print(|_| {});
+    engine.on_debug(|_, _, _| {});
+    api::register(&mut engine);
+    engine
+}
+
+/// A compiled script and the engine it runs in.
+pub(crate) struct Script {
+    name: String,
+    engine: Engine,
+    ast: AST
+}
+
+impl Script {
+    /// Compile a script in a fresh sandboxed engine
+    ///
+    /// ## Arguments:
+    /// * `name` - what the script is called in errors, usually its file name
+    /// * `source` - the script itself
+    /// * `limits` - the limits every call into the script runs under
+    pub fn compile(name: &str, source: &str, limits: &ScriptLimits) -> Result<Script, ScriptError> {
+        let engine = sandboxed_engine(limits);
+        let ast = engine.compile(source).map_err(|error| {
+            let (line, column) = line_and_column(error.position());
+            ScriptError::Compile { script: name.to_string(), line, column, message: error.err_type().to_string() }
+        })?;
+        Ok(Script { name: name.to_string(), engine, ast })
+    }
+
+    /// Read and compile a script file, named after the file
+    pub fn load(path: &Path, limits: &ScriptLimits) -> Result<Script, ScriptError> {
+        let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        let source = fs::read_to_string(path).map_err(|error| ScriptError::Io { script: name.clone(), error })?;
+        Self::compile(&name, &source, limits)
+    }
+
+    /// What the script is called in errors.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether the script defines a function with this name
+    pub fn defines(&self, function: &str) -> bool {
+        self.ast.iter_functions().any(|metadata| metadata.name == function)
+    }
+
+    /// Call one of the script's functions
+    ///
+    /// Top-level statements in the script aren't run, only the function itself, and each call gets
+    /// the whole operation budget to itself.
+    pub fn call(&self, function: &str, args: impl FuncArgs) -> Result<Dynamic, ScriptError> {
+        let options = CallFnOptions::new().eval_ast(false).rewind_scope(true);
+        self.engine.call_fn_with_options::<Dynamic>(options, &mut Scope::new(), &self.ast, function, args)
+            .map_err(|error| self.runtime_error(function, *error))
+    }
+
+    /// Describe a failure in one of the script's functions
+    fn runtime_error(&self, function: &str, error: EvalAltResult) -> ScriptError {
+        let (line, column) = line_and_column(error.position());
+        let message = match &error {
+            EvalAltResult::ErrorTooManyOperations(_) => "ran out of operations, is there an endless loop?".to_string(),
+            _ => error.unwrap_inner().to_string()
+        };
+        ScriptError::Runtime { script: self.name.clone(), function: function.to_string(), line, column, message }
+    }
+
+    /// Describe a return value the engine can't use
+    pub fn bad_return(&self, function: &str, message: impl Into<String>) -> ScriptError {
+        ScriptError::BadReturn { script: self.name.clone(), function: function.to_string(), message: message.into() }
+    }
+}
+
+impl fmt::Debug for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Script").field("name", &self.name).finish()
+    }
+}
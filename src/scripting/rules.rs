@@ -0,0 +1,267 @@
+/*! Rule sets written as rhai scripts
+*
+* A script can define either or both of
+*
+* - `round_results(round, resistance, suppression)`, returning an array of `RoundResult`s
+* - `check_outcome(round)`, returning a `GameOutcome`, or nothing while the round goes on
+*
+* and every hook it leaves out, along with every hook scripts can't define, plays by the classic
+* rules. The script is compiled once when it is loaded, and a script that fails while the round is
+* being played calls the turn off with a `RuleError` instead of taking the game down.
+*/
+
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use rhai::{Array, Dynamic};
+
+use crate::gameplay::actors::{ResistanceAction, SuppressionAction};
+use crate::gameplay::round_manager::{GameOutcome, RoundResult, RoundState};
+use crate::gameplay::rules::{Classic, RuleError, RuleSet};
+use crate::gameplay::tilemap::{Coord, TileKind};
+use crate::scripting::{Script, ScriptError, ScriptLimits};
+
+/// A rule set whose results and win check come from a script.
+#[derive(Debug)]
+pub(crate) struct ScriptedRuleSet {
+    name: String,
+    script: Script
+}
+
+impl ScriptedRuleSet {
+    /// Compile a rule set from the source of a script
+    ///
+    /// ## Arguments:
+    /// * `name` - the name the rule set is known by, and the script in errors
+    /// * `source` - the script itself
+    /// * `limits` - the limits every hook runs under
+    pub fn from_source(name: &str, source: &str, limits: &ScriptLimits) -> Result<ScriptedRuleSet, ScriptError> {
+        let script = Script::compile(name, source, limits)?;
+        Ok(ScriptedRuleSet { name: name.to_string(), script })
+    }
+
+    /// Load and compile a rule set from a `.rhai` file, named after the file
+    pub fn load(path: &Path, limits: &ScriptLimits) -> Result<ScriptedRuleSet, ScriptError> {
+        let script = Script::load(path, limits)?;
+        let name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        Ok(ScriptedRuleSet { name, script })
+    }
+
+    /// Turn a script error into the error the round reports
+    fn rule_error(&self, error: ScriptError) -> RuleError {
+        RuleError { rule_set: self.name.clone(), message: error.to_string() }
+    }
+}
+
+impl RuleSet for ScriptedRuleSet {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn round_results(&self, state: &RoundState, resistance: &ResistanceAction, suppression: &SuppressionAction) -> Result<Vec<RoundResult>, RuleError> {
+        const HOOK: &str = "round_results";
+        if !self.script.defines(HOOK) {
+            return Classic.round_results(state, resistance, suppression);
+        }
+        let returned = self.script.call(HOOK, (state.clone(), *resistance, suppression.clone()))
+            .map_err(|error| self.rule_error(error))?;
+        let results = returned.try_cast::<Array>()
+            .ok_or_else(|| self.script.bad_return(HOOK, "something other than an array"))
+            .and_then(|array| {
+                array.into_iter()
+                    .map(|result| result.try_cast::<RoundResult>()
+                        .ok_or_else(|| self.script.bad_return(HOOK, "an array holding something other than results")))
+                    .collect::<Result<Vec<RoundResult>, ScriptError>>()
+            })
+            .and_then(|results| match check_results(state, &results) {
+                Ok(()) => Ok(results),
+                Err(message) => Err(self.script.bad_return(HOOK, message))
+            })
+            .map_err(|error| self.rule_error(error))?;
+        Ok(results)
+    }
+
+    fn check_outcome(&self, state: &RoundState) -> Result<Option<GameOutcome>, RuleError> {
+        const HOOK: &str = "check_outcome";
+        if !self.script.defines(HOOK) {
+            return Classic.check_outcome(state);
+        }
+        let returned: Dynamic = self.script.call(HOOK, (state.clone(),)).map_err(|error| self.rule_error(error))?;
+        if returned.is_unit() {
+            return Ok(None);
+        }
+        returned.try_cast::<GameOutcome>()
+            .map(Some)
+            .ok_or_else(|| self.rule_error(self.script.bad_return(HOOK, "something other than an outcome")))
+    }
+}
+
+/// Check that results from a script can be applied to the round
+///
+/// Every coordinate has to be on the board, and a point can only be gained on a temp tile, whether
+/// it was already on the board or gained earlier in the same results.
+fn check_results(state: &RoundState, results: &[RoundResult]) -> Result<(), String> {
+    let mut temps: BTreeSet<Coord> = state.tiles().coords_in_state(TileKind::TemporaryResistance).into_iter()
+        .chain(state.tiles().coords_in_state(TileKind::HiddenResistance))
+        .collect();
+    for result in results {
+        let coords: Vec<Coord> = match result {
+            RoundResult::ResistanceEnclosesRegion(region) => region.clone(),
+            RoundResult::ResistanceGainsPoint(coord)
+            | RoundResult::ResistanceGainsTemporary(coord)
+            | RoundResult::PrivateTileGained(coord)
+            | RoundResult::PrivateTileRevealed(coord)
+            | RoundResult::SuppressorReclaims(coord) => vec![*coord]
+        };
+        if let Some(coord) = coords.iter().find(|coord| !state.board().contains(coord)) {
+            return Err(format!("{:?}, which is off the board at {:?}", result, coord));
+        }
+        match result {
+            RoundResult::ResistanceGainsTemporary(coord) | RoundResult::PrivateTileGained(coord) => {
+                temps.insert(*coord);
+            },
+            RoundResult::ResistanceGainsPoint(coord) if !temps.remove(coord) => {
+                return Err(format!("{:?}, but there is no temp tile there to make permanent", result));
+            },
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::gameplay::actors::{ResistanceAction, SuppressionAction};
+    use crate::gameplay::round_config::RoundConfig;
+    use crate::gameplay::round_manager::{ActionError, GameOutcome, RoundState};
+    use crate::gameplay::rules::{RuleError, RuleSet, Rules};
+    use crate::gameplay::tilemap::{TileKind, TileState};
+    use crate::scripting::{ScriptError, ScriptLimits};
+    use crate::scripting::rules::ScriptedRuleSet;
+
+    fn rules(source: &str) -> Result<ScriptedRuleSet, ScriptError> {
+        ScriptedRuleSet::from_source("variant.rhai", source, &ScriptLimits::default())
+    }
+
+    /// Intake a zone of (8, 8), returning what happens to a resistance action of (0, 0) and (1, 1)
+    fn play_turn(state: &mut RoundState) -> Result<(), ActionError> {
+        state.intake_suppression_action(SuppressionAction { suppression_zone: vec![(8, 8)] })?;
+        state.intake_resistance_action(ResistanceAction { public_coord: (0, 0), private_coord: (1, 1) })?;
+        Ok(())
+    }
+
+    /// Tests that both hooks can be scripted
+    #[test]
+    fn test_scripted_variant() {
+        let variant = rules(r#"
+            // Public tiles outside the zone are captured straight away
+            fn round_results(round, resistance, suppression) {
+                let placed = resistance.public_coord;
+                if suppression.covers(placed) || round.tile_at(placed) != "empty" {
+                    return [];
+                }
+                [gains_temporary(placed), gains_point(placed)]
+            }
+
+            fn check_outcome(round) {
+                if round.score >= 1 {
+                    return resistance_wins(round.score, round.turn);
+                }
+            }
+        "#).unwrap();
+        let mut state = RoundState::with_rules(RoundConfig::default(), Rules::new(variant));
+        play_turn(&mut state).unwrap();
+        assert_eq!(state.tiles().state_at(&(0, 0)), Some(TileState::Resistance));
+        // The private tile went nowhere, the script didn't place it
+        assert_eq!(state.tiles().state_at(&(1, 1)), None);
+        assert_eq!(state.outcome(), Some(GameOutcome::ResistanceWins { score: 1, turn: 1 }));
+    }
+
+    /// Tests that the hooks a script leaves out play by the classic rules
+    #[test]
+    fn test_missing_hooks_are_classic() {
+        let variant = rules("fn check_outcome(round) { if round.count(\"temporary\") > 0 { draw() } }").unwrap();
+        let mut state = RoundState::with_rules(RoundConfig::default(), Rules::new(variant));
+        play_turn(&mut state).unwrap();
+        // Both tiles were placed by the classic rules, the hidden one revealed as the round ended
+        assert_eq!(state.tiles().count(TileKind::TemporaryResistance), 2);
+        assert!(matches!(state.outcome(), Some(GameOutcome::Draw(_))));
+    }
+
+    /// Tests that a script running past its operation limit calls the turn off
+    #[test]
+    fn test_endless_script_is_stopped() {
+        let variant = rules("fn check_outcome(round) { loop { } }").unwrap();
+        let mut state = RoundState::with_rules(RoundConfig::default(), Rules::new(variant));
+        let before = {
+            state.intake_suppression_action(SuppressionAction { suppression_zone: vec![(8, 8)] }).unwrap();
+            state.clone()
+        };
+        let result = state.intake_resistance_action(ResistanceAction { public_coord: (0, 0), private_coord: (1, 1) });
+        match result {
+            Err(ActionError::RuleFailed(RuleError { rule_set, message })) => {
+                assert_eq!(rule_set, "variant.rhai");
+                assert!(message.contains("ran out of operations"), "{}", message);
+            },
+            other => panic!("expected the rules to fail, got {:?}", other)
+        }
+        assert_eq!(state, before);
+    }
+
+    /// Tests that scripts can't evaluate code or import modules
+    #[test]
+    fn test_scripts_are_sandboxed() {
+        assert!(matches!(rules("fn check_outcome(round) { eval(\"1\") }"), Err(ScriptError::Compile { .. })));
+
+        let variant = rules("fn check_outcome(round) { import \"rules\" as rules; }").unwrap();
+        let error = variant.check_outcome(&RoundState::default()).unwrap_err();
+        assert!(error.message.contains("error in check_outcome()"), "{}", error.message);
+    }
+
+    /// Tests that errors say where in the script they happened
+    #[test]
+    fn test_script_errors_are_located() {
+        let error = rules("fn round_results(round, r, s) {\n    let x = ;\n}").unwrap_err();
+        assert!(error.to_string().starts_with("variant.rhai:2:13: syntax error"), "{}", error);
+
+        let variant = rules("fn check_outcome(round) {\n    round.tile_at(coord(0, 0)) + round.tiles(\"walls\")\n}").unwrap();
+        let error = variant.check_outcome(&RoundState::default()).unwrap_err();
+        assert!(error.message.contains("unknown tile kind 'walls'"), "{}", error.message);
+    }
+
+    /// Tests that results the round can't apply are refused
+    #[test]
+    fn test_bad_results_are_refused() {
+        let resistance = ResistanceAction { public_coord: (0, 0), private_coord: (1, 1) };
+        let suppression = SuppressionAction { suppression_zone: vec![(8, 8)] };
+        let state = RoundState::default();
+        for source in [
+            "fn round_results(round, r, s) { 5 }",
+            "fn round_results(round, r, s) { [5] }",
+            "fn round_results(round, r, s) { [gains_point(r.public_coord)] }",
+            "fn round_results(round, r, s) { [gains_temporary(coord(20, 0))] }"
+        ] {
+            let error = rules(source).unwrap().round_results(&state, &resistance, &suppression).unwrap_err();
+            assert!(error.message.contains("round_results() returned"), "{}", error.message);
+        }
+    }
+
+    /// Tests that the sample variant loads and plays
+    #[test]
+    fn test_sample_variant() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("scripts/rules/landslide.rhai");
+        let variant = ScriptedRuleSet::load(&path, &ScriptLimits::default()).unwrap();
+        assert_eq!(variant.name(), "landslide");
+
+        let mut state = RoundState::with_rules(RoundConfig::default(), Rules::new(variant));
+        state.tiles_mut().set((3, 4), TileState::Resistance).unwrap();
+        state.tiles_mut().set((5, 4), TileState::Resistance).unwrap();
+        state.intake_suppression_action(SuppressionAction { suppression_zone: vec![(8, 8)] }).unwrap();
+        state.intake_resistance_action(ResistanceAction { public_coord: (4, 4), private_coord: (0, 0) }).unwrap();
+        // Placed against two perm tiles, the centre is captured on the spot and wins the round
+        assert_eq!(state.tiles().state_at(&(4, 4)), Some(TileState::Resistance));
+        assert_eq!(state.outcome(), Some(GameOutcome::ResistanceWins { score: 3, turn: 1 }));
+    }
+}
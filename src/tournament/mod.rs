@@ -0,0 +1,893 @@
+/*! Headless tournaments between players
+*
+* A tournament plays every registered player against every other one on both sides: each pairing is
+* played `games` times with the first player as the resistance and as many times with the roles
+* swapped. Games are spread over a pool of threads, each game with fresh players made by the
+* factories they were registered with, and drive `RoundState` directly so nothing needs a window.
+*
+* Given `best_of`, each pairing instead plays `games` matches, the players taking turns in the first
+* seat, and every round of a match is rated as a game of its own. Single games stay the default, as a
+* match has both players play both sides and plenty of bots only play one of them.
+*
+* Given an `archive` directory, every game's replay is saved there and named in its `GameRecord`, so
+* any game of the tournament can be played back afterwards.
+*
+* Ratings are worked out once every game is in, in schedule order, so a tournament run with the same
+* seed and players always gives the same report however the threads raced.
+*
+* `sweep` uses the same machinery to play a pair of players over a range of round configs instead.
+*/
+
+pub mod ratings;
+pub mod sweep;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use serde::{Serialize, Deserialize};
+
+use crate::ai::{play_match, play_round, PlayedMatch, PlayedRound, Player, PlayerError};
+use crate::ai::baseline::{GreedyPlayer, HeuristicPlayer, RandomPlayer};
+use crate::ai::external::ExternalPlayer;
+use crate::ai::mcts::{MctsConfig, MctsPlayer};
+use crate::gameplay::actors::Side;
+use crate::gameplay::match_manager::{MatchConfig, MatchState, Seat};
+use crate::gameplay::round_config::RoundConfig;
+use crate::gameplay::round_manager::RoundState;
+use crate::gameplay::rules::{RuleError, Rules};
+use crate::scripting::ScriptLimits;
+use crate::scripting::bots::{BotError, ScriptedBot};
+use crate::scripting::rules::ScriptedRuleSet;
+use crate::tournament::ratings::Rating;
+
+/// A played game, with the indices of its resistance and suppression players.
+type ScheduledGame = ((usize, usize), GameRecord);
+
+/// Makes a fresh player for a game from the game's seed.
+pub(crate) type PlayerFactory = Box<dyn Fn(u64) -> Result<Box<dyn Player>, PlayerError> + Send + Sync>;
+
+/** How a tournament is run
+
+*Fields*
+- `round` (RoundConfig): the rules every game is played under
+- `games` (u32): the games each pair of players plays with each of them as the resistance
+- `threads` (usize): the number of games played at once
+- `seed` (u64): where the seeds handed to the players start from
+- `k` (f32): the most a rating can move in one game
+- `initial_rating` (f32): the rating every player starts on
+- `time_limit` (Duration): how long an external player has to answer each turn before forfeiting
+- `best_of` (Option<u32>): play each pairing as matches of this many rounds rather than single games
+- `archive` (Option<PathBuf>): a directory to save a replay of every game to
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct TournamentConfig {
+    pub round: RoundConfig,
+    pub games: u32,
+    pub threads: usize,
+    pub seed: u64,
+    pub k: f32,
+    pub initial_rating: f32,
+    pub time_limit: Duration,
+    pub best_of: Option<u32>,
+    pub archive: Option<PathBuf>
+}
+
+impl Default for TournamentConfig {
+    fn default() -> Self {
+        Self {
+            round: RoundConfig::default(),
+            games: 10,
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+            seed: 0,
+            k: 32.0,
+            initial_rating: 1500.0,
+            time_limit: Duration::from_secs(10),
+            best_of: None,
+            archive: None
+        }
+    }
+}
+
+/// The ways a tournament can fail to run.
+#[derive(Debug)]
+pub enum TournamentError {
+    /// Two players were registered under the same name.
+    DuplicateName(String),
+    /// A player couldn't be made, when it was registered or for a game.
+    Unavailable(PlayerError),
+    /// There aren't enough players for a game.
+    TooFewPlayers(usize),
+    InvalidConfig(String),
+    /// The rule set failed during a game, so the game couldn't be scored.
+    RulesFailed(RuleError),
+    /// A player that only plays one side was entered into a tournament of matches.
+    OneSided(String),
+    /// A game's replay couldn't be saved to the archive.
+    Archive(String)
+}
+
+impl fmt::Display for TournamentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TournamentError::DuplicateName(name) => write!(f, "a player named {} is already registered", name),
+            TournamentError::Unavailable(error) => write!(f, "couldn't make a player: {}", error),
+            TournamentError::TooFewPlayers(count) => write!(f, "a tournament needs at least two players, not {}", count),
+            TournamentError::InvalidConfig(error) => write!(f, "invalid round config: {}", error),
+            TournamentError::RulesFailed(error) => write!(f, "a game was called off: {}", error),
+            TournamentError::OneSided(name) => write!(f, "{} only plays one side, so can't play a match", name),
+            TournamentError::Archive(error) => write!(f, "couldn't archive a game: {}", error)
+        }
+    }
+}
+
+impl std::error::Error for TournamentError {}
+
+/** One game of a tournament
+
+*Fields*
+- `resistance` (String): the player on the resistance
+- `suppression` (String): the player on the suppression
+- `winner` (Option<Side>): the side that won, `None` for a draw
+- `forfeit` (Option<String>): why the loser failed to take its turn, if the game was forfeited
+- `turns` (u32): the turns the game lasted
+- `score` (u32): the perm tiles the resistance finished with
+- `replay` (Option<String>): the file the game's replay was archived to, if the tournament kept one
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameRecord {
+    pub resistance: String,
+    pub suppression: String,
+    pub winner: Option<Side>,
+    pub forfeit: Option<String>,
+    pub turns: u32,
+    pub score: u32,
+    pub replay: Option<String>
+}
+
+/** How a pair of players did against each other with one on each side
+
+*Fields*
+- `resistance` (String): the player on the resistance
+- `suppression` (String): the player on the suppression
+- `resistance_wins` (u32): games the resistance won
+- `draws` (u32): games drawn
+- `suppression_wins` (u32): games the suppression won
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Matchup {
+    pub resistance: String,
+    pub suppression: String,
+    pub resistance_wins: u32,
+    pub draws: u32,
+    pub suppression_wins: u32
+}
+
+impl Matchup {
+    /// The number of games played.
+    pub fn games(&self) -> u32 {
+        self.resistance_wins + self.draws + self.suppression_wins
+    }
+
+    /// The share of the games the resistance won.
+    pub fn resistance_win_rate(&self) -> f32 {
+        match self.games() {
+            0 => 0.0,
+            games => self.resistance_wins as f32 / games as f32
+        }
+    }
+}
+
+/** Everything a tournament found out
+
+*Fields*
+- `rule_set` (String): the name of the rule set every game was played under
+- `ratings` (Vec<Rating>): every player, highest rated first
+- `matchups` (Vec<Matchup>): every pairing of players on each side
+- `games` (Vec<GameRecord>): every game, in schedule order
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TournamentReport {
+    pub rule_set: String,
+    pub ratings: Vec<Rating>,
+    pub matchups: Vec<Matchup>,
+    pub games: Vec<GameRecord>
+}
+
+impl fmt::Display for TournamentReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "played under the {} rules", self.rule_set)?;
+        writeln!(f)?;
+        writeln!(f, "{:<20} {:>8} {:>6} {:>6} {:>6} {:>6} {:>7}", "player", "rating", "games", "wins", "draws", "losses", "score")?;
+        for rating in &self.ratings {
+            writeln!(
+                f, "{:<20} {:>8.1} {:>6} {:>6} {:>6} {:>6} {:>6.1}%",
+                rating.name, rating.rating, rating.games(), rating.wins, rating.draws, rating.losses, rating.score_rate() * 100.0
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(f, "{:<20} {:<20} {:>6} {:>6} {:>6} {:>6} {:>7}", "resistance", "suppression", "games", "wins", "draws", "losses", "win")?;
+        for matchup in &self.matchups {
+            writeln!(
+                f, "{:<20} {:<20} {:>6} {:>6} {:>6} {:>6} {:>6.1}%",
+                matchup.resistance, matchup.suppression, matchup.games(), matchup.resistance_wins, matchup.draws,
+                matchup.suppression_wins, matchup.resistance_win_rate() * 100.0
+            )?;
+        }
+        let forfeits: Vec<&GameRecord> = self.games.iter().filter(|game| game.forfeit.is_some()).collect();
+        if !forfeits.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "forfeits")?;
+            for game in forfeits {
+                writeln!(f, "{} v {}: {}", game.resistance, game.suppression, game.forfeit.as_deref().unwrap_or_default())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A registered player.
+struct Entrant {
+    name: String,
+    factory: PlayerFactory,
+    resistance: bool,
+    suppression: bool
+}
+
+/// A set of players to pit against each other.
+pub(crate) struct Tournament {
+    config: TournamentConfig,
+    rules: Rules,
+    entrants: Vec<Entrant>
+}
+
+impl Tournament {
+    /// A tournament played under the classic rules
+    pub fn new(config: TournamentConfig) -> Tournament {
+        Tournament::with_rules(config, Rules::default())
+    }
+
+    /// A tournament played under a variant rule set
+    pub fn with_rules(config: TournamentConfig, rules: Rules) -> Tournament {
+        Tournament { config, rules, entrants: Vec::new() }
+    }
+
+    /// Add a player to the tournament
+    ///
+    /// The factory is called once up front to check the player can be made and to find out which
+    /// sides it plays, and then again for every game it's in.
+    pub fn register<F>(&mut self, name: &str, factory: F) -> Result<(), TournamentError>
+    where F: Fn(u64) -> Result<Box<dyn Player>, PlayerError> + Send + Sync + 'static {
+        if self.entrants.iter().any(|entrant| entrant.name == name) {
+            return Err(TournamentError::DuplicateName(name.to_string()));
+        }
+        let player = factory(self.config.seed).map_err(TournamentError::Unavailable)?;
+        self.entrants.push(Entrant {
+            name: name.to_string(),
+            resistance: player.plays(Side::Resistance),
+            suppression: player.plays(Side::Suppression),
+            factory: Box::new(factory)
+        });
+        Ok(())
+    }
+
+    /// Add one of the built-in players: "random", "greedy", "heuristic" or "mcts"
+    ///
+    /// Returns false for any other name. The search player is given a small budget, as a
+    /// tournament plays a lot of games.
+    pub fn register_builtin(&mut self, name: &str) -> Result<bool, TournamentError> {
+        match builtin_player(name) {
+            Some(factory) => self.register(name, factory).map(|_| true),
+            None => Ok(false)
+        }
+    }
+
+    /// Add a rhai bot, registered under its file's name
+    ///
+    /// The script is compiled again for every game, so a bot never carries anything over from one
+    /// game to the next.
+    pub fn register_script(&mut self, path: &Path, limits: &ScriptLimits) -> Result<(), TournamentError> {
+        let (name, factory) = script_player(path, limits).map_err(TournamentError::Unavailable)?;
+        self.register(&name, factory)
+    }
+
+    /// Add a program speaking the bot protocol, started afresh for every game
+    pub fn register_external(&mut self, name: &str, program: &str, args: &[String]) -> Result<(), TournamentError> {
+        let (owned_name, program, args) = (name.to_string(), program.to_string(), args.to_vec());
+        let time_limit = self.config.time_limit;
+        self.register(name, move |_| {
+            ExternalPlayer::spawn(&owned_name, Command::new(&program).args(&args), time_limit)
+                .map(|player| Box::new(player) as Box<dyn Player>)
+        })
+    }
+
+    /// Every game to be played, as the indices of the resistance and suppression players
+    fn schedule(&self) -> Vec<(usize, usize)> {
+        let mut games = Vec::new();
+        for first in 0..self.entrants.len() {
+            for second in first + 1..self.entrants.len() {
+                for _ in 0..self.config.games {
+                    for (resistance, suppression) in [(first, second), (second, first)] {
+                        if self.entrants[resistance].resistance && self.entrants[suppression].suppression {
+                            games.push((resistance, suppression));
+                        }
+                    }
+                }
+            }
+        }
+        games
+    }
+
+    /// Every match to be played, as the indices of the players in the first and second seats
+    ///
+    /// The players take turns in the first seat, so neither plays the resistance more often.
+    fn match_schedule(&self) -> Vec<(usize, usize)> {
+        let mut matches = Vec::new();
+        for first in 0..self.entrants.len() {
+            for second in first + 1..self.entrants.len() {
+                for game in 0..self.config.games {
+                    matches.push(if game % 2 == 0 { (first, second) } else { (second, first) });
+                }
+            }
+        }
+        matches
+    }
+
+    /// Play every game and rate the players on the results
+    ///
+    /// A game the rule set fails in can't be scored, so it stops the tournament, and so does a
+    /// player that can't be made for a game.
+    pub fn run(&self) -> Result<TournamentReport, TournamentError> {
+        if self.entrants.len() < 2 {
+            return Err(TournamentError::TooFewPlayers(self.entrants.len()));
+        }
+        self.config.round.validate().map_err(|error| TournamentError::InvalidConfig(error.to_string()))?;
+        if let Some(archive) = &self.config.archive {
+            fs::create_dir_all(archive).map_err(|error| TournamentError::Archive(error.to_string()))?;
+        }
+
+        let games = match self.config.best_of {
+            None => {
+                let schedule = self.schedule();
+                let games = parallel_map(schedule.len(), self.config.threads, |index| {
+                    let (resistance, suppression) = schedule[index];
+                    self.play_game(index, resistance, suppression).map(|game| ((resistance, suppression), game))
+                });
+                games.into_iter().collect::<Result<Vec<_>, TournamentError>>()?
+            },
+            Some(best_of) => {
+                if let Some(entrant) = self.entrants.iter().find(|entrant| !(entrant.resistance && entrant.suppression)) {
+                    return Err(TournamentError::OneSided(entrant.name.clone()));
+                }
+                let schedule = self.match_schedule();
+                let matches = parallel_map(schedule.len(), self.config.threads, |index| {
+                    let (first, second) = schedule[index];
+                    self.play_match(index, first, second, best_of)
+                });
+                let matches = matches.into_iter().collect::<Result<Vec<_>, TournamentError>>()?;
+                matches.into_iter().flatten().collect()
+            }
+        };
+        Ok(self.report(games))
+    }
+
+    /// The seed handed to the players of a scheduled game or match
+    fn seed(&self, index: usize) -> u64 {
+        self.config.seed.wrapping_add(2 * index as u64)
+    }
+
+    /// Play one game between fresh players
+    fn play_game(&self, index: usize, resistance: usize, suppression: usize) -> Result<GameRecord, TournamentError> {
+        let seed = self.seed(index);
+        let mut state = RoundState::with_rules(self.config.round.clone(), self.rules.clone());
+        if self.config.archive.is_some() {
+            state.attach_recorder(&self.entrants[resistance].name, &self.entrants[suppression].name, seed);
+        }
+        let played = play_game(&mut state, &self.entrants[resistance].factory, &self.entrants[suppression].factory, seed)
+            .map_err(TournamentError::Unavailable)?;
+        let mut game = self.record(resistance, suppression, played, &state)?;
+        game.replay = self.archive(&mut state, &format!("game-{}.json", index))?;
+        Ok(game)
+    }
+
+    /// Play one match between fresh players, recording each of its rounds as a game
+    ///
+    /// The second seat is made from the seed after the first's. The round a player forfeits is the
+    /// last one recorded, as forfeiting it loses the match.
+    fn play_match(&self, index: usize, first: usize, second: usize, best_of: u32) -> Result<Vec<ScheduledGame>, TournamentError> {
+        let seed = self.seed(index);
+        let config = MatchConfig { round: self.config.round.clone(), best_of };
+        let mut state = MatchState::with_rules(config, self.rules.clone())
+            .map_err(|error| TournamentError::InvalidConfig(error.to_string()))?;
+        let mut first_player = (self.entrants[first].factory)(seed).map_err(TournamentError::Unavailable)?;
+        let mut second_player = (self.entrants[second].factory)(seed.wrapping_add(1)).map_err(TournamentError::Unavailable)?;
+        let mut forfeit = match play_match(&mut state, first_player.as_mut(), second_player.as_mut()) {
+            PlayedMatch::Aborted(error) => return Err(TournamentError::RulesFailed(error)),
+            PlayedMatch::Forfeited { seat, error } => Some((seat, error)),
+            PlayedMatch::Finished => None
+        };
+
+        let entrant = |seat| if seat == Seat::First { first } else { second };
+        let mut games = Vec::new();
+        for (index, round) in state.rounds().iter().enumerate() {
+            let resistance = if MatchState::side_of(Seat::First, index as u32) == Side::Resistance { Seat::First } else { Seat::Second };
+            let played = match (round.outcome(), forfeit.take()) {
+                (Some(outcome), _) => PlayedRound::Finished(outcome),
+                (None, Some((seat, error))) => PlayedRound::Forfeited { side: MatchState::side_of(seat, index as u32), error },
+                // Only the round a player forfeited is left unfinished
+                (None, None) => continue
+            };
+            let pairing = (entrant(resistance), entrant(resistance.other()));
+            let mut game = self.record(pairing.0, pairing.1, played, round)?;
+            if self.config.archive.is_some() {
+                // The match starts its own rounds, so each is recorded from its history once played
+                let mut round = round.clone();
+                round.attach_recorder(&game.resistance, &game.suppression, seed);
+                game.replay = self.archive(&mut round, &format!("match-{}-round-{}.json", index, games.len()))?;
+            }
+            games.push((pairing, game));
+        }
+        Ok(games)
+    }
+
+    /// Save the replay recorded on a round to the archive, returning the file it was saved to
+    fn archive(&self, state: &mut RoundState, file_name: &str) -> Result<Option<String>, TournamentError> {
+        let (Some(archive), Some(replay)) = (&self.config.archive, state.detach_recorder()) else {
+            return Ok(None);
+        };
+        let path = archive.join(file_name);
+        replay.save_file(&path).map_err(|error| TournamentError::Archive(error.to_string()))?;
+        Ok(Some(path.display().to_string()))
+    }
+
+    /// Record how a game between two entrants went
+    fn record(&self, resistance: usize, suppression: usize, played: PlayedRound, state: &RoundState) -> Result<GameRecord, TournamentError> {
+        let forfeit = match &played {
+            PlayedRound::Aborted(error) => return Err(TournamentError::RulesFailed(error.clone())),
+            PlayedRound::Forfeited { error, .. } => Some(error.to_string()),
+            PlayedRound::Finished(_) => None
+        };
+        Ok(GameRecord {
+            resistance: self.entrants[resistance].name.clone(),
+            suppression: self.entrants[suppression].name.clone(),
+            winner: played.winner(),
+            forfeit,
+            turns: state.current_turn(),
+            score: state.resistance_score(),
+            replay: None
+        })
+    }
+
+    /// Rate the players and tally the matchups, going through the games in schedule order
+    fn report(&self, games: Vec<ScheduledGame>) -> TournamentReport {
+        let mut ratings: Vec<Rating> = self.entrants.iter().map(|entrant| Rating {
+            name: entrant.name.clone(),
+            rating: self.config.initial_rating,
+            wins: 0,
+            draws: 0,
+            losses: 0
+        }).collect();
+        let mut matchups: Vec<Matchup> = Vec::new();
+        let mut matchup_index: HashMap<(usize, usize), usize> = HashMap::new();
+
+        for ((resistance, suppression), game) in &games {
+            let score = match game.winner {
+                Some(Side::Resistance) => 1.0,
+                Some(Side::Suppression) => 0.0,
+                None => 0.5
+            };
+            let (mut resistance_rating, mut suppression_rating) = (ratings[*resistance].rating, ratings[*suppression].rating);
+            ratings::update(&mut resistance_rating, &mut suppression_rating, score, self.config.k);
+            ratings[*resistance].rating = resistance_rating;
+            ratings[*suppression].rating = suppression_rating;
+
+            let index = *matchup_index.entry((*resistance, *suppression)).or_insert_with(|| {
+                matchups.push(Matchup {
+                    resistance: game.resistance.clone(),
+                    suppression: game.suppression.clone(),
+                    resistance_wins: 0,
+                    draws: 0,
+                    suppression_wins: 0
+                });
+                matchups.len() - 1
+            });
+            match game.winner {
+                Some(Side::Resistance) => {
+                    ratings[*resistance].wins += 1;
+                    ratings[*suppression].losses += 1;
+                    matchups[index].resistance_wins += 1;
+                },
+                Some(Side::Suppression) => {
+                    ratings[*suppression].wins += 1;
+                    ratings[*resistance].losses += 1;
+                    matchups[index].suppression_wins += 1;
+                },
+                None => {
+                    ratings[*resistance].draws += 1;
+                    ratings[*suppression].draws += 1;
+                    matchups[index].draws += 1;
+                }
+            }
+        }
+
+        ratings.sort_by(|a, b| b.rating.total_cmp(&a.rating));
+        let games = games.into_iter().map(|(_, game)| game).collect();
+        TournamentReport { rule_set: self.rules.name().to_string(), ratings, matchups, games }
+    }
+}
+
+/// The factory for one of the built-in players: "random", "greedy", "heuristic" or "mcts"
+///
+/// The search player is given a small budget, as tournaments and sweeps play a lot of games.
+pub(crate) fn builtin_player(name: &str) -> Option<PlayerFactory> {
+    let factory: PlayerFactory = match name {
+        "random" => Box::new(|seed| Ok(Box::new(RandomPlayer::new(seed)) as Box<dyn Player>)),
+        "greedy" => Box::new(|_| Ok(Box::new(GreedyPlayer) as Box<dyn Player>)),
+        "heuristic" => Box::new(|_| Ok(Box::new(HeuristicPlayer::default()) as Box<dyn Player>)),
+        "mcts" => Box::new(|seed| {
+            let config = MctsConfig { iterations: Some(200), seed, ..MctsConfig::default() };
+            Ok(Box::new(MctsPlayer::new(config)) as Box<dyn Player>)
+        }),
+        _ => return None
+    };
+    Some(factory)
+}
+
+/// The name and factory of a rhai bot
+///
+/// The script is loaded once up front to check it compiles, and again for every player made.
+pub(crate) fn script_player(path: &Path, limits: &ScriptLimits) -> Result<(String, PlayerFactory), PlayerError> {
+    let bot = ScriptedBot::load(path, limits).map_err(|error| PlayerError::from(BotError::Script(error)))?;
+    let (path, limits) = (path.to_path_buf(), limits.clone());
+    let factory: PlayerFactory = Box::new(move |_| {
+        ScriptedBot::load(&path, &limits)
+            .map(|bot| Box::new(bot) as Box<dyn Player>)
+            .map_err(|error| PlayerError::from(BotError::Script(error)))
+    });
+    Ok((bot.name().to_string(), factory))
+}
+
+/// Load a rhai rule set to play under, as given on the command line
+pub(crate) fn rules_from_path(path: &str) -> Result<Rules, String> {
+    ScriptedRuleSet::load(Path::new(path), &ScriptLimits::default())
+        .map(Rules::new)
+        .map_err(|error| error.to_string())
+}
+
+/// Play a round out between fresh players from two factories
+///
+/// The suppressor is made from the seed after the resistance's, so the two never share a seed. A
+/// player that can't be made is an error rather than a forfeit, as no game was played.
+pub(crate) fn play_game(state: &mut RoundState, resistance: &PlayerFactory, suppression: &PlayerFactory, seed: u64) -> Result<PlayedRound, PlayerError> {
+    let mut resistance = resistance(seed)?;
+    let mut suppression = suppression(seed.wrapping_add(1))?;
+    Ok(play_round(state, resistance.as_mut(), suppression.as_mut()))
+}
+
+/// Run a job for every index from 0 to `count` on a pool of threads, returning the results in order
+pub(crate) fn parallel_map<T: Send>(count: usize, threads: usize, job: impl Fn(usize) -> T + Sync) -> Vec<T> {
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<T>>> = Mutex::new((0..count).map(|_| None).collect());
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, count.max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                if index >= count {
+                    break;
+                }
+                let result = job(index);
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+    // Every index was claimed by a thread, and the threads are all done
+    results.into_inner().unwrap().into_iter().map(Option::unwrap).collect()
+}
+
+/// Run a tournament from the command line and print the report
+///
+/// Takes `--games N`, `--threads N`, `--seed N`, `--best-of N` to play pairings as matches of N rounds
+/// rather than single games, `--archive DIR` to save a replay of every game to, `--json`, `--players a,b,...` to choose the built-in
+/// players (random, greedy and heuristic by default), `--external NAME=COMMAND` for a program
+/// speaking the bot protocol, `--time-limit SECONDS` for how long such a program has to answer each
+/// turn, `--rules PATH` for a rhai rule set to play under instead of the
+/// classic rules, and the paths of any rhai bots to enter.
+pub fn run_cli(args: &[String]) -> Result<(), String> {
+    let mut config = TournamentConfig::default();
+    let mut builtins = vec!["random".to_string(), "greedy".to_string(), "heuristic".to_string()];
+    let mut externals: Vec<(String, String)> = Vec::new();
+    let mut scripts: Vec<String> = Vec::new();
+    let mut json = false;
+    let mut rules = Rules::default();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().cloned().ok_or_else(|| format!("{} needs a value", flag));
+        match arg.as_str() {
+            "--games" => config.games = parse_flag("--games", &value("--games")?)?,
+            "--threads" => config.threads = parse_flag("--threads", &value("--threads")?)?,
+            "--seed" => config.seed = parse_flag("--seed", &value("--seed")?)?,
+            "--best-of" => config.best_of = Some(parse_flag("--best-of", &value("--best-of")?)?),
+            "--archive" => config.archive = Some(PathBuf::from(value("--archive")?)),
+            "--time-limit" => {
+                let seconds: f64 = parse_flag("--time-limit", &value("--time-limit")?)?;
+                config.time_limit = Duration::try_from_secs_f64(seconds).map_err(|_| format!("{} isn't a time limit", seconds))?;
+            },
+            "--json" => json = true,
+            "--rules" => rules = rules_from_path(&value("--rules")?)?,
+            "--players" => builtins = value("--players")?.split(',').filter(|name| !name.is_empty()).map(str::to_string).collect(),
+            "--external" => {
+                let external = value("--external")?;
+                let (name, command) = external.split_once('=').ok_or_else(|| format!("expected NAME=COMMAND, not {}", external))?;
+                externals.push((name.to_string(), command.to_string()));
+            },
+            flag if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+            path => scripts.push(path.to_string())
+        }
+    }
+
+    let mut tournament = Tournament::with_rules(config, rules);
+    for name in &builtins {
+        if !tournament.register_builtin(name).map_err(|error| error.to_string())? {
+            return Err(format!("there is no built-in player called {}", name));
+        }
+    }
+    for path in &scripts {
+        tournament.register_script(Path::new(path), &ScriptLimits::default()).map_err(|error| error.to_string())?;
+    }
+    for (name, command) in &externals {
+        let mut words = command.split_whitespace().map(str::to_string);
+        let program = words.next().ok_or_else(|| format!("no command given for {}", name))?;
+        let args: Vec<String> = words.collect();
+        tournament.register_external(name, &program, &args).map_err(|error| error.to_string())?;
+    }
+
+    let report = tournament.run().map_err(|error| error.to_string())?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report).map_err(|error| error.to_string())?);
+    } else {
+        print!("{}", report);
+    }
+    Ok(())
+}
+
+/// Parse the value given to a command line flag
+pub(crate) fn parse_flag<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("{} can't be {}", flag, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+
+    use crate::ai::baseline::GreedyPlayer;
+    use crate::ai::{Player, PlayerError};
+    use crate::gameplay::actors::{ResistanceAction, Side, SuppressionAction};
+    use crate::gameplay::replay::{Replay, ReplayPlayer};
+    use crate::gameplay::round_config::RoundConfig;
+    use crate::gameplay::rules::Rules;
+    use crate::gameplay::views::{ResistanceView, SuppressionView};
+    use crate::scripting::ScriptLimits;
+    use crate::scripting::rules::ScriptedRuleSet;
+    use crate::tournament::{rules_from_path, run_cli, Tournament, TournamentConfig, TournamentError};
+
+    fn config(threads: usize) -> TournamentConfig {
+        let round = RoundConfig::builder().board_size(5, 5).max_turns(8).build().unwrap();
+        TournamentConfig { round, games: 3, threads, ..TournamentConfig::default() }
+    }
+
+    /// Tests that every pairing is played on both sides and the report adds up
+    #[test]
+    fn test_round_robin() {
+        let mut tournament = Tournament::new(config(4));
+        for name in ["random", "greedy", "heuristic"] {
+            assert!(tournament.register_builtin(name).unwrap());
+        }
+        let report = tournament.run().unwrap();
+
+        assert_eq!(report.games.len(), 3 * 2 * 3);
+        assert_eq!(report.matchups.len(), 6);
+        assert!(report.matchups.iter().all(|matchup| matchup.games() == 3));
+        for rating in &report.ratings {
+            assert_eq!(rating.games(), 12);
+        }
+        let total: f32 = report.ratings.iter().map(|rating| rating.rating).sum();
+        assert!((total - 4500.0).abs() < 0.1);
+        assert!(report.ratings.windows(2).all(|pair| pair[0].rating >= pair[1].rating));
+    }
+
+    /// Tests that the report doesn't depend on how many threads played the games
+    #[test]
+    fn test_deterministic() {
+        let run = |threads| {
+            let mut tournament = Tournament::new(config(threads));
+            tournament.register_builtin("random").unwrap();
+            tournament.register_builtin("greedy").unwrap();
+            tournament.run().unwrap()
+        };
+        assert_eq!(run(1), run(3));
+    }
+
+    /// Tests playing a tournament under the sample rhai rule set
+    #[test]
+    fn test_scripted_rules() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/scripts/rules/landslide.rhai");
+        let mut tournament = Tournament::with_rules(config(2), rules_from_path(path).unwrap());
+        tournament.register_builtin("greedy").unwrap();
+        tournament.register_builtin("heuristic").unwrap();
+        let report = tournament.run().unwrap();
+        assert_eq!(report.rule_set, "landslide");
+        assert_eq!(report.games.len(), 6);
+        assert!(report.games.iter().all(|game| game.forfeit.is_none()));
+
+        let args = |rules: &str| ["--games", "1", "--threads", "2", "--players", "greedy,random", "--rules", rules].map(str::to_string);
+        assert!(run_cli(&args(path)).is_ok());
+        assert!(run_cli(&args("scripts/rules/missing.rhai")).is_err());
+    }
+
+    /// Tests registering players the tournament can't use
+    #[test]
+    fn test_registration() {
+        let mut tournament = Tournament::new(config(1));
+        assert!(!tournament.register_builtin("nobody").unwrap());
+        tournament.register_builtin("greedy").unwrap();
+        assert!(matches!(tournament.register_builtin("greedy"), Err(TournamentError::DuplicateName(_))));
+        assert!(matches!(tournament.run(), Err(TournamentError::TooFewPlayers(1))));
+
+        let unavailable = tournament.register("broken", |_| Err(PlayerError::Failed {
+            player: "broken".to_string(), message: "no".to_string()
+        }));
+        assert!(matches!(unavailable, Err(TournamentError::Unavailable(_))));
+    }
+
+    /// A greedy player that only ever plays the resistance
+    struct ResistanceOnly;
+
+    impl Player for ResistanceOnly {
+        fn name(&self) -> &str {
+            "resistance only"
+        }
+
+        fn plays(&self, side: Side) -> bool {
+            side == Side::Resistance
+        }
+
+        fn resistance_action(&mut self, view: &ResistanceView) -> Result<ResistanceAction, PlayerError> {
+            GreedyPlayer.resistance_action(view)
+        }
+
+        fn suppression_action(&mut self, view: &SuppressionView) -> Result<SuppressionAction, PlayerError> {
+            GreedyPlayer.suppression_action(view)
+        }
+    }
+
+    /// Tests that players are only scheduled on the sides they play
+    #[test]
+    fn test_one_sided_player() {
+        let mut tournament = Tournament::new(config(2));
+        tournament.register("resistance only", |_| Ok(Box::new(ResistanceOnly) as Box<dyn Player>)).unwrap();
+        tournament.register_builtin("greedy").unwrap();
+        let report = tournament.run().unwrap();
+        assert_eq!(report.games.len(), 3);
+        assert!(report.games.iter().all(|game| game.resistance == "resistance only"));
+    }
+
+    /// Tests that pairings are played as matches, every round rated as a game
+    #[test]
+    fn test_matches() {
+        let config = TournamentConfig { best_of: Some(3), ..config(2) };
+        let mut tournament = Tournament::new(config.clone());
+        tournament.register_builtin("greedy").unwrap();
+        tournament.register_builtin("random").unwrap();
+        let report = tournament.run().unwrap();
+        // Three matches of two or three rounds each
+        assert!((6..=9).contains(&report.games.len()), "{}", report.games.len());
+        let rounds: u32 = report.matchups.iter().map(|matchup| matchup.games()).sum();
+        assert_eq!(rounds as usize, report.games.len());
+        assert_eq!(report.matchups.len(), 2);
+        assert!(report.ratings.iter().all(|rating| rating.games() as usize == report.games.len()));
+
+        let run = |threads| {
+            let mut tournament = Tournament::new(TournamentConfig { threads, ..config.clone() });
+            tournament.register_builtin("greedy").unwrap();
+            tournament.register_builtin("random").unwrap();
+            tournament.run().unwrap()
+        };
+        assert_eq!(run(1), run(3));
+
+        let mut tournament = Tournament::new(config);
+        tournament.register("resistance only", |_| Ok(Box::new(ResistanceOnly) as Box<dyn Player>)).unwrap();
+        tournament.register_builtin("greedy").unwrap();
+        assert!(matches!(tournament.run(), Err(TournamentError::OneSided(name)) if name == "resistance only"));
+    }
+
+    /// Tests that a player forfeiting a round of a match loses the match there
+    #[test]
+    fn test_match_forfeit() {
+        let config = TournamentConfig { games: 1, best_of: Some(3), time_limit: Duration::from_millis(200), ..config(2) };
+        let mut tournament = Tournament::new(config);
+        tournament.register_external("sleeper", "sh", &["-c".to_string(), "sleep 30".to_string()]).unwrap();
+        tournament.register_builtin("greedy").unwrap();
+        let report = tournament.run().unwrap();
+        // The sleeper sits first, so forfeits the first round as the resistance
+        assert_eq!(report.games.len(), 1);
+        assert_eq!(report.games[0].resistance, "sleeper");
+        assert_eq!(report.games[0].winner, Some(Side::Suppression));
+        assert!(report.games[0].forfeit.is_some());
+        assert!(run_cli(&["--games", "1", "--players", "greedy,random", "--best-of", "3"].map(str::to_string)).is_ok());
+        assert!(run_cli(&["--best-of", "none"].map(str::to_string)).is_err());
+    }
+
+    /// Tests that every game of a tournament is archived as a replay that plays back
+    #[test]
+    fn test_archive() {
+        for best_of in [None, Some(3)] {
+            let archive = std::env::temp_dir().join(format!("deepphase_tournament_archive_{}", best_of.is_some()));
+            let config = TournamentConfig { games: 1, best_of, archive: Some(archive.clone()), ..config(2) };
+            let mut tournament = Tournament::new(config);
+            tournament.register_builtin("greedy").unwrap();
+            tournament.register_builtin("random").unwrap();
+            let report = tournament.run().unwrap();
+            for game in &report.games {
+                let replay = Replay::load_file(Path::new(game.replay.as_deref().unwrap())).unwrap();
+                assert_eq!(replay.header.resistance_player, game.resistance);
+                assert_eq!(replay.turns.len() as u32, game.turns);
+                ReplayPlayer::new(replay).unwrap().verify().unwrap();
+            }
+            std::fs::remove_dir_all(archive).unwrap();
+        }
+    }
+
+    /// Tests that a rule script failing mid-game stops the tournament instead of scoring a forfeit
+    #[test]
+    fn test_failing_rules() {
+        let script = ScriptedRuleSet::from_source("broken.rhai", "fn check_outcome(round) { throw \"no outcome\"; }", &ScriptLimits::default()).unwrap();
+        let mut tournament = Tournament::with_rules(config(2), Rules::new(script));
+        tournament.register_builtin("greedy").unwrap();
+        tournament.register_builtin("random").unwrap();
+        let error = tournament.run().unwrap_err();
+        assert!(matches!(&error, TournamentError::RulesFailed(error) if error.rule_set == "broken.rhai"), "{}", error);
+    }
+
+    /// Tests that an external player that stops answering forfeits its games once its time is up
+    #[test]
+    fn test_external_time_limit() {
+        let config = TournamentConfig { games: 1, time_limit: Duration::from_millis(200), ..config(2) };
+        let mut tournament = Tournament::new(config);
+        tournament.register_external("sleeper", "sh", &["-c".to_string(), "sleep 30".to_string()]).unwrap();
+        tournament.register_builtin("greedy").unwrap();
+        let started = Instant::now();
+        let report = tournament.run().unwrap();
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(report.games.len(), 2);
+        for game in &report.games {
+            let reason = game.forfeit.as_deref().unwrap();
+            assert!(reason.starts_with("sleeper failed to choose an action: no action within"), "{}", reason);
+        }
+        let sleeper = report.ratings.iter().find(|rating| rating.name == "sleeper").unwrap();
+        assert_eq!(sleeper.losses, 2);
+        assert!(report.to_string().contains("forfeits\nsleeper v greedy: sleeper failed to choose an action"));
+    }
+
+    /// Tests that a player that can't be made for a game is an error rather than a loss
+    #[test]
+    fn test_player_unavailable_mid_tournament() {
+        let made = AtomicUsize::new(0);
+        let mut tournament = Tournament::new(config(1));
+        tournament.register("flaky", move |_| match made.fetch_add(1, Ordering::Relaxed) {
+            0 => Ok(Box::new(GreedyPlayer) as Box<dyn Player>),
+            _ => Err(PlayerError::Failed { player: "flaky".to_string(), message: "gone".to_string() })
+        }).unwrap();
+        tournament.register_builtin("random").unwrap();
+        let error = tournament.run().unwrap_err();
+        assert!(matches!(&error, TournamentError::Unavailable(PlayerError::Failed { message, .. }) if message == "gone"), "{}", error);
+    }
+}
@@ -0,0 +1,72 @@
+/*! Elo ratings
+*
+* Every game is scored for its resistance as 1 for a win, 0.5 for a draw and 0 for a loss, and both
+* players' ratings move by `k` times the difference between that score and the one their ratings
+* predicted. Ratings don't care which side a player was on, so a rule set that favours one side
+* shows up in the matchups rather than the ratings.
+*/
+
+use serde::{Serialize, Deserialize};
+
+/// The score a player rated `rating` is expected to take off one rated `opponent`, between 0 and 1.
+pub fn expected_score(rating: f32, opponent: f32) -> f32 {
+    1.0 / (1.0 + 10f32.powf((opponent - rating) / 400.0))
+}
+
+/// Move two ratings after a game, `score` being what the first player scored
+pub fn update(first: &mut f32, second: &mut f32, score: f32, k: f32) {
+    let change = k * (score - expected_score(*first, *second));
+    *first += change;
+    *second -= change;
+}
+
+/** How a player did over a tournament
+
+*Fields*
+- `name` (String): the name the player was registered under
+- `rating` (f32): the player's Elo rating after every game
+- `wins` (u32): games won, on either side
+- `draws` (u32): games drawn
+- `losses` (u32): games lost, forfeits included
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rating {
+    pub name: String,
+    pub rating: f32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32
+}
+
+impl Rating {
+    /// The number of games played.
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The share of its games the player scored, a draw counting for half.
+    pub fn score_rate(&self) -> f32 {
+        match self.games() {
+            0 => 0.0,
+            games => (self.wins as f32 + self.draws as f32 / 2.0) / games as f32
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tournament::ratings::{expected_score, update};
+
+    /// Tests that ratings move towards the result and keep their total
+    #[test]
+    fn test_update() {
+        assert_eq!(expected_score(1500.0, 1500.0), 0.5);
+        assert!((expected_score(1900.0, 1500.0) - 0.909).abs() < 0.001);
+
+        let (mut first, mut second) = (1500.0, 1500.0);
+        update(&mut first, &mut second, 1.0, 32.0);
+        assert_eq!((first, second), (1516.0, 1484.0));
+        update(&mut first, &mut second, 0.5, 32.0);
+        assert!(first < 1516.0 && first + second == 3000.0);
+    }
+}
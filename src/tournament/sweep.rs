@@ -0,0 +1,526 @@
+/*! Parameter sweeps for balancing rounds
+*
+* A sweep plays the same pair of players over many round configs: every combination of the values
+* given for `max_turns`, `temp_turn_count`, `score_to_win` and the largest zone, or a random sample
+* of them. Each point gets the same number of games and is summed up by how often the resistance
+* won, how long the games lasted and how many tiles were captured, so configs that land near an even
+* split can be picked out of the report.
+*
+* Combinations that don't make a valid round, such as a zone that covers the whole board, are
+* skipped rather than played.
+*/
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+use rand::SeedableRng;
+use serde::{Serialize, Deserialize};
+
+use crate::ai::PlayedRound;
+use crate::gameplay::actors::Side;
+use crate::gameplay::events::RoundEvent;
+use crate::gameplay::round_config::RoundConfig;
+use crate::gameplay::round_manager::RoundState;
+use crate::gameplay::rules::{RuleError, Rules};
+use crate::scripting::ScriptLimits;
+use crate::tournament::{builtin_player, parallel_map, parse_flag, play_game, rules_from_path, script_player, PlayerFactory};
+
+/** The values each swept parameter takes
+
+*Fields*
+- `max_turns` (Vec<u32>): values for `RoundConfig::max_turns`
+- `temp_turn_count` (Vec<u32>): values for `RoundConfig::temp_turn_count`
+- `score_to_win` (Vec<u32>): values for `RoundConfig::score_to_win`
+- `zone_max_tiles` (Vec<u32>): values for the largest zone, the smallest being lowered to match if need be
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SweepSpace {
+    pub max_turns: Vec<u32>,
+    pub temp_turn_count: Vec<u32>,
+    pub score_to_win: Vec<u32>,
+    pub zone_max_tiles: Vec<u32>
+}
+
+impl SweepSpace {
+    /// A space holding just the values of one config.
+    pub fn single(config: &RoundConfig) -> SweepSpace {
+        SweepSpace {
+            max_turns: vec![config.max_turns],
+            temp_turn_count: vec![config.temp_turn_count],
+            score_to_win: vec![config.score_to_win],
+            zone_max_tiles: vec![config.zone.max_tiles]
+        }
+    }
+
+    /// The number of combinations in the grid.
+    pub fn size(&self) -> usize {
+        self.max_turns.len() * self.temp_turn_count.len() * self.score_to_win.len() * self.zone_max_tiles.len()
+    }
+}
+
+/// How points are drawn from the space.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Sampling {
+    /// Every combination of values.
+    Grid,
+    /// This many combinations, each value picked at random.
+    Random(usize)
+}
+
+/** How a sweep is run
+
+*Fields*
+- `base` (RoundConfig): the config every point starts from, giving everything that isn't swept
+- `space` (SweepSpace): the values to sweep over
+- `sampling` (Sampling): which combinations of values to play
+- `games` (u32): the games played at each point
+- `threads` (usize): the number of games played at once
+- `seed` (u64): fixes the random sample and the seeds handed to the players
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct SweepConfig {
+    pub base: RoundConfig,
+    pub space: SweepSpace,
+    pub sampling: Sampling,
+    pub games: u32,
+    pub threads: usize,
+    pub seed: u64
+}
+
+impl Default for SweepConfig {
+    fn default() -> Self {
+        let base = RoundConfig::default();
+        Self {
+            space: SweepSpace::single(&base),
+            base,
+            sampling: Sampling::Grid,
+            games: 20,
+            threads: std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+            seed: 0
+        }
+    }
+}
+
+/// The ways a sweep can fail to run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SweepError {
+    /// A parameter was given no values to take.
+    NoValues(&'static str),
+    /// No combination of values makes a valid round.
+    NoValidPoints,
+    /// The rule set failed during a game, so the game couldn't be counted.
+    RulesFailed(RuleError),
+    /// A player couldn't be made for a game.
+    PlayerUnavailable(String)
+}
+
+impl fmt::Display for SweepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SweepError::NoValues(parameter) => write!(f, "no values given for {}", parameter),
+            SweepError::NoValidPoints => write!(f, "none of the configs swept over make a valid round"),
+            SweepError::RulesFailed(error) => write!(f, "a game was called off: {}", error),
+            SweepError::PlayerUnavailable(error) => write!(f, "couldn't make a player: {}", error)
+        }
+    }
+}
+
+impl std::error::Error for SweepError {}
+
+/** How the games at one point of a sweep went
+
+*Fields*
+- `max_turns` (u32): the point's `max_turns`
+- `temp_turn_count` (u32): the point's `temp_turn_count`
+- `score_to_win` (u32): the point's `score_to_win`
+- `zone_max_tiles` (u32): the point's largest zone
+- `games` (u32): the games played
+- `resistance_wins` (u32): games the resistance won
+- `draws` (u32): games drawn
+- `suppression_wins` (u32): games the suppression won
+- `forfeits` (u32): games lost by a player failing to take its turn, counted in the wins as well
+- `resistance_win_rate` (f32): the share of the games the resistance won
+- `average_turns` (f32): the turns a game lasted on average
+- `average_captures` (f32): the tiles made permanent per game, by surrounding or enclosing them
+- `average_expired` (f32): the temporary tiles that ran out of time per game
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SweepPoint {
+    pub max_turns: u32,
+    pub temp_turn_count: u32,
+    pub score_to_win: u32,
+    pub zone_max_tiles: u32,
+    pub games: u32,
+    pub resistance_wins: u32,
+    pub draws: u32,
+    pub suppression_wins: u32,
+    pub forfeits: u32,
+    pub resistance_win_rate: f32,
+    pub average_turns: f32,
+    pub average_captures: f32,
+    pub average_expired: f32
+}
+
+impl SweepPoint {
+    /// How far the resistance's score is from an even split, a draw counting for half: 0 for a
+    /// perfectly balanced point and 0.5 for a one-sided one.
+    pub fn imbalance(&self) -> f32 {
+        if self.games == 0 {
+            return 0.5;
+        }
+        let score = (self.resistance_wins as f32 + self.draws as f32 / 2.0) / self.games as f32;
+        (score - 0.5).abs()
+    }
+}
+
+/** Everything a sweep found out
+
+*Fields*
+- `rule_set` (String): the name of the rule set every game was played under
+- `points` (Vec<SweepPoint>): every point played, in the order they were drawn
+- `skipped` (u32): the points drawn that didn't make a valid round
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SweepReport {
+    pub rule_set: String,
+    pub points: Vec<SweepPoint>,
+    pub skipped: u32
+}
+
+impl SweepReport {
+    /// The most balanced points, most balanced first
+    pub fn most_balanced(&self, count: usize) -> Vec<&SweepPoint> {
+        let mut points: Vec<&SweepPoint> = self.points.iter().collect();
+        points.sort_by(|a, b| a.imbalance().total_cmp(&b.imbalance()));
+        points.truncate(count);
+        points
+    }
+
+    /// The report as CSV, one line per point under a header
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "max_turns,temp_turn_count,score_to_win,zone_max_tiles,games,resistance_wins,draws,suppression_wins,\
+            forfeits,resistance_win_rate,average_turns,average_captures,average_expired\n"
+        );
+        for point in &self.points {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{},{},{},{:.4},{:.2},{:.2},{:.2}\n",
+                point.max_turns, point.temp_turn_count, point.score_to_win, point.zone_max_tiles, point.games,
+                point.resistance_wins, point.draws, point.suppression_wins, point.forfeits, point.resistance_win_rate,
+                point.average_turns, point.average_captures, point.average_expired
+            ));
+        }
+        csv
+    }
+}
+
+/// What one game of a sweep is reduced to.
+struct GameStats {
+    winner: Option<Side>,
+    forfeit: bool,
+    turns: u32,
+    captures: u32,
+    expired: u32
+}
+
+/// A pair of players to sweep over round configs with.
+pub(crate) struct Sweep {
+    config: SweepConfig,
+    rules: Rules,
+    resistance: PlayerFactory,
+    suppression: PlayerFactory
+}
+
+impl Sweep {
+    /// A sweep played under the classic rules
+    pub fn new(config: SweepConfig, resistance: PlayerFactory, suppression: PlayerFactory) -> Sweep {
+        Sweep::with_rules(config, Rules::default(), resistance, suppression)
+    }
+
+    /// A sweep played under a variant rule set
+    pub fn with_rules(config: SweepConfig, rules: Rules, resistance: PlayerFactory, suppression: PlayerFactory) -> Sweep {
+        Sweep { config, rules, resistance, suppression }
+    }
+
+    /// The configs to play, and how many of the points drawn were invalid
+    pub fn points(&self) -> Result<(Vec<RoundConfig>, u32), SweepError> {
+        let space = &self.config.space;
+        for (parameter, values) in [
+            ("max_turns", &space.max_turns),
+            ("temp_turn_count", &space.temp_turn_count),
+            ("score_to_win", &space.score_to_win),
+            ("zone_max_tiles", &space.zone_max_tiles)
+        ] {
+            if values.is_empty() {
+                return Err(SweepError::NoValues(parameter));
+            }
+        }
+
+        let mut drawn: Vec<[u32; 4]> = Vec::new();
+        match self.config.sampling {
+            Sampling::Grid => for max_turns in &space.max_turns {
+                for temp_turn_count in &space.temp_turn_count {
+                    for score_to_win in &space.score_to_win {
+                        for zone_max_tiles in &space.zone_max_tiles {
+                            drawn.push([*max_turns, *temp_turn_count, *score_to_win, *zone_max_tiles]);
+                        }
+                    }
+                }
+            },
+            Sampling::Random(count) => {
+                let mut rng = StdRng::seed_from_u64(self.config.seed);
+                for _ in 0..count {
+                    // Every list was checked to have a value
+                    let mut pick = |values: &Vec<u32>| *values.choose(&mut rng).unwrap();
+                    drawn.push([pick(&space.max_turns), pick(&space.temp_turn_count), pick(&space.score_to_win), pick(&space.zone_max_tiles)]);
+                }
+            }
+        }
+
+        let mut points = Vec::new();
+        let mut skipped = 0;
+        for [max_turns, temp_turn_count, score_to_win, zone_max_tiles] in drawn {
+            let mut config = self.config.base.clone();
+            config.max_turns = max_turns;
+            config.temp_turn_count = temp_turn_count;
+            config.score_to_win = score_to_win;
+            config.zone.max_tiles = zone_max_tiles;
+            config.zone.min_tiles = config.zone.min_tiles.min(zone_max_tiles);
+            match config.validate() {
+                Ok(()) => points.push(config),
+                Err(_) => skipped += 1
+            }
+        }
+        if points.is_empty() {
+            return Err(SweepError::NoValidPoints);
+        }
+        Ok((points, skipped))
+    }
+
+    /// Play every point and sum up the games
+    pub fn run(&self) -> Result<SweepReport, SweepError> {
+        let (points, skipped) = self.points()?;
+        let games = self.config.games as usize;
+        let stats = parallel_map(points.len() * games, self.config.threads, |index| {
+            self.play_game(&points[index / games], self.config.seed.wrapping_add(2 * index as u64))
+        });
+        let stats = stats.into_iter().collect::<Result<Vec<GameStats>, SweepError>>()?;
+
+        let points = points.iter().enumerate().map(|(index, config)| {
+            let games = &stats[index * games..(index + 1) * games];
+            let count = games.len() as u32;
+            let wins = |side| games.iter().filter(|game| game.winner == Some(side)).count() as u32;
+            let average = |value: fn(&GameStats) -> u32| match count {
+                0 => 0.0,
+                count => games.iter().map(value).sum::<u32>() as f32 / count as f32
+            };
+            SweepPoint {
+                max_turns: config.max_turns,
+                temp_turn_count: config.temp_turn_count,
+                score_to_win: config.score_to_win,
+                zone_max_tiles: config.zone.max_tiles,
+                games: count,
+                resistance_wins: wins(Side::Resistance),
+                draws: games.iter().filter(|game| game.winner.is_none()).count() as u32,
+                suppression_wins: wins(Side::Suppression),
+                forfeits: games.iter().filter(|game| game.forfeit).count() as u32,
+                resistance_win_rate: match count {
+                    0 => 0.0,
+                    count => wins(Side::Resistance) as f32 / count as f32
+                },
+                average_turns: average(|game| game.turns),
+                average_captures: average(|game| game.captures),
+                average_expired: average(|game| game.expired)
+            }
+        }).collect();
+        Ok(SweepReport { rule_set: self.rules.name().to_string(), points, skipped })
+    }
+
+    /// Play one game at a point, counting its captures from the round's events
+    fn play_game(&self, config: &RoundConfig, seed: u64) -> Result<GameStats, SweepError> {
+        let mut state = RoundState::with_rules(config.clone(), self.rules.clone());
+        state.enable_event_queue();
+        let played = play_game(&mut state, &self.resistance, &self.suppression, seed)
+            .map_err(|error| SweepError::PlayerUnavailable(error.to_string()))?;
+        if let PlayedRound::Aborted(error) = played {
+            return Err(SweepError::RulesFailed(error));
+        }
+        let (mut captures, mut expired) = (0, 0);
+        for event in state.drain_events() {
+            match event {
+                RoundEvent::PermanentCaptured(_) => captures += 1,
+                RoundEvent::RegionCaptured(region) => captures += region.len() as u32,
+                RoundEvent::TemporaryExpired { .. } => expired += 1,
+                _ => {}
+            }
+        }
+        Ok(GameStats {
+            winner: played.winner(),
+            forfeit: matches!(played, PlayedRound::Forfeited { .. }),
+            turns: state.current_turn(),
+            captures,
+            expired
+        })
+    }
+}
+
+/// A built-in player by name, or a rhai bot by the path to its script
+fn player_from_arg(arg: &str) -> Result<PlayerFactory, String> {
+    if let Some(factory) = builtin_player(arg) {
+        return Ok(factory);
+    }
+    if arg.ends_with(".rhai") {
+        return script_player(Path::new(arg), &ScriptLimits::default())
+            .map(|(_, factory)| factory)
+            .map_err(|error| error.to_string());
+    }
+    Err(format!("{} is neither a built-in player nor a rhai script", arg))
+}
+
+/// A comma separated list of values
+fn parse_list(flag: &str, value: &str) -> Result<Vec<u32>, String> {
+    value.split(',').map(|item| parse_flag(flag, item.trim())).collect()
+}
+
+/// Run a sweep from the command line, print the most balanced points and write the report
+///
+/// Takes `--games N`, `--threads N`, `--seed N`, `--sample N` to play a random sample of N points
+/// instead of the whole grid, `--resistance` and `--suppression` naming a built-in player or a rhai
+/// bot's script (heuristic by default), `--rules PATH` for a rhai rule set to play under instead of
+/// the classic rules, `--config PATH` for the base round config, comma separated
+/// values for `--max-turns`, `--temp-turns`, `--score` and `--zone`, `--top N` for how many points to
+/// print, and `--csv PATH` or `--json PATH` to write the whole report.
+pub fn run_cli(args: &[String]) -> Result<(), String> {
+    let mut config = SweepConfig::default();
+    let mut space = SweepSpace { max_turns: vec![10, 15, 20, 25, 30], temp_turn_count: vec![2, 3, 4], score_to_win: vec![3, 5, 7], zone_max_tiles: vec![2, 3, 4, 5] };
+    let (mut resistance, mut suppression) = ("heuristic".to_string(), "heuristic".to_string());
+    let (mut csv, mut json): (Option<String>, Option<String>) = (None, None);
+    let mut top = 10;
+    let mut rules = Rules::default();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let flag = arg.as_str();
+        let value = args.next().ok_or_else(|| format!("{} needs a value", flag))?;
+        match flag {
+            "--games" => config.games = parse_flag(flag, value)?,
+            "--threads" => config.threads = parse_flag(flag, value)?,
+            "--seed" => config.seed = parse_flag(flag, value)?,
+            "--sample" => config.sampling = Sampling::Random(parse_flag(flag, value)?),
+            "--resistance" => resistance = value.clone(),
+            "--suppression" => suppression = value.clone(),
+            "--rules" => rules = rules_from_path(value)?,
+            "--config" => config.base = RoundConfig::from_file(Path::new(value)).map_err(|error| error.to_string())?,
+            "--max-turns" => space.max_turns = parse_list(flag, value)?,
+            "--temp-turns" => space.temp_turn_count = parse_list(flag, value)?,
+            "--score" => space.score_to_win = parse_list(flag, value)?,
+            "--zone" => space.zone_max_tiles = parse_list(flag, value)?,
+            "--top" => top = parse_flag(flag, value)?,
+            "--csv" => csv = Some(value.clone()),
+            "--json" => json = Some(value.clone()),
+            _ => return Err(format!("unknown option {}", flag))
+        }
+    }
+    config.space = space;
+
+    let sweep = Sweep::with_rules(config, rules, player_from_arg(&resistance)?, player_from_arg(&suppression)?);
+    let report = sweep.run().map_err(|error| error.to_string())?;
+    if let Some(path) = csv {
+        fs::write(&path, report.to_csv()).map_err(|error| format!("couldn't write {}: {}", path, error))?;
+    }
+    if let Some(path) = json {
+        let json = serde_json::to_string_pretty(&report).map_err(|error| error.to_string())?;
+        fs::write(&path, json).map_err(|error| format!("couldn't write {}: {}", path, error))?;
+    }
+
+    println!("{} points played under the {} rules, {} skipped as invalid", report.points.len(), report.rule_set, report.skipped);
+    println!("{:>9} {:>10} {:>6} {:>5} {:>6} {:>9} {:>7} {:>9}", "max_turns", "temp_turns", "score", "zone", "games", "res. win", "turns", "captures");
+    for point in report.most_balanced(top) {
+        println!(
+            "{:>9} {:>10} {:>6} {:>5} {:>6} {:>8.1}% {:>7.1} {:>9.1}",
+            point.max_turns, point.temp_turn_count, point.score_to_win, point.zone_max_tiles, point.games,
+            point.resistance_win_rate * 100.0, point.average_turns, point.average_captures
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gameplay::round_config::RoundConfig;
+    use crate::gameplay::rules::Rules;
+    use crate::scripting::ScriptLimits;
+    use crate::scripting::rules::ScriptedRuleSet;
+    use crate::tournament::builtin_player;
+    use crate::tournament::sweep::{run_cli, Sampling, Sweep, SweepConfig, SweepError, SweepReport, SweepSpace};
+
+    fn sweep(space: SweepSpace, sampling: Sampling) -> Sweep {
+        let base = RoundConfig::builder().board_size(5, 5).build().unwrap();
+        let config = SweepConfig { base, space, sampling, games: 4, threads: 3, seed: 7 };
+        Sweep::new(config, builtin_player("greedy").unwrap(), builtin_player("random").unwrap())
+    }
+
+    /// Tests that a grid plays every valid combination and the numbers add up
+    #[test]
+    fn test_grid_sweep() {
+        // A zone of 25 tiles covers the 5x5 board, so those points are skipped
+        let space = SweepSpace { max_turns: vec![6, 10], temp_turn_count: vec![2, 3], score_to_win: vec![3], zone_max_tiles: vec![2, 25] };
+        let report = sweep(space, Sampling::Grid).run().unwrap();
+        assert_eq!((report.points.len(), report.skipped), (4, 4));
+        for point in &report.points {
+            assert_eq!(point.resistance_wins + point.draws + point.suppression_wins, 4);
+            assert!(point.average_turns <= point.max_turns as f32);
+            assert!(point.resistance_win_rate >= 0.0 && point.resistance_win_rate <= 1.0);
+        }
+        let balanced = report.most_balanced(2);
+        assert!(balanced[0].imbalance() <= balanced[1].imbalance());
+        assert_eq!(report.to_csv().lines().count(), 5);
+    }
+
+    /// Tests that a random sample is drawn from the space and fixed by the seed
+    #[test]
+    fn test_random_sweep() {
+        let space = SweepSpace { max_turns: vec![5, 8, 12], temp_turn_count: vec![2, 3], score_to_win: vec![2, 4], zone_max_tiles: vec![1, 3] };
+        let first = sweep(space.clone(), Sampling::Random(5)).run().unwrap();
+        assert_eq!(first.points.len(), 5);
+        assert!(first.points.iter().all(|point| space.max_turns.contains(&point.max_turns)));
+        assert_eq!(first, sweep(space, Sampling::Random(5)).run().unwrap());
+    }
+
+    /// Tests a sweep from the command line under the sample rhai rule set
+    #[test]
+    fn test_cli_scripted_rules() {
+        let rules = concat!(env!("CARGO_MANIFEST_DIR"), "/scripts/rules/landslide.rhai");
+        let json = std::env::temp_dir().join("deepphase_sweep_rules_test.json");
+        let args = [
+            "--games", "2", "--resistance", "greedy", "--suppression", "random", "--max-turns", "6",
+            "--temp-turns", "2", "--score", "3", "--zone", "2,3", "--rules", rules, "--json", json.to_str().unwrap()
+        ].map(str::to_string);
+        run_cli(&args).unwrap();
+        let report: SweepReport = serde_json::from_str(&std::fs::read_to_string(&json).unwrap()).unwrap();
+        std::fs::remove_file(&json).unwrap();
+        assert_eq!(report.rule_set, "landslide");
+        assert_eq!(report.points.len(), 2);
+    }
+
+    /// Tests sweeping over nothing
+    #[test]
+    fn test_empty_sweep() {
+        let space = SweepSpace { max_turns: vec![], ..SweepSpace::single(&RoundConfig::default()) };
+        assert_eq!(sweep(space, Sampling::Grid).run().unwrap_err(), SweepError::NoValues("max_turns"));
+        let space = SweepSpace { zone_max_tiles: vec![30], ..SweepSpace::single(&RoundConfig::default()) };
+        assert_eq!(sweep(space, Sampling::Grid).run().unwrap_err(), SweepError::NoValidPoints);
+    }
+
+    /// Tests that a rule script failing mid-game stops the sweep instead of counting a forfeit
+    #[test]
+    fn test_failing_rules() {
+        let script = ScriptedRuleSet::from_source("broken.rhai", "fn check_outcome(round) { throw \"no outcome\"; }", &ScriptLimits::default()).unwrap();
+        let config = SweepConfig { games: 2, threads: 1, ..SweepConfig::default() };
+        let sweep = Sweep::with_rules(config, Rules::new(script), builtin_player("greedy").unwrap(), builtin_player("random").unwrap());
+        assert!(matches!(sweep.run(), Err(SweepError::RulesFailed(error)) if error.rule_set == "broken.rhai"));
+    }
+}